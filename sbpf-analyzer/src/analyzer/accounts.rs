@@ -0,0 +1,146 @@
+use crate::analyzer::anchor::AnchorInfo;
+use crate::analyzer::lints::{AccessKind, FieldAccess, LintContext};
+use sbpf_common::dispatch::Dispatch;
+use sbpf_common::input::AccountField;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Fields of one account read and written by a handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountAccess {
+    pub index: u16,
+    /// Fields loaded, compared or passed to a call
+    pub reads: BTreeSet<AccountField>,
+    /// Fields stored to
+    pub writes: BTreeSet<AccountField>,
+}
+
+/// Accounts used by one instruction handler
+#[derive(Debug, Clone)]
+pub struct HandlerAccounts {
+    /// Instruction name, `handle_ix_<tag>` for native dispatch
+    pub name: String,
+    /// Address of the handler function or inlined handler block
+    pub handler: u64,
+    /// Accounts sorted by index
+    pub accounts: Vec<AccountAccess>,
+}
+
+/// Map account field accesses to the instruction handlers that perform them.
+/// Programs without a recognized dispatch are reported as a single `entry`
+/// handler.
+pub fn analyze(
+    ctx: &LintContext,
+    anchor: &AnchorInfo,
+    dispatch: Option<&Dispatch>,
+    entry: u64,
+) -> Vec<HandlerAccounts> {
+    let handlers: Vec<(String, u64)> = if anchor.is_anchor {
+        anchor
+            .instructions
+            .iter()
+            .map(|ix| (ix.display_name(), ix.handler))
+            .collect()
+    } else if let Some(dispatch) = dispatch {
+        dispatch
+            .cases
+            .iter()
+            .map(|case| (Dispatch::handler_name(case.tag), case.handler))
+            .collect()
+    } else {
+        vec![("entry".to_string(), entry)]
+    };
+
+    handlers
+        .into_iter()
+        .map(|(name, handler)| HandlerAccounts {
+            accounts: handler_accesses(ctx, handler),
+            name,
+            handler,
+        })
+        .collect()
+}
+
+/// Accounts accessed by a handler, with the fields read and written
+fn handler_accesses(ctx: &LintContext, handler: u64) -> Vec<AccountAccess> {
+    let mut accounts: BTreeMap<u16, AccountAccess> = BTreeMap::new();
+    for access in handler_field_accesses(ctx, handler) {
+        let account = accounts.entry(access.account).or_insert(AccountAccess {
+            index: access.account,
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+        });
+        match access.kind {
+            AccessKind::Write => account.writes.insert(access.field),
+            AccessKind::Read | AccessKind::Compared | AccessKind::Passed => account.reads.insert(access.field),
+        };
+    }
+    accounts.into_values().collect()
+}
+
+/// Accesses made by a handler function and its callees, or by the blocks
+/// reachable from an inlined handler and their callees
+pub fn handler_field_accesses<'a>(ctx: &'a LintContext, handler: u64) -> Vec<&'a FieldAccess> {
+    let cfg = ctx.cfg;
+    let mut functions = ctx.call_tree(handler);
+    let mut blocks: BTreeSet<usize> = BTreeSet::new();
+    if functions.is_empty() {
+        let function = cfg.block_id_at(handler).and_then(|id| {
+            cfg.functions.iter().find(|f| f.blocks.contains(&id)).map(|f| (id, f))
+        });
+        if let Some((start, function)) = function {
+            let mut stack = vec![start];
+            while let Some(id) = stack.pop() {
+                if !function.blocks.contains(&id) || !blocks.insert(id) {
+                    continue;
+                }
+                stack.extend(cfg.blocks[id].successors.iter().copied());
+                for inst in &ctx.instructions[cfg.blocks[id].instructions.clone()] {
+                    if let Some(target) = inst.call_target() {
+                        functions.extend(ctx.call_tree(target));
+                    }
+                }
+            }
+        }
+    }
+
+    ctx.field_accesses
+        .iter()
+        .filter(|access| {
+            functions.contains(&access.function)
+                || cfg.block_containing(access.address).is_some_and(|id| blocks.contains(&id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_entry_accesses() {
+        let program: Vec<u8> = [
+            insn(0x71, 2, 1, 9, 0),    // ldxb r2, [r1+9]      account 0 is_signer
+            insn(0x79, 3, 1, 80, 0),   // ldxdw r3, [r1+80]    account 0 lamports
+            insn(0x7b, 1, 3, 80, 0),   // stxdw [r1+80], r3
+            insn(0x72, 1, 0, 96, 1),   // stb [r1+96], 1       account 0 data
+            insn(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let handlers = analyze(&ctx, &AnchorInfo::default(), None, 0);
+
+        assert_eq!(handlers.len(), 1);
+        assert_eq!(handlers[0].name, "entry");
+        let account = &handlers[0].accounts[0];
+        assert_eq!(account.index, 0);
+        assert_eq!(account.reads, BTreeSet::from([AccountField::IsSigner, AccountField::Lamports]));
+        assert_eq!(account.writes, BTreeSet::from([AccountField::Lamports, AccountField::Data]));
+    }
+}
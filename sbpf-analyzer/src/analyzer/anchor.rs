@@ -0,0 +1,324 @@
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::binary::SbpfBinary;
+use sbpf_common::cfg::{dominators::reverse_postorder, ControlFlowGraph, Function};
+use sbpf_common::dataflow::{Dataflow, Value};
+use sbpf_common::dispatch::{instruction_data_records, is_instruction_data, resolve_handler};
+use sbpf_common::hash::sha256;
+use sbpf_common::instruction::Instruction;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Instruction recovered from an Anchor dispatcher
+#[derive(Debug, Clone)]
+pub struct AnchorInstruction {
+    /// First 8 bytes of the instruction data selecting this instruction
+    pub discriminator: [u8; 8],
+    /// Instruction name, if a candidate name hashes to the discriminator
+    pub name: Option<String>,
+    /// Address of the function containing the comparison
+    pub dispatcher: u64,
+    /// Address of the comparison against the discriminator
+    pub compare: u64,
+    /// Address of the handler function, or of the matching branch when the
+    /// handler is inlined into the dispatcher
+    pub handler: u64,
+    /// Name of the handler function, if it is a separate function
+    pub handler_name: Option<String>,
+}
+
+impl AnchorInstruction {
+    /// Instruction name, or `ix_<discriminator hex>` when no name matched
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let hex: String = self.discriminator.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("ix_{}", hex)
+        })
+    }
+}
+
+/// Anchor framework detection results
+#[derive(Debug, Clone, Default)]
+pub struct AnchorInfo {
+    /// Whether the program follows the Anchor dispatch pattern
+    pub is_anchor: bool,
+    /// Dispatched instructions, sorted by discriminator
+    pub instructions: Vec<AnchorInstruction>,
+}
+
+/// Anchor's 8-byte discriminator: `sha256("<namespace>:<name>")[..8]`
+pub fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let digest = sha256(format!("{}:{}", namespace, name).as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&digest[..8]);
+    discriminator
+}
+
+/// Name candidates: identifiers found in read-only data (such as Anchor's
+/// `Instruction: <Name>` log messages), as written and in snake case, and
+/// the supplied wordlist
+pub fn candidate_names(binary: &SbpfBinary, wordlist: &[String]) -> BTreeSet<String> {
+    let mut names: BTreeSet<String> = wordlist.iter().map(|w| w.trim().to_string()).collect();
+    for (_, text) in binary.rodata_strings() {
+        for token in text.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+            if token.len() >= 3 && token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                names.insert(token.to_string());
+                names.insert(to_snake_case(token));
+            }
+        }
+    }
+    names.remove("");
+    names
+}
+
+/// Convert `InitializeVault` to `initialize_vault`, as Anchor does for
+/// instruction names
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let chars: Vec<char> = name.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let prev_lower = i > 0 && (chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit());
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            let prev_upper = i > 0 && chars[i - 1].is_ascii_uppercase();
+            if prev_lower || (prev_upper && next_lower) {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// What a register is known to hold within a straight-line region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegValue {
+    /// Result of an 8-byte load
+    Load64,
+    /// 64-bit constant
+    Const(u64),
+}
+
+/// Find Anchor's discriminator comparisons and resolve their handlers; a
+/// program is Anchor when a discriminator matches a known name or when
+/// several are compared against the start of the instruction data
+pub fn analyze(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    dataflow: &Dataflow,
+    names: &BTreeSet<String>,
+) -> AnchorInfo {
+    let known: HashMap<[u8; 8], String> = names
+        .iter()
+        .map(|name| (sighash("global", name), name.clone()))
+        .chain(std::iter::once((sighash("anchor", "idl"), "idl".to_string())))
+        .collect();
+
+    let on_data = instruction_data_compares(cfg, instructions, dataflow);
+
+    let mut found: HashMap<[u8; 8], AnchorInstruction> = HashMap::new();
+    for function in cfg.functions.iter().filter(|f| !f.library) {
+        for (compare, discriminator, branch) in discriminator_compares(cfg, function, instructions) {
+            let (handler, handler_name) = resolve_handler(cfg, branch, instructions);
+            let candidate = AnchorInstruction {
+                discriminator,
+                name: known.get(&discriminator).cloned(),
+                dispatcher: function.address,
+                compare,
+                handler,
+                handler_name,
+            };
+            found
+                .entry(discriminator)
+                .and_modify(|existing| {
+                    if candidate.compare < existing.compare {
+                        *existing = candidate.clone();
+                    }
+                })
+                .or_insert(candidate);
+        }
+    }
+
+    let mut instructions: Vec<AnchorInstruction> = found.into_values().collect();
+    instructions.sort_by_key(|i| i.discriminator);
+    let is_anchor = instructions.iter().any(|i| i.name.is_some())
+        || instructions.iter().filter(|i| on_data.contains(&i.compare)).count() >= 2;
+    if !is_anchor {
+        instructions.clear();
+    }
+    AnchorInfo {
+        is_anchor,
+        instructions,
+    }
+}
+
+/// Equality comparisons between an 8-byte load and a 64-bit constant, as
+/// (compare address, discriminator, block taken on a match)
+pub(crate) fn discriminator_compares(
+    cfg: &ControlFlowGraph,
+    function: &Function,
+    instructions: &[Instruction],
+) -> Vec<(u64, [u8; 8], usize)> {
+    let mut compares = Vec::new();
+    let mut exit_states: HashMap<usize, [Option<RegValue>; 11]> = HashMap::new();
+
+    for id in reverse_postorder(cfg, function.entry) {
+        let block = &cfg.blocks[id];
+        // Register values only flow along edges into single-predecessor blocks
+        let mut regs = match block.predecessors.as_slice() {
+            [pred] => exit_states.get(pred).copied().unwrap_or([None; 11]),
+            _ => [None; 11],
+        };
+
+        for inst in &instructions[block.instructions.clone()] {
+            if matches!(inst.opcode, Opcode::Jeq | Opcode::Jne) && inst.uses_src_reg() && !inst.is_alu32() {
+                let pair = (regs.get(inst.dst_reg as usize), regs.get(inst.src_reg as usize));
+                let constant = match pair {
+                    (Some(Some(RegValue::Load64)), Some(Some(RegValue::Const(c))))
+                    | (Some(Some(RegValue::Const(c))), Some(Some(RegValue::Load64))) => Some(*c),
+                    _ => None,
+                };
+                if let Some(c) = constant.filter(|&c| is_discriminator_like(c)) {
+                    // Successors are ordered target first, then fallthrough
+                    let taken = match inst.opcode {
+                        Opcode::Jeq => block.successors.first(),
+                        _ => block.successors.get(1),
+                    };
+                    if let Some(&branch) = taken {
+                        compares.push((inst.address, c.to_le_bytes(), branch));
+                    }
+                }
+            }
+
+            let value = match inst.opcode {
+                Opcode::Ldx if inst.mem_size() == Some(8) => Some(RegValue::Load64),
+                Opcode::Lddw => Some(RegValue::Const(inst.imm as u64)),
+                Opcode::Mov if !inst.uses_src_reg() && !inst.is_alu32() => Some(RegValue::Const(inst.imm as u64)),
+                Opcode::Mov if !inst.is_alu32() => regs.get(inst.src_reg as usize).copied().flatten(),
+                _ => None,
+            };
+            for reg in inst.written_registers() {
+                if let Some(slot) = regs.get_mut(reg as usize) {
+                    *slot = None;
+                }
+            }
+            if let (Some(value), Some(slot)) = (value, regs.get_mut(inst.dst_reg as usize)) {
+                *slot = Some(value);
+            }
+        }
+        exit_states.insert(id, regs);
+    }
+    compares
+}
+
+/// Addresses of register comparisons against an 8-byte load from the start
+/// of the instruction data
+fn instruction_data_compares(cfg: &ControlFlowGraph, instructions: &[Instruction], dataflow: &Dataflow) -> HashSet<u64> {
+    let mut compares = HashSet::new();
+    let records = instruction_data_records(cfg, instructions, dataflow);
+    for function in dataflow.functions.iter().filter(|f| !cfg.functions[f.index].library) {
+        function.visit(cfg, instructions, |_, inst, state| {
+            if !matches!(inst.opcode, Opcode::Jeq | Opcode::Jne) || !inst.uses_src_reg() {
+                return;
+            }
+            let on_data = [inst.dst_reg, inst.src_reg]
+                .iter()
+                .any(|&reg| matches!(state.reg(reg), Value::Loaded(loc, 8) if is_instruction_data(loc, &records)));
+            if on_data {
+                compares.insert(inst.address);
+            }
+        });
+    }
+    compares
+}
+
+/// Discriminators are hash prefixes and use the full 64 bits
+fn is_discriminator_like(value: u64) -> bool {
+    value > u32::MAX as u64 && value.leading_ones() < 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    /// `lddw dst, value` as two slots
+    fn lddw(dst: u8, value: [u8; 8]) -> [[u8; 8]; 2] {
+        let value = u64::from_le_bytes(value);
+        [
+            insn(0x18, dst, 0, 0, value as u32 as i32),
+            insn(0x00, 0, 0, 0, (value >> 32) as u32 as i32),
+        ]
+    }
+
+    #[test]
+    fn test_recover_dispatch() {
+        let [init_lo, init_hi] = lddw(3, sighash("global", "initialize"));
+        let [other_lo, other_hi] = lddw(3, sighash("global", "withdraw_all"));
+        let program: Vec<u8> = [
+            insn(0x79, 2, 1, 0, 0),  // 0x00: ldxdw r2, [r1+0]
+            init_lo, init_hi,        // 0x08: lddw r3, sighash(initialize)
+            insn(0x1d, 2, 3, 4, 0),  // 0x18: jeq r2, r3, +4
+            other_lo, other_hi,      // 0x20: lddw r3, sighash(withdraw_all)
+            insn(0x5d, 2, 3, 2, 0),  // 0x30: jne r2, r3, +2
+            insn(0x85, 0, 1, 0, 2),  // 0x38: call 0x50
+            insn(0x95, 0, 0, 0, 0),  // 0x40: exit
+            insn(0x95, 0, 0, 0, 0),  // 0x48: exit
+            insn(0xb7, 0, 0, 0, 0),  // 0x50: mov r0, 0
+            insn(0x95, 0, 0, 0, 0),  // 0x58: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let names = BTreeSet::from(["initialize".to_string()]);
+        let info = analyze(&cfg, &instructions, &dataflow, &names);
+
+        assert!(info.is_anchor);
+        assert_eq!(info.instructions.len(), 2);
+        let init = info.instructions.iter().find(|i| i.name.is_some()).unwrap();
+        assert_eq!(init.name.as_deref(), Some("initialize"));
+        assert_eq!(init.handler, 0x40);
+        let other = info.instructions.iter().find(|i| i.name.is_none()).unwrap();
+        assert_eq!(other.handler, 0x50);
+        assert_eq!(other.handler_name.as_deref(), Some("func_0x50"));
+    }
+
+    #[test]
+    fn test_unnamed_compares_need_instruction_data() {
+        let [first_lo, first_hi] = lddw(3, sighash("global", "deposit"));
+        let [second_lo, second_hi] = lddw(3, sighash("global", "withdraw"));
+        let compares = |base: u8| -> Vec<u8> {
+            [
+                insn(0x79, 2, base, 0, 0), // 0x00: ldxdw r2, [base+0]
+                first_lo, first_hi,        // 0x08: lddw r3, sighash(deposit)
+                insn(0x1d, 2, 3, 3, 0),    // 0x18: jeq r2, r3, +3
+                second_lo, second_hi,      // 0x20: lddw r3, sighash(withdraw)
+                insn(0x1d, 2, 3, 1, 0),    // 0x30: jeq r2, r3, +1
+                insn(0x95, 0, 0, 0, 0),    // 0x38: exit
+                insn(0x95, 0, 0, 0, 0),    // 0x40: exit
+            ]
+            .concat()
+        };
+        let detect = |program: Vec<u8>| {
+            let instructions = InstructionDecoder::new(None).decode_all(&program);
+            let cfg = ControlFlowGraph::build(&instructions, 0);
+            let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+            analyze(&cfg, &instructions, &dataflow, &BTreeSet::new()).is_anchor
+        };
+
+        // Loaded from the instruction data argument
+        assert!(detect(compares(4)));
+        // Loaded from the start of the input buffer
+        assert!(!detect(compares(1)));
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(to_snake_case("InitializeVault"), "initialize_vault");
+        assert_eq!(to_snake_case("SetURIForToken"), "set_uri_for_token");
+        assert_eq!(to_snake_case("withdraw"), "withdraw");
+    }
+}
@@ -0,0 +1,413 @@
+use crate::analyzer::lints::LintContext;
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::binary::SbpfBinary;
+use sbpf_common::dataflow::{Location, State, Value};
+use sbpf_common::input::AccountField;
+use sbpf_common::pubkey::{Pubkey, PUBKEY_SIZE};
+use sbpf_common::syscalls::{is_invoke, syscall_name};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Offset of the instruction data pointer in both `SolInstruction` and the
+/// Rust `StableInstruction`
+const DATA_PTR_OFFSET: i64 = 24;
+
+/// Offset of the instruction data length in the C `SolInstruction`
+const C_DATA_LEN_OFFSET: i64 = 32;
+
+/// Offset of the instruction data length in the Rust `StableInstruction`
+const RUST_DATA_LEN_OFFSET: i64 = 40;
+
+/// Offset of the inline program id in the Rust `StableInstruction`
+const RUST_PROGRAM_ID_OFFSET: i64 = 48;
+
+/// Longest instruction data prefix recovered for unknown programs, enough
+/// for an Anchor discriminator
+const MAX_DISCRIMINATOR_SIZE: usize = 8;
+
+/// Instructions of a program commonly invoked through CPI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InstructionSet {
+    /// Width of the little-endian instruction tag
+    tag_size: usize,
+    /// Instruction names indexed by tag
+    instructions: &'static [&'static str],
+    /// Instruction selected by empty instruction data
+    empty: Option<&'static str>,
+}
+
+impl InstructionSet {
+    /// Name of the instruction selected by the leading data bytes
+    fn instruction(&self, data: &[u8]) -> Option<&'static str> {
+        let tag = data.get(..self.tag_size)?;
+        let tag = tag.iter().rev().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        self.instructions.get(tag).copied()
+    }
+}
+
+const TOKEN_INSTRUCTIONS: &[&str] = &[
+    "InitializeMint",
+    "InitializeAccount",
+    "InitializeMultisig",
+    "Transfer",
+    "Approve",
+    "Revoke",
+    "SetAuthority",
+    "MintTo",
+    "Burn",
+    "CloseAccount",
+    "FreezeAccount",
+    "ThawAccount",
+    "TransferChecked",
+    "ApproveChecked",
+    "MintToChecked",
+    "BurnChecked",
+    "InitializeAccount2",
+    "SyncNative",
+    "InitializeAccount3",
+    "InitializeMultisig2",
+    "InitializeMint2",
+    "GetAccountDataSize",
+    "InitializeImmutableOwner",
+    "AmountToUiAmount",
+    "UiAmountToAmount",
+];
+
+/// Instruction sets of programs recognized as CPI targets, by base58
+/// program id; their names come from [`Pubkey::name`]
+const KNOWN_INSTRUCTIONS: &[(&str, InstructionSet)] = &[
+    (
+        "11111111111111111111111111111111",
+        InstructionSet {
+            tag_size: 4,
+            instructions: &[
+                "CreateAccount",
+                "Assign",
+                "Transfer",
+                "CreateAccountWithSeed",
+                "AdvanceNonceAccount",
+                "WithdrawNonceAccount",
+                "InitializeNonceAccount",
+                "AuthorizeNonceAccount",
+                "Allocate",
+                "AllocateWithSeed",
+                "AssignWithSeed",
+                "TransferWithSeed",
+                "UpgradeNonceAccount",
+            ],
+            empty: None,
+        },
+    ),
+    (
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+        InstructionSet {
+            tag_size: 1,
+            instructions: TOKEN_INSTRUCTIONS,
+            empty: None,
+        },
+    ),
+    (
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        InstructionSet {
+            tag_size: 1,
+            instructions: TOKEN_INSTRUCTIONS,
+            empty: None,
+        },
+    ),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        InstructionSet {
+            tag_size: 1,
+            instructions: &["Create", "CreateIdempotent", "RecoverNested"],
+            // An empty instruction creates an associated token account
+            empty: Some("Create"),
+        },
+    ),
+];
+
+/// Instruction set of the program `id`, if it is a known CPI target
+fn instruction_set(id: &Pubkey) -> Option<&'static InstructionSet> {
+    static SETS: OnceLock<HashMap<Pubkey, InstructionSet>> = OnceLock::new();
+    SETS.get_or_init(|| {
+        KNOWN_INSTRUCTIONS
+            .iter()
+            .filter_map(|&(address, set)| Some((Pubkey::from_base58(address)?, set)))
+            .collect()
+    })
+    .get(id)
+}
+
+/// Program invoked by a CPI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpiProgram {
+    /// Constant program id
+    Constant(Pubkey),
+    /// Key of an account passed to the program
+    Account(u16),
+    Unknown,
+}
+
+impl fmt::Display for CpiProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpiProgram::Constant(id) => match id.name() {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "{}", id),
+            },
+            CpiProgram::Account(index) => write!(f, "accounts[{}].key", index),
+            CpiProgram::Unknown => write!(f, "<unknown>"),
+        }
+    }
+}
+
+/// Signer seeds passed to `sol_invoke_signed_*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerSeeds {
+    /// Plain `invoke`, no PDA signs
+    Absent,
+    /// Seeds for this many PDA signers
+    Present(u64),
+    Unknown,
+}
+
+impl fmt::Display for SignerSeeds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignerSeeds::Absent => write!(f, "none"),
+            SignerSeeds::Present(n) => write!(f, "{}", n),
+            SignerSeeds::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// A cross-program invocation site
+#[derive(Debug, Clone)]
+pub struct CpiSite {
+    /// Address of the syscall
+    pub address: u64,
+    /// Address of the containing function
+    pub function: u64,
+    pub function_name: String,
+    pub syscall: &'static str,
+    pub program: CpiProgram,
+    /// Leading instruction data bytes known statically: the tag for known
+    /// programs, up to 8 bytes otherwise
+    pub discriminator: Option<Vec<u8>>,
+    /// Instruction name, for known programs
+    pub instruction: Option<&'static str>,
+    /// Length of the instruction data, if constant
+    pub data_len: Option<u64>,
+    pub signer_seeds: SignerSeeds,
+}
+
+/// Recover the target, instruction and signers of every CPI
+pub fn analyze(ctx: &LintContext, binary: &SbpfBinary) -> Vec<CpiSite> {
+    let mut sites = Vec::new();
+    for function in &ctx.dataflow.functions {
+        let cfg_function = &ctx.cfg.functions[function.index];
+        if cfg_function.library {
+            continue;
+        }
+
+        // Narrow stack stores by offset, which the dataflow only tracks at
+        // 8-byte granularity
+        let mut stores: BTreeMap<i64, (u8, Value)> = BTreeMap::new();
+        function.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+            if matches!(inst.opcode, Opcode::St | Opcode::Stx) {
+                if let Some(Location::Stack(off)) = state.memory_location(inst) {
+                    let size = inst.mem_size().unwrap_or(8);
+                    stores.retain(|&slot, (len, _)| slot + *len as i64 <= off || slot >= off + size as i64);
+                    let value = match inst.opcode {
+                        Opcode::St => Value::Const(inst.imm as u64),
+                        _ => state.reg(inst.src_reg),
+                    };
+                    stores.insert(off, (size, value));
+                }
+                return;
+            }
+
+            let Some(syscall) = inst.syscall_number().filter(|n| is_invoke(*n)).and_then(syscall_name) else {
+                return;
+            };
+            let c_abi = syscall == "sol_invoke_signed_c";
+            let (program, data_ptr, data_len) = match state.reg(1).location() {
+                Some(Location::Stack(ix)) => {
+                    let len_offset = if c_abi { C_DATA_LEN_OFFSET } else { RUST_DATA_LEN_OFFSET };
+                    (
+                        program_id(state, Some(binary), ix, c_abi),
+                        state.stack_slot(ix + DATA_PTR_OFFSET),
+                        match state.stack_slot(ix + len_offset) {
+                            Value::Const(len) => Some(len),
+                            _ => None,
+                        },
+                    )
+                }
+                _ => (CpiProgram::Unknown, Value::Unknown, None),
+            };
+
+            let known = match &program {
+                CpiProgram::Constant(id) => instruction_set(id),
+                _ => None,
+            };
+            let limit = known.map_or(MAX_DISCRIMINATOR_SIZE, |set| set.tag_size);
+            let limit = data_len.map_or(limit, |len| limit.min(len as usize));
+            let discriminator = leading_bytes(data_ptr, &stores, binary, limit).filter(|d| !d.is_empty());
+            let instruction = match (known, &discriminator) {
+                (Some(set), Some(data)) => set.instruction(data),
+                (Some(set), None) if data_len == Some(0) => set.empty,
+                _ => None,
+            };
+
+            let signer_seeds = match (state.reg(4), state.reg(5)) {
+                (Value::Const(0), _) | (_, Value::Const(0)) => SignerSeeds::Absent,
+                (_, Value::Const(n)) => SignerSeeds::Present(n),
+                _ => SignerSeeds::Unknown,
+            };
+
+            sites.push(CpiSite {
+                address: inst.address,
+                function: cfg_function.address,
+                function_name: cfg_function.name.clone(),
+                syscall,
+                program,
+                discriminator,
+                instruction,
+                data_len,
+                signer_seeds,
+            });
+        });
+    }
+    sites.sort_by_key(|s| s.address);
+    sites
+}
+
+/// Program id of the instruction at stack offset `ix`. The C ABI stores a
+/// pointer to it, the Rust ABI stores it inline. Ids in read-only data are
+/// only resolved given the `binary`.
+pub fn program_id(state: &State, binary: Option<&SbpfBinary>, ix: i64, c_abi: bool) -> CpiProgram {
+    let from_rodata = |address: u64| {
+        binary
+            .and_then(|binary| binary.read_rodata(address, PUBKEY_SIZE))
+            .and_then(Pubkey::from_slice)
+            .map_or(CpiProgram::Unknown, CpiProgram::Constant)
+    };
+    let from_location = |loc: Location| match loc {
+        Location::Absolute(address) => from_rodata(address),
+        _ => match loc.account_field() {
+            Some((account, AccountField::Key)) => CpiProgram::Account(account),
+            _ => CpiProgram::Unknown,
+        },
+    };
+
+    if c_abi {
+        return match state.stack_slot(ix) {
+            Value::Const(address) => from_rodata(address),
+            Value::Pointer(loc) => from_location(loc),
+            _ => CpiProgram::Unknown,
+        };
+    }
+
+    let words: Vec<Value> = (0..4)
+        .map(|i| state.stack_slot(ix + RUST_PROGRAM_ID_OFFSET + i * 8))
+        .collect();
+    if words.iter().all(|w| matches!(w, Value::Const(_))) {
+        let mut id = [0u8; PUBKEY_SIZE];
+        for (chunk, word) in id.chunks_mut(8).zip(&words) {
+            if let Value::Const(c) = word {
+                chunk.copy_from_slice(&c.to_le_bytes());
+            }
+        }
+        return CpiProgram::Constant(Pubkey(id));
+    }
+    // Copied word by word from one place
+    match words[0] {
+        Value::Loaded(start, 8)
+            if (1..4).all(|i| words[i] == Value::Loaded(start.offset_by(i as i64 * 8), 8)) =>
+        {
+            from_location(start)
+        }
+        _ => CpiProgram::Unknown,
+    }
+}
+
+/// Up to `limit` constant bytes at the start of the instruction data
+fn leading_bytes(
+    data: Value,
+    stores: &BTreeMap<i64, (u8, Value)>,
+    binary: &SbpfBinary,
+    limit: usize,
+) -> Option<Vec<u8>> {
+    match data {
+        Value::Const(address) => binary.read_rodata(address, limit).map(<[u8]>::to_vec),
+        Value::Pointer(Location::Absolute(address)) => binary.read_rodata(address, limit).map(<[u8]>::to_vec),
+        Value::Pointer(Location::Stack(mut off)) => {
+            let mut bytes = Vec::new();
+            while bytes.len() < limit {
+                let Some(&(size, Value::Const(c))) = stores.get(&off) else {
+                    break;
+                };
+                bytes.extend_from_slice(&c.to_le_bytes()[..size as usize]);
+                off += size as i64;
+            }
+            bytes.truncate(limit);
+            Some(bytes)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::instruction::InstructionDecoder;
+    use sbpf_common::syscalls::syscall_hash;
+
+    #[test]
+    fn test_system_transfer_with_seeds() {
+        let invoke = syscall_hash("sol_invoke_signed_rust") as u32 as i32;
+        let program: Vec<u8> = [
+            insn(0x62, 10, 0, -8, 2),    // stw [r10-8], 2        Transfer
+            insn(0x7a, 10, 0, -48, 0),   // stdw [r10-48], 0      program id
+            insn(0x7a, 10, 0, -40, 0),   // stdw [r10-40], 0
+            insn(0x7a, 10, 0, -32, 0),   // stdw [r10-32], 0
+            insn(0x7a, 10, 0, -24, 0),   // stdw [r10-24], 0
+            insn(0xbf, 2, 10, 0, 0),     // mov r2, r10
+            insn(0x07, 2, 0, 0, -8),     // add r2, -8
+            insn(0x7b, 10, 2, -72, 0),   // stxdw [r10-72], r2    data pointer
+            insn(0x7a, 10, 0, -56, 12),  // stdw [r10-56], 12     data length
+            insn(0xbf, 1, 10, 0, 0),     // mov r1, r10
+            insn(0x07, 1, 0, 0, -96),    // add r1, -96
+            insn(0xb7, 5, 0, 0, 1),      // mov r5, 1
+            insn(0x85, 0, 0, 0, invoke), // call sol_invoke_signed_rust
+            insn(0x95, 0, 0, 0, 0),      // exit
+        ]
+        .concat();
+        let binary = SbpfBinary {
+            bytecode: program.clone(),
+            entry_point: 0,
+            text_address: 0,
+            text_offset: 0,
+            version: None,
+            metadata: Vec::new(),
+            symbols: Vec::new(),
+        };
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let sites = analyze(&ctx, &binary);
+
+        assert_eq!(sites.len(), 1);
+        let site = &sites[0];
+        assert_eq!(site.address, 0x60);
+        assert!(matches!(site.program, CpiProgram::Constant(id) if id.name() == Some("System Program")));
+        assert_eq!(site.discriminator.as_deref(), Some(&[2, 0, 0, 0][..]));
+        assert_eq!(site.instruction, Some("Transfer"));
+        assert_eq!(site.data_len, Some(12));
+        assert_eq!(site.signer_seeds, SignerSeeds::Present(1));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::analyzer::lints::Severity;
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::instruction::{Instruction, InstructionCategory};
+use sbpf_common::syscalls::syscall_name;
+use std::collections::HashSet;
+
+/// Stage that produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCategory {
+    /// Problems decoding the bytecode
+    Decode,
+    /// Checks the runtime verifier would reject the program for
+    Verifier,
+}
+
+/// Kind of decode or verifier problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    UnknownOpcode,
+    TruncatedInstruction,
+    InvalidRegister,
+    JumpOutOfBounds,
+    CallOutOfBounds,
+    WriteToFramePointer,
+    DivisionByZero,
+    UnknownSyscall,
+    MissingExit,
+}
+
+impl DiagnosticCode {
+    /// Every diagnostic code
+    pub const ALL: &'static [DiagnosticCode] = &[
+        DiagnosticCode::UnknownOpcode,
+        DiagnosticCode::TruncatedInstruction,
+        DiagnosticCode::InvalidRegister,
+        DiagnosticCode::JumpOutOfBounds,
+        DiagnosticCode::CallOutOfBounds,
+        DiagnosticCode::WriteToFramePointer,
+        DiagnosticCode::DivisionByZero,
+        DiagnosticCode::UnknownSyscall,
+        DiagnosticCode::MissingExit,
+    ];
+
+    /// Short kebab-case identifier
+    pub fn id(&self) -> &'static str {
+        match self {
+            DiagnosticCode::UnknownOpcode => "unknown-opcode",
+            DiagnosticCode::TruncatedInstruction => "truncated-instruction",
+            DiagnosticCode::InvalidRegister => "invalid-register",
+            DiagnosticCode::JumpOutOfBounds => "jump-out-of-bounds",
+            DiagnosticCode::CallOutOfBounds => "call-out-of-bounds",
+            DiagnosticCode::WriteToFramePointer => "write-to-frame-pointer",
+            DiagnosticCode::DivisionByZero => "division-by-zero",
+            DiagnosticCode::UnknownSyscall => "unknown-syscall",
+            DiagnosticCode::MissingExit => "missing-exit",
+        }
+    }
+
+    /// One-line description of the problem
+    pub fn description(&self) -> &'static str {
+        match self {
+            DiagnosticCode::UnknownOpcode => "Instruction opcode is not a valid SBPF opcode",
+            DiagnosticCode::TruncatedInstruction => "Bytecode ends in the middle of an instruction",
+            DiagnosticCode::InvalidRegister => "Instruction references a register above r10",
+            DiagnosticCode::JumpOutOfBounds => "Jump target is not the start of an instruction",
+            DiagnosticCode::CallOutOfBounds => "Call target is not the start of an instruction",
+            DiagnosticCode::WriteToFramePointer => "Instruction writes to the read-only frame pointer r10",
+            DiagnosticCode::DivisionByZero => "Division or remainder by a constant zero",
+            DiagnosticCode::UnknownSyscall => "Call to a syscall the runtime does not register",
+            DiagnosticCode::MissingExit => "Execution can run past the last instruction",
+        }
+    }
+
+    /// Stage the diagnostic belongs to
+    pub fn category(&self) -> DiagnosticCategory {
+        match self {
+            DiagnosticCode::UnknownOpcode | DiagnosticCode::TruncatedInstruction => {
+                DiagnosticCategory::Decode
+            }
+            _ => DiagnosticCategory::Verifier,
+        }
+    }
+
+    /// Severity of the diagnostic
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticCode::UnknownSyscall => Severity::Low,
+            DiagnosticCode::DivisionByZero | DiagnosticCode::TruncatedInstruction => {
+                Severity::Medium
+            }
+            _ => Severity::High,
+        }
+    }
+}
+
+/// A decode or verifier problem at a bytecode address
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    /// Address of the offending instruction
+    pub address: u64,
+    /// Size of the offending instruction, or of the trailing bytes, in bytes
+    pub size: usize,
+    pub message: String,
+}
+
+/// Check the decoded program for decode and verifier problems
+pub fn analyze(bytecode: &[u8], instructions: &[Instruction]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let starts: HashSet<u64> = instructions.iter().map(|i| i.address).collect();
+
+    let mut push = |code: DiagnosticCode, address: u64, size: usize, message: String| {
+        diagnostics.push(Diagnostic { code, address, size, message });
+    };
+
+    for inst in instructions {
+        if let Opcode::Unknown(byte) = inst.opcode {
+            push(
+                DiagnosticCode::UnknownOpcode,
+                inst.address,
+                inst.size,
+                format!("unknown opcode 0x{:02x}", byte),
+            );
+            continue;
+        }
+
+        if inst.dst_reg > 10 || inst.src_reg > 10 {
+            push(
+                DiagnosticCode::InvalidRegister,
+                inst.address,
+                inst.size,
+                format!("invalid register in r{}, r{}", inst.dst_reg, inst.src_reg),
+            );
+        }
+
+        if inst.written_registers().contains(&10) && inst.category() != InstructionCategory::ControlFlow {
+            push(
+                DiagnosticCode::WriteToFramePointer,
+                inst.address,
+                inst.size,
+                "frame pointer r10 is overwritten".to_string(),
+            );
+        }
+
+        if let Some(target) = inst.jump_target() {
+            if !starts.contains(&target) {
+                push(
+                    DiagnosticCode::JumpOutOfBounds,
+                    inst.address,
+                inst.size,
+                    format!("jump to 0x{:x} which is not an instruction", target),
+                );
+            }
+        }
+
+        if let Some(target) = inst.call_target() {
+            if !starts.contains(&target) {
+                push(
+                    DiagnosticCode::CallOutOfBounds,
+                    inst.address,
+                inst.size,
+                    format!("call to 0x{:x} which is not an instruction", target),
+                );
+            }
+        }
+
+        if matches!(inst.opcode, Opcode::Div | Opcode::Mod) && !inst.uses_src_reg() && inst.imm == 0 {
+            push(
+                DiagnosticCode::DivisionByZero,
+                inst.address,
+                inst.size,
+                "division by constant zero".to_string(),
+            );
+        }
+
+        if let Some(num) = inst.syscall_number() {
+            if syscall_name(num).is_none() {
+                push(
+                    DiagnosticCode::UnknownSyscall,
+                    inst.address,
+                inst.size,
+                    format!("unknown syscall 0x{:08x}", num),
+                );
+            }
+        }
+    }
+
+    let decoded_end = instructions.last().map(|i| i.next_address()).unwrap_or(0);
+    if decoded_end < bytecode.len() as u64 {
+        push(
+            DiagnosticCode::TruncatedInstruction,
+            decoded_end,
+            (bytecode.len() as u64 - decoded_end) as usize,
+            format!("{} trailing bytes do not form an instruction", bytecode.len() as u64 - decoded_end),
+        );
+    }
+
+    if let Some(last) = instructions.last() {
+        if !last.is_terminator() {
+            push(
+                DiagnosticCode::MissingExit,
+                last.address,
+                last.size,
+                "last instruction is neither exit nor an unconditional jump".to_string(),
+            );
+        }
+    }
+
+    diagnostics
+}
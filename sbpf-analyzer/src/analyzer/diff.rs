@@ -0,0 +1,397 @@
+use crate::analyzer::functions::{self, FunctionInfo};
+use sbpf_common::binary::SbpfBinary;
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::cfg::{ControlFlowGraph, Function};
+use sbpf_common::instruction::disasm::opcode_to_mnemonic;
+use sbpf_common::instruction::{Instruction, InstructionDecoder};
+use sbpf_common::signatures::SignatureDb;
+use sbpf_common::syscalls::syscall_name;
+use std::collections::{BTreeMap, HashMap};
+
+/// How a function was paired across the two versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Same symbol name
+    Symbol,
+    /// Same structural hash
+    Structure,
+}
+
+/// Line of an instruction-level diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A function present in both versions whose body changed
+#[derive(Debug, Clone)]
+pub struct FunctionChange {
+    pub old: FunctionInfo,
+    pub new: FunctionInfo,
+    pub matched_by: MatchKind,
+    /// Normalized instruction diff of the two bodies
+    pub lines: Vec<DiffLine>,
+}
+
+/// Change in the number of call sites of a syscall
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallChange {
+    pub name: String,
+    pub old: usize,
+    pub new: usize,
+}
+
+/// Differences between two versions of a program
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    pub added: Vec<FunctionInfo>,
+    pub removed: Vec<FunctionInfo>,
+    pub modified: Vec<FunctionChange>,
+    /// Number of matched functions with identical bodies
+    pub unchanged: usize,
+    pub syscall_changes: Vec<SyscallChange>,
+    /// Total compute unit estimate of the old version
+    pub old_cu: u64,
+    /// Total compute unit estimate of the new version
+    pub new_cu: u64,
+}
+
+/// Decoded program with its functions
+struct Program {
+    instructions: Vec<Instruction>,
+    cfg: ControlFlowGraph,
+    functions: Vec<FunctionInfo>,
+}
+
+impl Program {
+    fn load(binary: &SbpfBinary, signatures: &SignatureDb) -> Self {
+        let instructions = InstructionDecoder::new(binary.version).decode_all(&binary.bytecode);
+        let mut cfg = ControlFlowGraph::build_with_symbols(&instructions, binary.entry_offset(), &binary.symbols);
+        signatures.label(&mut cfg, &instructions);
+        let functions = functions::analyze(&cfg, &instructions);
+        Program {
+            instructions,
+            cfg,
+            functions,
+        }
+    }
+
+    /// Body of function `idx` rendered without absolute addresses
+    fn normalized_body(&self, idx: usize) -> Vec<String> {
+        let function = &self.cfg.functions[idx];
+        self.cfg
+            .function_instructions(function, &self.instructions)
+            .map(|inst| self.normalize(function, inst))
+            .collect()
+    }
+
+    /// Render `inst` from its raw fields, keeping access widths, 32-bit
+    /// operations and source registers so any change in them shows up
+    fn normalize(&self, function: &Function, inst: &Instruction) -> String {
+        let mut mnemonic = opcode_to_mnemonic(inst.opcode);
+        if let Some(num) = inst.syscall_number() {
+            return match syscall_name(num) {
+                Some(name) => format!("call {}", name),
+                None => format!("call syscall_0x{:08x}", num),
+            };
+        }
+        if let Some(target) = inst.call_target() {
+            return match self.cfg.function_at(target) {
+                Some(callee) if !is_auto_name(&callee.name) => format!("call {}", callee.name),
+                _ => "call <function>".to_string(),
+            };
+        }
+        if let Some(target) = inst.jump_target() {
+            let target = relative(target, function.address);
+            return match inst.opcode {
+                Opcode::Ja => format!("{} {}", mnemonic, target),
+                _ if inst.uses_src_reg() => {
+                    format!("{} r{}, r{}, {}", mnemonic, inst.dst_reg, inst.src_reg, target)
+                }
+                _ => format!("{} r{}, {}, {}", mnemonic, inst.dst_reg, inst.imm, target),
+            };
+        }
+
+        match inst.mem_size() {
+            Some(1) => mnemonic.push('b'),
+            Some(2) => mnemonic.push('h'),
+            Some(4) => mnemonic.push('w'),
+            Some(_) => mnemonic.push_str("dw"),
+            None if inst.is_alu32() => mnemonic.push_str("32"),
+            None => {}
+        }
+        let source = if inst.uses_src_reg() {
+            format!("r{}", inst.src_reg)
+        } else {
+            inst.imm.to_string()
+        };
+        match inst.opcode {
+            Opcode::Exit => mnemonic,
+            Opcode::Neg => format!("{} r{}", mnemonic, inst.dst_reg),
+            Opcode::Lddw => format!("{} r{}, 0x{:x}", mnemonic, inst.dst_reg, inst.imm),
+            Opcode::Ldx => format!("{} r{}, [r{}{:+}]", mnemonic, inst.dst_reg, inst.src_reg, inst.off),
+            Opcode::St | Opcode::Stx => format!("{} [r{}{:+}], {}", mnemonic, inst.dst_reg, inst.off, source),
+            Opcode::LdAbs | Opcode::LdInd | Opcode::Callx | Opcode::Unknown(_) => format!(
+                "{} r{}, r{}, {}, {}",
+                mnemonic, inst.dst_reg, inst.src_reg, inst.off, inst.imm
+            ),
+            _ => format!("{} r{}, {}", mnemonic, inst.dst_reg, source),
+        }
+    }
+}
+
+/// Render `target` as an offset from the function start
+fn relative(target: u64, base: u64) -> String {
+    if target >= base {
+        format!("+0x{:x}", target - base)
+    } else {
+        format!("-0x{:x}", base - target)
+    }
+}
+
+/// Names generated for functions without a symbol embed their address and
+/// cannot be matched across versions
+fn is_auto_name(name: &str) -> bool {
+    name.starts_with("func_0x")
+}
+
+/// Compare two versions of a program
+pub fn diff(old: &SbpfBinary, new: &SbpfBinary, signatures: &SignatureDb) -> DiffReport {
+    let old = Program::load(old, signatures);
+    let new = Program::load(new, signatures);
+    let pairs = match_functions(&old.functions, &new.functions);
+
+    let mut modified = Vec::new();
+    let mut unchanged = 0;
+    let mut old_matched = vec![false; old.functions.len()];
+    let mut new_matched = vec![false; new.functions.len()];
+    for &(o, n, matched_by) in &pairs {
+        old_matched[o] = true;
+        new_matched[n] = true;
+        let lines = diff_lines(&old.normalized_body(o), &new.normalized_body(n));
+        if lines.iter().all(|l| matches!(l, DiffLine::Same(_))) {
+            unchanged += 1;
+        } else {
+            modified.push(FunctionChange {
+                old: old.functions[o].clone(),
+                new: new.functions[n].clone(),
+                matched_by,
+                lines,
+            });
+        }
+    }
+    modified.sort_by_key(|c| c.new.address);
+
+    let unmatched = |functions: &[FunctionInfo], matched: &[bool]| -> Vec<FunctionInfo> {
+        functions
+            .iter()
+            .zip(matched)
+            .filter(|(_, &m)| !m)
+            .map(|(f, _)| f.clone())
+            .collect()
+    };
+
+    DiffReport {
+        added: unmatched(&new.functions, &new_matched),
+        removed: unmatched(&old.functions, &old_matched),
+        modified,
+        unchanged,
+        syscall_changes: syscall_changes(&old.functions, &new.functions),
+        old_cu: old.functions.iter().map(|f| f.cu_estimate).sum(),
+        new_cu: new.functions.iter().map(|f| f.cu_estimate).sum(),
+    }
+}
+
+/// Key functions are paired on; `None` excludes a function from matching
+type MatchKey = fn(&FunctionInfo) -> Option<String>;
+
+/// Pair functions by unique symbol name, then by unique structural hash
+fn match_functions(old: &[FunctionInfo], new: &[FunctionInfo]) -> Vec<(usize, usize, MatchKind)> {
+    let mut pairs = Vec::new();
+    let mut old_used = vec![false; old.len()];
+    let mut new_used = vec![false; new.len()];
+
+    let unique_by = |functions: &[FunctionInfo], used: &[bool], key: MatchKey| {
+        let mut map: HashMap<String, Option<usize>> = HashMap::new();
+        for (idx, f) in functions.iter().enumerate() {
+            if used[idx] {
+                continue;
+            }
+            if let Some(k) = key(f) {
+                map.entry(k)
+                    .and_modify(|slot| *slot = None)
+                    .or_insert(Some(idx));
+            }
+        }
+        map.into_iter()
+            .filter_map(|(k, idx)| idx.map(|idx| (k, idx)))
+            .collect::<BTreeMap<String, usize>>()
+    };
+
+    let by_name: MatchKey = |f| (!is_auto_name(&f.name)).then(|| f.name.clone());
+    let by_hash: MatchKey = |f| Some(format!("{:016x}", f.structural_hash));
+
+    for (kind, key) in [(MatchKind::Symbol, by_name), (MatchKind::Structure, by_hash)] {
+        let old_keys = unique_by(old, &old_used, key);
+        let new_keys = unique_by(new, &new_used, key);
+        for (k, &o) in &old_keys {
+            if let Some(&n) = new_keys.get(k) {
+                old_used[o] = true;
+                new_used[n] = true;
+                pairs.push((o, n, kind));
+            }
+        }
+    }
+    pairs
+}
+
+/// Compare syscall call-site counts across all functions
+fn syscall_changes(old: &[FunctionInfo], new: &[FunctionInfo]) -> Vec<SyscallChange> {
+    let totals = |functions: &[FunctionInfo]| {
+        let mut totals: BTreeMap<String, usize> = BTreeMap::new();
+        for f in functions {
+            for (name, count) in &f.syscalls {
+                *totals.entry(name.clone()).or_insert(0) += count;
+            }
+        }
+        totals
+    };
+    let old = totals(old);
+    let new = totals(new);
+
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| SyscallChange {
+            name: name.clone(),
+            old: old.get(name).copied().unwrap_or(0),
+            new: new.get(name).copied().unwrap_or(0),
+        })
+        .filter(|c| c.old != c.new)
+        .collect()
+}
+
+/// Longest-common-subsequence diff of two instruction listings
+fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().cloned().map(DiffLine::Same).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(a[i].clone()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j].clone()));
+            j += 1;
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().cloned().map(DiffLine::Same));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::binary::parse_binary;
+    use sbpf_common::syscalls::syscall_hash;
+
+    #[test]
+    fn test_diff_versions() {
+        let log = syscall_hash("sol_log_") as i32;
+        let old: Vec<u8> = [
+            insn(0x85, 0, 1, 0, 1),   // 0x00: call 0x10
+            insn(0x95, 0, 0, 0, 0),   // 0x08: exit
+            insn(0xb7, 0, 0, 0, 1),   // 0x10: mov r0, 1
+            insn(0x95, 0, 0, 0, 0),   // 0x18: exit
+        ]
+        .concat();
+        let new: Vec<u8> = [
+            insn(0x85, 0, 0, 0, log), // 0x00: call sol_log_
+            insn(0x85, 0, 1, 0, 1),   // 0x08: call 0x18
+            insn(0x95, 0, 0, 0, 0),   // 0x10: exit
+            insn(0xb7, 0, 0, 0, 2),   // 0x18: mov r0, 2
+            insn(0x95, 0, 0, 0, 0),   // 0x20: exit
+        ]
+        .concat();
+        let report = diff(&parse_binary(&old).unwrap(), &parse_binary(&new).unwrap(), &SignatureDb::new());
+
+        assert!(report.added.is_empty() && report.removed.is_empty());
+        assert_eq!(report.modified.len(), 2);
+        let entry = &report.modified[0];
+        assert_eq!(entry.matched_by, MatchKind::Symbol);
+        assert_eq!(entry.lines[0], DiffLine::Added("call sol_log_".to_string()));
+        let callee = &report.modified[1];
+        assert_eq!(callee.matched_by, MatchKind::Structure);
+        assert_eq!(
+            callee.lines[..2],
+            [
+                DiffLine::Removed("mov r0, 1".to_string()),
+                DiffLine::Added("mov r0, 2".to_string()),
+            ]
+        );
+        assert_eq!(
+            report.syscall_changes,
+            vec![SyscallChange { name: "sol_log_".to_string(), old: 0, new: 1 }]
+        );
+        assert_eq!(report.new_cu - report.old_cu, 101);
+    }
+
+    #[test]
+    fn test_diff_source_register_and_width() {
+        let old: Vec<u8> = [
+            insn(0xbf, 1, 6, 0, 0),   // 0x00: mov r1, r6
+            insn(0x71, 0, 1, 0, 0),   // 0x08: ldxb r0, [r1+0]
+            insn(0x95, 0, 0, 0, 0),   // 0x10: exit
+        ]
+        .concat();
+        let new: Vec<u8> = [
+            insn(0xbf, 1, 7, 0, 0),   // 0x00: mov r1, r7
+            insn(0x79, 0, 1, 0, 0),   // 0x08: ldxdw r0, [r1+0]
+            insn(0x95, 0, 0, 0, 0),   // 0x10: exit
+        ]
+        .concat();
+        let report = diff(&parse_binary(&old).unwrap(), &parse_binary(&new).unwrap(), &SignatureDb::new());
+
+        assert_eq!(report.unchanged, 0);
+        assert_eq!(
+            report.modified[0].lines,
+            [
+                DiffLine::Removed("mov r1, r6".to_string()),
+                DiffLine::Removed("ldxb r0, [r1+0]".to_string()),
+                DiffLine::Added("mov r1, r7".to_string()),
+                DiffLine::Added("ldxdw r0, [r1+0]".to_string()),
+                DiffLine::Same("exit".to_string()),
+            ]
+        );
+    }
+}
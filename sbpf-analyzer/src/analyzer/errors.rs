@@ -0,0 +1,433 @@
+use crate::analyzer::lints::LintContext;
+use crate::analyzer::logs::{format_pieces, is_panic_function, rodata_str};
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::binary::SbpfBinary;
+use sbpf_common::dataflow::{Location, State, Value};
+use sbpf_common::syscalls::syscall_name;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// Return value of `ProgramError::Custom(0)`, which cannot be encoded as 0
+const CUSTOM_ZERO: u64 = 1 << 32;
+
+/// Builtin `ProgramError`s, encoded as `(index + 2) << 32`
+const BUILTIN_ERRORS: &[&str] = &[
+    "InvalidArgument",
+    "InvalidInstructionData",
+    "InvalidAccountData",
+    "AccountDataTooSmall",
+    "InsufficientFunds",
+    "IncorrectProgramId",
+    "MissingRequiredSignature",
+    "AccountAlreadyInitialized",
+    "UninitializedAccount",
+    "NotEnoughAccountKeys",
+    "AccountBorrowFailed",
+    "MaxSeedLengthExceeded",
+    "InvalidSeeds",
+    "BorshIoError",
+    "AccountNotRentExempt",
+    "UnsupportedSysvar",
+    "IllegalOwner",
+    "MaxAccountsDataAllocationsExceeded",
+    "InvalidRealloc",
+    "MaxInstructionTraceLengthExceeded",
+    "BuiltinProgramsMustConsumeComputeUnits",
+    "InvalidAccountOwner",
+    "ArithmeticOverflow",
+    "Immutable",
+    "IncorrectAuthority",
+];
+
+/// Size of a `core::panic::Location`: file `&str`, line and column
+const LOCATION_SIZE: usize = 24;
+
+/// Longest source file path accepted in a panic location
+const MAX_FILE_LEN: u64 = 512;
+
+/// Stack slots searched for the pieces of a `fmt::Arguments`
+const ARGUMENTS_SLOTS: i64 = 6;
+
+/// Decoded program return value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Custom(u32),
+    Builtin(&'static str),
+    /// Nonzero value that is not a valid `ProgramError` encoding
+    Other(u64),
+}
+
+impl ErrorCode {
+    /// Decode a nonzero return value of the entrypoint
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0 => None,
+            CUSTOM_ZERO => Some(ErrorCode::Custom(0)),
+            1..=0xffff_ffff => Some(ErrorCode::Custom(code as u32)),
+            _ if code & 0xffff_ffff == 0 => Some(
+                BUILTIN_ERRORS
+                    .get(((code >> 32) as usize).wrapping_sub(2))
+                    .map_or(ErrorCode::Other(code), |name| ErrorCode::Builtin(name)),
+            ),
+            _ => Some(ErrorCode::Other(code)),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::Custom(n) => write!(f, "Custom({})", n),
+            ErrorCode::Builtin(name) => write!(f, "{}", name),
+            ErrorCode::Other(code) => write!(f, "0x{:x}", code),
+        }
+    }
+}
+
+/// How a failure path aborts the transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Abort,
+    Panic,
+    Unwrap,
+    Expect,
+}
+
+impl FailureKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureKind::Abort => "abort",
+            FailureKind::Panic => "panic",
+            FailureKind::Unwrap => "unwrap",
+            FailureKind::Expect => "expect",
+        }
+    }
+}
+
+/// Source position of a panic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicLocation {
+    pub file: String,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl PanicLocation {
+    /// Read a `core::panic::Location` from read-only data
+    fn read(binary: &SbpfBinary, address: Value) -> Option<Self> {
+        let Value::Const(address) = address else {
+            return None;
+        };
+        let bytes = binary.read_rodata(address, LOCATION_SIZE)?;
+        let word = |range: std::ops::Range<usize>| {
+            bytes[range].iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64)
+        };
+        let len = word(8..16);
+        if len == 0 || len > MAX_FILE_LEN {
+            return None;
+        }
+        let file = rodata_str(binary, Value::Const(word(0..8)), Value::Const(len))?;
+        let line = word(16..20);
+        (line > 0).then(|| PanicLocation {
+            file: file.to_string(),
+            line,
+            column: word(20..24),
+        })
+    }
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A call that never returns
+#[derive(Debug, Clone)]
+pub struct PanicSite {
+    /// Address of the call
+    pub address: u64,
+    /// Start of the block containing the call
+    pub block: u64,
+    pub kind: FailureKind,
+    /// Syscall or function called
+    pub callee: String,
+    pub message: Option<String>,
+    pub location: Option<PanicLocation>,
+}
+
+/// Ways a function can fail
+#[derive(Debug, Clone)]
+pub struct FunctionFailures {
+    pub function: u64,
+    pub name: String,
+    /// Nonzero return values that reach the entrypoint's return
+    pub codes: BTreeSet<u64>,
+    pub panics: Vec<PanicSite>,
+}
+
+/// Constants and callee results that reach `r0` at some point
+#[derive(Debug, Default)]
+struct Sources {
+    consts: BTreeSet<u64>,
+    calls: BTreeSet<u64>,
+}
+
+/// Collect what `r0` may hold before instruction `idx` of `block`, where
+/// `value` is its tracked value. A value merged from several paths is
+/// split by walking back into the predecessors.
+fn r0_sources(
+    ctx: &LintContext,
+    block_end_r0: &HashMap<usize, Value>,
+    block: usize,
+    idx: usize,
+    value: Value,
+    sources: &mut Sources,
+    seen: &mut BTreeSet<usize>,
+) {
+    let range = ctx.cfg.blocks[block].instructions.start..idx;
+    let writer = ctx.instructions[range].iter().rev().find(|i| i.written_registers().contains(&0));
+    match (writer, value) {
+        (Some(inst), _) if inst.opcode == Opcode::Call => {
+            sources.calls.extend(inst.call_target());
+        }
+        (_, Value::Const(c)) => {
+            sources.consts.insert(c);
+        }
+        (Some(_), _) => {}
+        (None, _) => {
+            for &pred in &ctx.cfg.blocks[block].predecessors {
+                if seen.insert(pred) {
+                    let value = block_end_r0.get(&pred).copied().unwrap_or(Value::Unknown);
+                    let end = ctx.cfg.blocks[pred].instructions.end;
+                    r0_sources(ctx, block_end_r0, pred, end, value, sources, seen);
+                }
+            }
+        }
+    }
+}
+
+/// Message of a panic: a `&str` in r1/r2, or the pieces of the
+/// `fmt::Arguments` r1 points at
+fn panic_message(binary: &SbpfBinary, state: &State) -> Option<String> {
+    if let Some(text) = rodata_str(binary, state.reg(1), state.reg(2)) {
+        return Some(text.to_string());
+    }
+    let Value::Pointer(Location::Stack(base)) = state.reg(1) else {
+        return None;
+    };
+    (0..ARGUMENTS_SLOTS).find_map(|i| {
+        let slot = base + i * 8;
+        match (state.stack_slot(slot), state.stack_slot(slot + 8)) {
+            (Value::Const(address), Value::Const(count)) => {
+                format_pieces(binary, address, count).map(|pieces| pieces.join("{}"))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Classify a call to a panicking function
+fn failure_kind(callee: &str, message: Option<&str>) -> FailureKind {
+    match message {
+        Some(m) if m.starts_with("called `") && m.contains("::unwrap()") => FailureKind::Unwrap,
+        None if callee.ends_with("::unwrap_failed") => FailureKind::Unwrap,
+        _ if callee.ends_with("::expect_failed") || callee.ends_with("::unwrap_failed") => FailureKind::Expect,
+        _ => FailureKind::Panic,
+    }
+}
+
+/// Inventory the failure modes of each program function: the error codes
+/// it returns to the entrypoint at `entry` and its panic, abort and unwrap
+/// sites with their messages and source locations
+pub fn analyze(ctx: &LintContext, binary: &SbpfBinary, entry: u64) -> Vec<FunctionFailures> {
+    let cfg = ctx.cfg;
+    let block_ends: HashMap<usize, usize> = cfg
+        .blocks
+        .iter()
+        .filter(|b| !b.instructions.is_empty())
+        .map(|b| (b.instructions.end - 1, b.id))
+        .collect();
+
+    let mut exit_sources: HashMap<usize, Sources> = HashMap::new();
+    let mut panics: HashMap<usize, Vec<PanicSite>> = HashMap::new();
+    for function in &ctx.dataflow.functions {
+        let cfg_function = &cfg.functions[function.index];
+        let mut block_end_r0 = HashMap::new();
+        let mut exits = Vec::new();
+        let mut sites = Vec::new();
+        function.visit(cfg, ctx.instructions, |idx, inst, state| {
+            if let Some(&block) = block_ends.get(&idx) {
+                let mut after = state.clone();
+                after.transfer(inst);
+                block_end_r0.insert(block, after.reg(0));
+            }
+            if inst.opcode == Opcode::Exit {
+                exits.push((idx, state.reg(0)));
+            }
+            if cfg_function.library {
+                return;
+            }
+            let block = cfg.block_containing(inst.address).map_or(inst.address, |id| cfg.blocks[id].start);
+            let site = |kind, callee: &str, message, location| PanicSite {
+                address: inst.address,
+                block,
+                kind,
+                callee: callee.to_string(),
+                message,
+                location,
+            };
+            match inst.syscall_number().and_then(syscall_name) {
+                Some(name @ "abort") => sites.push(site(FailureKind::Abort, name, None, None)),
+                Some(name @ "sol_panic_") => {
+                    let location = match (rodata_str(binary, state.reg(1), state.reg(2)), state.reg(3), state.reg(4)) {
+                        (Some(file), Value::Const(line), Value::Const(column)) => Some(PanicLocation {
+                            file: file.to_string(),
+                            line,
+                            column,
+                        }),
+                        _ => None,
+                    };
+                    sites.push(site(FailureKind::Panic, name, None, location));
+                }
+                _ => {}
+            }
+            let callee = inst.call_target().and_then(|target| cfg.function_at(target));
+            if let Some(callee) = callee.filter(|f| is_panic_function(&f.name)) {
+                let message = panic_message(binary, state);
+                let location = (1..=5).find_map(|reg| PanicLocation::read(binary, state.reg(reg)));
+                let kind = failure_kind(&callee.name, message.as_deref());
+                sites.push(site(kind, &callee.name, message, location));
+            }
+        });
+
+        let mut sources = Sources::default();
+        for (idx, value) in exits {
+            if let Some(block) = cfg.block_containing(ctx.instructions[idx].address) {
+                r0_sources(ctx, &block_end_r0, block, idx, value, &mut sources, &mut BTreeSet::new());
+            }
+        }
+        exit_sources.insert(function.index, sources);
+        panics.insert(function.index, sites);
+    }
+
+    // Functions whose return value becomes the entrypoint's
+    let index_of = |address: u64| cfg.functions.iter().position(|f| f.address == address);
+    let mut returning: BTreeSet<usize> = index_of(entry).into_iter().collect();
+    let mut worklist: Vec<usize> = returning.iter().copied().collect();
+    while let Some(idx) = worklist.pop() {
+        let Some(sources) = exit_sources.get(&idx) else {
+            continue;
+        };
+        for callee in sources.calls.iter().filter_map(|&target| index_of(target)) {
+            let function = &cfg.functions[callee];
+            if !function.library && !is_panic_function(&function.name) && returning.insert(callee) {
+                worklist.push(callee);
+            }
+        }
+    }
+
+    let mut failures: BTreeMap<u64, FunctionFailures> = BTreeMap::new();
+    for (idx, function) in cfg.functions.iter().enumerate() {
+        let codes: BTreeSet<u64> = match exit_sources.get(&idx) {
+            Some(sources) if returning.contains(&idx) => sources.consts.iter().copied().filter(|&c| c != 0).collect(),
+            _ => BTreeSet::new(),
+        };
+        let sites = panics.remove(&idx).unwrap_or_default();
+        if codes.is_empty() && sites.is_empty() {
+            continue;
+        }
+        failures.insert(
+            function.address,
+            FunctionFailures {
+                function: function.address,
+                name: function.name.clone(),
+                codes,
+                panics: sites,
+            },
+        );
+    }
+    failures.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::binary::MetadataSection;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_decode_error_codes() {
+        assert_eq!(ErrorCode::from_code(0), None);
+        assert_eq!(ErrorCode::from_code(6000), Some(ErrorCode::Custom(6000)));
+        assert_eq!(ErrorCode::from_code(1 << 32), Some(ErrorCode::Custom(0)));
+        assert_eq!(ErrorCode::from_code(3 << 32), Some(ErrorCode::Builtin("InvalidInstructionData")));
+        assert_eq!(ErrorCode::from_code(0x1234_5678_9abc), Some(ErrorCode::Other(0x1234_5678_9abc)));
+    }
+
+    #[test]
+    fn test_failure_inventory() {
+        let message = "called `Option::unwrap()` on a `None` value";
+        let file = "src/lib.rs";
+        let mut rodata = message.as_bytes().to_vec();
+        rodata.extend_from_slice(file.as_bytes());
+        rodata.resize(0x40, 0);
+        rodata.extend_from_slice(&(0x200 + message.len() as u64).to_le_bytes());
+        rodata.extend_from_slice(&(file.len() as u64).to_le_bytes());
+        rodata.extend_from_slice(&12u32.to_le_bytes());
+        rodata.extend_from_slice(&34u32.to_le_bytes());
+
+        let program: Vec<u8> = [
+            insn(0x79, 2, 1, 0, 0),           // 0x00: ldxdw r2, [r1+0]
+            insn(0x55, 2, 0, 3, 0),           // 0x08: jne r2, 0, 0x28
+            insn(0x18, 0, 0, 0, 0),           // 0x10: lddw r0, 0x300000000
+            insn(0x00, 0, 0, 0, 3),
+            insn(0x95, 0, 0, 0, 0),           // 0x20: exit
+            insn(0x55, 2, 0, 4, 1),           // 0x28: jne r2, 1, 0x50
+            insn(0xb7, 0, 0, 0, 6000),        // 0x30: mov r0, 6000
+            insn(0x15, 2, 0, 1, 2),           // 0x38: jeq r2, 2, 0x48
+            insn(0xb7, 0, 0, 0, 7),           // 0x40: mov r0, 7
+            insn(0x95, 0, 0, 0, 0),           // 0x48: exit
+            insn(0x18, 1, 0, 0, 0x200),       // 0x50: lddw r1, message
+            insn(0x00, 0, 0, 0, 0),
+            insn(0xb7, 2, 0, 0, message.len() as i32), // 0x60: mov r2, len
+            insn(0x18, 3, 0, 0, 0x240),       // 0x68: lddw r3, location
+            insn(0x00, 0, 0, 0, 0),
+            insn(0x85, 0, 1, 0, 1),           // 0x78: call 0x88
+            insn(0x95, 0, 0, 0, 0),           // 0x80: exit
+            insn(0x95, 0, 0, 0, 0),           // 0x88: core::panicking::panic
+        ]
+        .concat();
+        let binary = SbpfBinary {
+            bytecode: program.clone(),
+            entry_point: 0,
+            text_address: 0,
+            text_offset: 0,
+            version: None,
+            metadata: vec![MetadataSection {
+                name: ".rodata".to_string(),
+                address: 0x200,
+                data: rodata,
+            }],
+            symbols: Vec::new(),
+        };
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let mut cfg = ControlFlowGraph::build(&instructions, 0);
+        let panic = cfg.functions.iter_mut().find(|f| f.address == 0x88).unwrap();
+        panic.name = "core::panicking::panic".to_string();
+        panic.library = true;
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let failures = analyze(&ctx, &binary, 0);
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].codes, BTreeSet::from([7, 6000, 3 << 32]));
+        let site = &failures[0].panics[0];
+        assert_eq!((site.address, site.block, site.kind), (0x78, 0x50, FailureKind::Unwrap));
+        assert_eq!(site.message.as_deref(), Some(message));
+        assert_eq!(site.location.as_ref().map(|l| l.to_string()).as_deref(), Some("src/lib.rs:12:34"));
+    }
+}
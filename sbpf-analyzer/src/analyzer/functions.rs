@@ -0,0 +1,91 @@
+use sbpf_common::cfg::{ControlFlowGraph, Function};
+use sbpf_common::hash::StableHasher;
+use sbpf_common::instruction::disasm::opcode_to_mnemonic;
+use sbpf_common::instruction::Instruction;
+use sbpf_common::syscalls::{syscall_cost, syscall_name, SYSCALL_BASE_COST};
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+/// Per-function summary
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    /// Address of the first instruction
+    pub address: u64,
+    pub name: String,
+    /// Number of instructions in the function body
+    pub instructions: usize,
+    /// Number of basic blocks
+    pub blocks: usize,
+    /// Syscall name -> number of call sites
+    pub syscalls: BTreeMap<String, usize>,
+    /// Straight-line compute unit estimate: one unit per instruction plus
+    /// the base cost of every syscall site
+    pub cu_estimate: u64,
+    /// Statically linked library code
+    pub library: bool,
+    /// Hash of the CFG shape and instruction mnemonics, independent of
+    /// addresses and immediates
+    pub structural_hash: u64,
+}
+
+/// Summarize every function in the control flow graph
+pub fn analyze(cfg: &ControlFlowGraph, instructions: &[Instruction]) -> Vec<FunctionInfo> {
+    cfg.functions
+        .iter()
+        .map(|function| summarize(cfg, function, instructions))
+        .collect()
+}
+
+fn summarize(cfg: &ControlFlowGraph, function: &Function, instructions: &[Instruction]) -> FunctionInfo {
+    let mut syscalls = BTreeMap::new();
+    let mut count = 0;
+    let mut cu_estimate = 0;
+
+    for inst in cfg.function_instructions(function, instructions) {
+        count += 1;
+        cu_estimate += 1;
+        if let Some(num) = inst.syscall_number() {
+            let name = match syscall_name(num) {
+                Some(name) => {
+                    cu_estimate += syscall_cost(name);
+                    name.to_string()
+                }
+                None => {
+                    cu_estimate += SYSCALL_BASE_COST;
+                    format!("syscall_0x{:08x}", num)
+                }
+            };
+            *syscalls.entry(name).or_insert(0) += 1;
+        }
+    }
+
+    FunctionInfo {
+        address: function.address,
+        name: function.name.clone(),
+        instructions: count,
+        blocks: function.blocks.len(),
+        syscalls,
+        cu_estimate,
+        library: function.library,
+        structural_hash: structural_hash(cfg, function, instructions),
+    }
+}
+
+/// Hash the block graph (successors as positions within the function) and
+/// the mnemonic sequence of every block
+fn structural_hash(cfg: &ControlFlowGraph, function: &Function, instructions: &[Instruction]) -> u64 {
+    let position = |id: usize| function.blocks.iter().position(|&b| b == id);
+    let mut hasher = StableHasher::new();
+    for &id in &function.blocks {
+        let block = &cfg.blocks[id];
+        for &succ in &block.successors {
+            hasher.write_u64(position(succ).map_or(u64::MAX, |p| p as u64));
+        }
+        hasher.write_u8(0xff);
+        for inst in &instructions[block.instructions.clone()] {
+            hasher.write(opcode_to_mnemonic(inst.opcode).as_bytes());
+            hasher.write_u8(0);
+        }
+    }
+    hasher.finish()
+}
@@ -0,0 +1,332 @@
+use crate::analyzer::accounts::handler_field_accesses;
+use crate::analyzer::anchor::{discriminator_compares, sighash, AnchorInfo, AnchorInstruction};
+use sbpf_common::dataflow::{Location, Value};
+use crate::analyzer::lints::{AccessKind, LintContext};
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::input::AccountField;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Length of the discriminator preceding instruction arguments
+const DISCRIMINATOR_SIZE: i64 = 8;
+
+/// Best-effort interface description of an Anchor program
+#[derive(Debug, Clone, Default)]
+pub struct Idl {
+    pub instructions: Vec<IdlInstruction>,
+    /// Account types recognized by their discriminator checks
+    pub accounts: Vec<IdlAccountType>,
+}
+
+/// Instruction with the accounts and arguments its handler uses
+#[derive(Debug, Clone)]
+pub struct IdlInstruction {
+    pub name: String,
+    pub discriminator: [u8; 8],
+    /// Accounts by position in the instruction's account list
+    pub accounts: Vec<IdlAccount>,
+    /// Arguments covering the instruction data after the discriminator, in
+    /// order and without gaps
+    pub args: Vec<IdlArg>,
+}
+
+/// Account argument of an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdlAccount {
+    pub index: u16,
+    /// Whether the handler checks `is_signer`
+    pub signer: bool,
+    /// Whether the handler checks `is_writable` or modifies the account
+    pub writable: bool,
+}
+
+/// Instruction argument loaded from instruction data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdlArg {
+    /// Byte offset after the discriminator
+    pub offset: i64,
+    /// Width in bytes, spanning every load that overlaps the argument
+    pub size: u64,
+    /// Whether the handler never loads these bytes
+    pub padding: bool,
+}
+
+/// Account type identified by an `account:<Name>` discriminator
+#[derive(Debug, Clone)]
+pub struct IdlAccountType {
+    pub name: Option<String>,
+    pub discriminator: [u8; 8],
+}
+
+/// Reconstruct the IDL of an Anchor program
+pub fn analyze(ctx: &LintContext, anchor: &AnchorInfo, names: &BTreeSet<String>) -> Idl {
+    if !anchor.is_anchor {
+        return Idl::default();
+    }
+
+    let instructions = anchor
+        .instructions
+        .iter()
+        .map(|ix| IdlInstruction {
+            name: ix.display_name(),
+            discriminator: ix.discriminator,
+            accounts: handler_accounts(ctx, ix),
+            args: handler_args(ctx, ix),
+        })
+        .collect();
+
+    Idl {
+        instructions,
+        accounts: account_types(ctx, anchor, names),
+    }
+}
+
+/// Accounts the handler touches, with flag checks and writes
+fn handler_accounts(ctx: &LintContext, ix: &AnchorInstruction) -> Vec<IdlAccount> {
+    let mut accounts: BTreeMap<u16, IdlAccount> = BTreeMap::new();
+    for access in handler_field_accesses(ctx, ix.handler) {
+        let account = accounts.entry(access.account).or_insert(IdlAccount {
+            index: access.account,
+            signer: false,
+            writable: false,
+        });
+        match (access.field, access.kind) {
+            (AccountField::IsSigner, AccessKind::Read | AccessKind::Compared) => account.signer = true,
+            (AccountField::IsWritable, AccessKind::Read | AccessKind::Compared) => account.writable = true,
+            (AccountField::Lamports | AccountField::Data, AccessKind::Write) => account.writable = true,
+            _ => {}
+        }
+    }
+
+    // Positions matter, so fill in accounts the handler never touches
+    let count = accounts.keys().next_back().map_or(0, |&last| last + 1);
+    (0..count)
+        .map(|index| {
+            accounts.remove(&index).unwrap_or(IdlAccount {
+                index,
+                signer: false,
+                writable: false,
+            })
+        })
+        .collect()
+}
+
+/// Loads from the instruction data passed to a separate handler function
+fn handler_args(ctx: &LintContext, ix: &AnchorInstruction) -> Vec<IdlArg> {
+    let Some(dispatcher) = ctx.dataflow.functions.iter().find(|f| f.address == ix.dispatcher) else {
+        return Vec::new();
+    };
+    let Some(handler) = ctx.dataflow.functions.iter().find(|f| f.address == ix.handler) else {
+        return Vec::new();
+    };
+
+    // Location of the discriminator, from the loaded operand of the compare
+    let mut data = None;
+    dispatcher.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+        if inst.address == ix.compare {
+            for reg in inst.read_registers() {
+                if let Value::Loaded(loc, 8) = state.reg(reg) {
+                    data = Some(loc);
+                }
+            }
+        }
+    });
+    let Some(data) = data else {
+        return Vec::new();
+    };
+
+    // Argument register pointing into the instruction data at the call,
+    // for handlers that see the data through an argument
+    let mut passed: Option<(u8, i64)> = None;
+    dispatcher.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+        if passed.is_some() || inst.call_target() != Some(ix.handler) {
+            return;
+        }
+        for reg in 1..=5u8 {
+            for skip in [DISCRIMINATOR_SIZE, 0] {
+                if state.reg(reg) == Value::Pointer(data.offset_by(skip)) {
+                    passed = Some((reg, DISCRIMINATOR_SIZE - skip));
+                    return;
+                }
+            }
+        }
+    });
+
+    let mut args: BTreeMap<i64, u64> = BTreeMap::new();
+    handler.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+        if inst.opcode != Opcode::Ldx {
+            return;
+        }
+        let offset = match (state.memory_location(inst), passed) {
+            (Some(Location::Arg(r, off)), Some((reg, bias))) if r == reg => off - bias,
+            (Some(loc), _) => match distance(data, loc) {
+                Some(d) => d - DISCRIMINATOR_SIZE,
+                None => return,
+            },
+            _ => return,
+        };
+        if offset >= 0 {
+            let size = inst.mem_size().unwrap_or(8) as u64;
+            let slot = args.entry(offset).or_insert(size);
+            *slot = (*slot).max(size);
+        }
+    });
+    sequential_args(args)
+}
+
+/// Lay out loads by offset as consecutive arguments, merging loads that
+/// overlap and padding the bytes between them
+fn sequential_args(loads: BTreeMap<i64, u64>) -> Vec<IdlArg> {
+    let mut args: Vec<IdlArg> = Vec::new();
+    let mut end = 0;
+    for (offset, size) in loads {
+        match args.last_mut() {
+            Some(last) if offset < end => {
+                end = end.max(offset + size as i64);
+                last.size = (end - last.offset) as u64;
+                continue;
+            }
+            _ if offset > end => args.push(IdlArg {
+                offset: end,
+                size: (offset - end) as u64,
+                padding: true,
+            }),
+            _ => {}
+        }
+        args.push(IdlArg {
+            offset,
+            size,
+            padding: false,
+        });
+        end = offset + size as i64;
+    }
+    args
+}
+
+/// Byte distance from `from` to `to` when both are in the same region
+fn distance(from: Location, to: Location) -> Option<i64> {
+    match (from, to) {
+        (Location::Input(a), Location::Input(b)) => Some(b - a),
+        (Location::Account(i, a), Location::Account(j, b)) if i == j => Some(b - a),
+        (Location::AfterData(i, a), Location::AfterData(j, b)) if i == j => Some(b - a),
+        _ => None,
+    }
+}
+
+/// Discriminator checks that do not select an instruction are account
+/// deserialization checks
+fn account_types(ctx: &LintContext, anchor: &AnchorInfo, names: &BTreeSet<String>) -> Vec<IdlAccountType> {
+    let known: HashMap<[u8; 8], &String> = names
+        .iter()
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+        .map(|name| (sighash("account", name), name))
+        .collect();
+    let instruction_discriminators: BTreeSet<[u8; 8]> =
+        anchor.instructions.iter().map(|ix| ix.discriminator).collect();
+
+    let mut found: BTreeSet<[u8; 8]> = BTreeSet::new();
+    for function in ctx.cfg.functions.iter().filter(|f| !f.library) {
+        for (_, discriminator, _) in discriminator_compares(ctx.cfg, function, ctx.instructions) {
+            if !instruction_discriminators.contains(&discriminator) {
+                found.insert(discriminator);
+            }
+        }
+    }
+    found
+        .into_iter()
+        .map(|discriminator| IdlAccountType {
+            name: known.get(&discriminator).map(|name| name.to_string()),
+            discriminator,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::anchor;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_reconstruct_instruction() {
+        let disc = u64::from_le_bytes(sighash("global", "initialize"));
+        let program: Vec<u8> = [
+            insn(0x79, 3, 1, 200, 0),                     // 0x00: ldxdw r3, [r1+200]
+            insn(0x18, 4, 0, 0, disc as u32 as i32),      // 0x08: lddw r4, sighash(initialize)
+            insn(0x00, 0, 0, 0, (disc >> 32) as u32 as i32),
+            insn(0x5d, 3, 4, 3, 0),                       // 0x18: jne r3, r4, +3
+            insn(0xbf, 2, 1, 0, 0),                       // 0x20: mov r2, r1
+            insn(0x07, 2, 0, 0, 208),                     // 0x28: add r2, 208
+            insn(0x85, 0, 1, 0, 1),                       // 0x30: call 0x40
+            insn(0x95, 0, 0, 0, 0),                       // 0x38: exit
+            insn(0x71, 3, 1, 9, 0),                       // 0x40: ldxb r3, [r1+9]
+            insn(0x79, 4, 2, 0, 0),                       // 0x48: ldxdw r4, [r2+0]
+            insn(0x71, 5, 2, 8, 0),                       // 0x50: ldxb r5, [r2+8]
+            insn(0x95, 0, 0, 0, 0),                       // 0x58: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let names = BTreeSet::from(["initialize".to_string()]);
+        let info = anchor::analyze(&cfg, &instructions, &dataflow, &names);
+        let idl = analyze(&ctx, &info, &names);
+
+        let ix = &idl.instructions[0];
+        assert_eq!(ix.name, "initialize");
+        assert_eq!(ix.accounts, vec![IdlAccount { index: 0, signer: true, writable: false }]);
+        assert_eq!(
+            ix.args,
+            vec![
+                IdlArg { offset: 0, size: 8, padding: false },
+                IdlArg { offset: 8, size: 1, padding: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequential_args() {
+        // Gaps become padding and overlapping loads one argument
+        let loads = BTreeMap::from([(2, 2), (8, 8), (12, 4), (14, 4), (24, 1)]);
+        assert_eq!(
+            sequential_args(loads),
+            vec![
+                IdlArg { offset: 0, size: 2, padding: true },
+                IdlArg { offset: 2, size: 2, padding: false },
+                IdlArg { offset: 4, size: 4, padding: true },
+                IdlArg { offset: 8, size: 10, padding: false },
+                IdlArg { offset: 18, size: 6, padding: true },
+                IdlArg { offset: 24, size: 1, padding: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inlined_handler_accounts() {
+        let disc = u64::from_le_bytes(sighash("global", "initialize"));
+        let program: Vec<u8> = [
+            insn(0x79, 3, 1, 200, 0),                     // 0x00: ldxdw r3, [r1+200]
+            insn(0x18, 4, 0, 0, disc as u32 as i32),      // 0x08: lddw r4, sighash(initialize)
+            insn(0x00, 0, 0, 0, (disc >> 32) as u32 as i32),
+            insn(0x5d, 3, 4, 2, 0),                       // 0x18: jne r3, r4, +2
+            insn(0x71, 5, 1, 9, 0),                       // 0x20: ldxb r5, [r1+9]
+            insn(0x95, 0, 0, 0, 0),                       // 0x28: exit
+            insn(0x95, 0, 0, 0, 0),                       // 0x30: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let names = BTreeSet::from(["initialize".to_string()]);
+        let info = anchor::analyze(&cfg, &instructions, &dataflow, &names);
+        let idl = analyze(&ctx, &info, &names);
+
+        // The handler has no function of its own
+        assert_eq!(info.instructions[0].handler, 0x20);
+        assert_eq!(idl.instructions[0].accounts, vec![IdlAccount { index: 0, signer: true, writable: false }]);
+    }
+}
@@ -46,26 +46,25 @@ pub fn analyze(instructions: &[Instruction]) -> InstructionStats {
         *by_opcode.entry(opcode_name).or_insert(0) += 1;
         
         // Analyze control flow
-        match inst.category() {
-            InstructionCategory::ControlFlow => {
-                match inst.opcode {
-                    sbpf_common::binary::sbpf::Opcode::Call => {
-                        calls += 1;
-                    }
-                    sbpf_common::binary::sbpf::Opcode::Exit => {
-                        exits += 1;
-                    }
-                    _ => {
-                        jumps += 1;
-                        // Calculate jump target
-                        let target = (inst.address as i64 + inst.imm + 1) as u64;
+        if inst.category() == InstructionCategory::ControlFlow {
+            match inst.opcode {
+                sbpf_common::binary::sbpf::Opcode::Call
+                | sbpf_common::binary::sbpf::Opcode::Callx => {
+                    calls += 1;
+                }
+                sbpf_common::binary::sbpf::Opcode::Exit => {
+                    exits += 1;
+                }
+                _ => {
+                    jumps += 1;
+                    // Calculate jump target
+                    if let Some(target) = inst.jump_target() {
                         if !jump_targets.contains(&target) {
                             jump_targets.push(target);
                         }
                     }
                 }
             }
-            _ => {}
         }
     }
     
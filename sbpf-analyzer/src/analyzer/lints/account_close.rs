@@ -0,0 +1,58 @@
+use sbpf_common::dataflow::Value;
+use crate::analyzer::lints::{AccessKind, Finding, LintContext, LintRule, Severity};
+use sbpf_common::input::AccountField;
+
+/// Account closed by draining its lamports without clearing its data
+///
+/// A closed account whose data is left intact can be revived within the same
+/// transaction by refunding it rent.
+pub struct UnzeroedAccountClose;
+
+impl LintRule for UnzeroedAccountClose {
+    fn id(&self) -> &'static str {
+        "unzeroed-account-close"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn description(&self) -> &'static str {
+        "Account lamports are set to zero without zeroing the account data or reassigning its owner"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let accesses = &ctx.field_accesses;
+
+        accesses
+            .iter()
+            .filter(|a| {
+                a.field == AccountField::Lamports
+                    && a.kind == AccessKind::Write
+                    && a.value == Value::Const(0)
+            })
+            .filter(|close| {
+                !accesses.iter().any(|a| {
+                    a.function == close.function
+                        && a.account == close.account
+                        && matches!(
+                            (a.field, a.kind),
+                            (AccountField::Data, AccessKind::Write | AccessKind::Passed)
+                                | (AccountField::DataLen | AccountField::Owner, AccessKind::Write)
+                        )
+                })
+            })
+            .map(|close| {
+                ctx.finding(
+                    self,
+                    close.function,
+                    close.address,
+                    format!(
+                        "account {} is closed but its data is not zeroed and its owner is not reassigned",
+                        close.account
+                    ),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,61 @@
+use crate::analyzer::cpi::{program_id, CpiProgram};
+use sbpf_common::dataflow::Location;
+use crate::analyzer::lints::{AccessKind, Finding, LintContext, LintRule, Severity};
+use sbpf_common::input::AccountField;
+use sbpf_common::syscalls::{is_invoke, syscall_name};
+
+/// Cross-program invocation of a program id taken from an unchecked account
+pub struct ArbitraryCpi;
+
+impl LintRule for ArbitraryCpi {
+    fn id(&self) -> &'static str {
+        "arbitrary-cpi"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn description(&self) -> &'static str {
+        "CPI target program id comes from an account key that is never validated"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let accesses = &ctx.field_accesses;
+        let key_checked = |account: u16| {
+            accesses.iter().any(|a| {
+                a.account == account
+                    && a.field == AccountField::Key
+                    && matches!(a.kind, AccessKind::Compared | AccessKind::Passed)
+            })
+        };
+
+        let mut findings = Vec::new();
+        for (f, function) in ctx.dataflow.functions.iter().enumerate() {
+            function.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+                let Some(num) = inst.syscall_number().filter(|n| is_invoke(*n)) else {
+                    return;
+                };
+                let Some(Location::Stack(instruction)) = state.reg(1).location() else {
+                    return;
+                };
+
+                let c_abi = syscall_name(num) == Some("sol_invoke_signed_c");
+                if let CpiProgram::Account(account) = program_id(state, None, instruction, c_abi) {
+                    if !key_checked(account) {
+                        findings.push(ctx.finding(
+                            self,
+                            f,
+                            inst.address,
+                            format!(
+                                "CPI invokes the program id taken from account {} without validating it",
+                                account
+                            ),
+                        ));
+                    }
+                }
+            });
+        }
+        findings
+    }
+}
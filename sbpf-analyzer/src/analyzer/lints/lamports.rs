@@ -0,0 +1,83 @@
+use sbpf_common::dataflow::Value;
+use crate::analyzer::lints::{Finding, LintContext, LintRule, Severity};
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::input::AccountField;
+use std::collections::HashMap;
+
+/// Lamport balances updated with wrapping arithmetic
+///
+/// Flags `add`/`sub` on a value loaded from a lamports field whose result is
+/// stored back to a lamports field without first being compared, which is how
+/// `checked_add`/`checked_sub` overflow checks compile.
+pub struct UncheckedLamportsArithmetic;
+
+impl LintRule for UncheckedLamportsArithmetic {
+    fn id(&self) -> &'static str {
+        "unchecked-lamports-arithmetic"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn description(&self) -> &'static str {
+        "Lamports are updated with arithmetic that is never checked for overflow"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let is_lamports = |value: Value| match value {
+            Value::Loaded(loc, 8) => {
+                matches!(loc.account_field(), Some((_, AccountField::Lamports)))
+            }
+            _ => false,
+        };
+
+        let mut findings = Vec::new();
+        for (f, function) in ctx.dataflow.functions.iter().enumerate() {
+            // Register -> (address of the arithmetic, checked)
+            let mut pending: HashMap<u8, (u64, bool)> = HashMap::new();
+            function.visit(ctx.cfg, ctx.instructions, |_, inst, state| {
+                if ctx.cfg.block_id_at(inst.address).is_some() {
+                    pending.clear();
+                }
+                match inst.opcode {
+                    Opcode::Add | Opcode::Sub
+                        if is_lamports(state.reg(inst.dst_reg)) || is_lamports(state.source(inst)) =>
+                    {
+                        pending.insert(inst.dst_reg, (inst.address, false));
+                        return;
+                    }
+                    op if op.is_conditional_jump() => {
+                        for reg in inst.read_registers() {
+                            if let Some(entry) = pending.get_mut(&reg) {
+                                entry.1 = true;
+                            }
+                        }
+                    }
+                    Opcode::Stx => {
+                        let target = state.memory_location(inst).and_then(|l| l.account_field());
+                        if let (Some((account, AccountField::Lamports)), Some(&(address, false))) =
+                            (target, pending.get(&inst.src_reg))
+                        {
+                            findings.push(ctx.finding(
+                                self,
+                                f,
+                                address,
+                                format!(
+                                    "lamports of account {} are updated without an overflow check",
+                                    account
+                                ),
+                            ));
+                            pending.remove(&inst.src_reg);
+                        }
+                    }
+                    _ => {}
+                }
+                for reg in inst.written_registers() {
+                    pending.remove(&reg);
+                }
+            });
+        }
+        findings
+    }
+}
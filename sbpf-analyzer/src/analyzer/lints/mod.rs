@@ -0,0 +1,390 @@
+pub mod account_close;
+pub mod arbitrary_cpi;
+pub mod lamports;
+pub mod owner_check;
+pub mod pda_bump;
+pub mod signer_check;
+
+use sbpf_common::dataflow::{Dataflow, Value};
+use sbpf_common::binary::sbpf::{Opcode, INSN_SIZE};
+use sbpf_common::cfg::ControlFlowGraph;
+use sbpf_common::input::AccountField;
+use sbpf_common::instruction::Instruction;
+use sbpf_common::syscalls::syscall_name;
+use std::collections::BTreeSet;
+
+/// Severity of a lint finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// A single issue reported by a lint rule
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Identifier of the rule that produced the finding
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// Address of the offending instruction
+    pub address: u64,
+    /// Size of the offending instruction in bytes
+    pub size: usize,
+    /// Address of the containing function
+    pub function: u64,
+    /// Name of the containing function
+    pub function_name: String,
+    /// Explanation of the issue
+    pub message: String,
+}
+
+/// How an account record field is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Loaded from memory
+    Read,
+    /// Stored to memory
+    Write,
+    /// Loaded value used as a conditional jump operand
+    Compared,
+    /// Pointer to the field passed to a call
+    Passed,
+}
+
+/// Use of a field of a serialized account record
+#[derive(Debug, Clone)]
+pub struct FieldAccess {
+    /// Dataflow index of the containing function
+    pub function: usize,
+    /// Address of the instruction
+    pub address: u64,
+    /// Account record index
+    pub account: u16,
+    pub field: AccountField,
+    pub kind: AccessKind,
+    /// Value written, for stores
+    pub value: Value,
+}
+
+/// Program facts shared by all lint rules
+pub struct LintContext<'a> {
+    pub instructions: &'a [Instruction],
+    pub cfg: &'a ControlFlowGraph,
+    pub dataflow: &'a Dataflow,
+    /// Every use of an account record field found by the dataflow analysis
+    pub field_accesses: Vec<FieldAccess>,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(instructions: &'a [Instruction], cfg: &'a ControlFlowGraph, dataflow: &'a Dataflow) -> Self {
+        LintContext {
+            instructions,
+            cfg,
+            dataflow,
+            field_accesses: field_accesses(instructions, cfg, dataflow),
+        }
+    }
+
+    /// Addresses of every call to the syscall `name`, with their function
+    pub fn syscall_sites(&self, name: &str) -> Vec<(u64, usize)> {
+        let mut sites = Vec::new();
+        for (f, function) in self.dataflow.functions.iter().enumerate() {
+            for &block in &self.cfg.functions[function.index].blocks {
+                for inst in &self.instructions[self.cfg.blocks[block].instructions.clone()] {
+                    if inst.syscall_number().and_then(syscall_name) == Some(name) {
+                        sites.push((inst.address, f));
+                    }
+                }
+            }
+        }
+        sites
+    }
+
+    /// Functions reachable from the function at `address` through internal
+    /// calls, skipping library code
+    pub fn call_tree(&self, address: u64) -> BTreeSet<usize> {
+        let cfg = self.cfg;
+        let mut seen = BTreeSet::new();
+        let mut stack: Vec<usize> = cfg.functions.iter().position(|f| f.address == address).into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            let function = &cfg.functions[idx];
+            if function.library || !seen.insert(idx) {
+                continue;
+            }
+            for inst in cfg.function_instructions(function, self.instructions) {
+                if let Some(target) = inst.call_target() {
+                    stack.extend(cfg.functions.iter().position(|f| f.address == target));
+                }
+            }
+        }
+        seen
+    }
+
+    /// Build a finding located in the function at dataflow index `function`
+    pub fn finding(
+        &self,
+        rule: &dyn LintRule,
+        function: usize,
+        address: u64,
+        message: String,
+    ) -> Finding {
+        let function = &self.cfg.functions[self.dataflow.functions[function].index];
+        let size = match self.instructions.binary_search_by_key(&address, |inst| inst.address) {
+            Ok(index) => self.instructions[index].size,
+            Err(_) => INSN_SIZE,
+        };
+        Finding {
+            rule: rule.id(),
+            severity: rule.severity(),
+            address,
+            size,
+            function: function.address,
+            function_name: function.name.clone(),
+            message,
+        }
+    }
+}
+
+/// Every use of an account record field found by the dataflow analysis
+fn field_accesses(instructions: &[Instruction], cfg: &ControlFlowGraph, dataflow: &Dataflow) -> Vec<FieldAccess> {
+    let mut accesses = Vec::new();
+    for (f, function) in dataflow.functions.iter().enumerate() {
+        function.visit(cfg, instructions, |_, inst, state| {
+            let mut push = |account, field, kind, value| {
+                accesses.push(FieldAccess {
+                    function: f,
+                    address: inst.address,
+                    account,
+                    field,
+                    kind,
+                    value,
+                });
+            };
+            match inst.opcode {
+                Opcode::Ldx | Opcode::St | Opcode::Stx => {
+                    let Some(loc) = state.memory_location(inst) else {
+                        return;
+                    };
+                    let Some((account, field)) = loc.account_field() else {
+                        return;
+                    };
+                    match inst.opcode {
+                        Opcode::Ldx => push(account, field, AccessKind::Read, Value::Unknown),
+                        Opcode::St => {
+                            let value = Value::Const(inst.imm as u64);
+                            push(account, field, AccessKind::Write, value)
+                        }
+                        _ => push(account, field, AccessKind::Write, state.reg(inst.src_reg)),
+                    }
+                }
+                Opcode::Call | Opcode::Callx => {
+                    for reg in 1..=5 {
+                        if let Some((account, field)) = state.reg(reg).account_field() {
+                            push(account, field, AccessKind::Passed, Value::Unknown);
+                        }
+                    }
+                }
+                op if op.is_conditional_jump() => {
+                    for reg in inst.read_registers() {
+                        if let Value::Loaded(loc, _) = state.reg(reg) {
+                            if let Some((account, field)) = loc.account_field() {
+                                push(account, field, AccessKind::Compared, Value::Unknown);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        });
+    }
+    accesses
+}
+
+/// A rule checking the program for one kind of vulnerability
+pub trait LintRule {
+    /// Short kebab-case identifier
+    fn id(&self) -> &'static str;
+    /// Default severity of findings
+    fn severity(&self) -> Severity;
+    /// One-line description of what the rule checks
+    fn description(&self) -> &'static str;
+    /// Run the rule
+    fn check(&self, ctx: &LintContext) -> Vec<Finding>;
+}
+
+/// All built-in lint rules
+pub fn all_rules() -> Vec<Box<dyn LintRule>> {
+    vec![
+        Box::new(owner_check::MissingOwnerCheck),
+        Box::new(signer_check::MissingSignerCheck),
+        Box::new(arbitrary_cpi::ArbitraryCpi),
+        Box::new(lamports::UncheckedLamportsArithmetic),
+        Box::new(account_close::UnzeroedAccountClose),
+        Box::new(pda_bump::NonCanonicalBump),
+    ]
+}
+
+/// Lint results
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    /// Findings ordered by severity (highest first), then address
+    pub findings: Vec<Finding>,
+}
+
+/// Run every lint rule
+pub fn analyze(ctx: &LintContext) -> LintReport {
+    // Library code is audited upstream; only report program-specific code
+    let in_program = |f: &Finding| ctx.cfg.function_at(f.function).is_none_or(|func| !func.library);
+    let mut findings: Vec<Finding> = all_rules()
+        .iter()
+        .flat_map(|rule| rule.check(ctx))
+        .filter(in_program)
+        .collect();
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.address.cmp(&b.address)));
+    LintReport { findings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::instruction::InstructionDecoder;
+    use sbpf_common::syscalls::syscall_hash;
+
+    #[test]
+    fn test_account_mutation_rules() {
+        let program: Vec<u8> = [
+            insn(0x71, 2, 1, 96, 0),  // ldxb r2, [r1+96]     account 0 data
+            insn(0x79, 3, 1, 80, 0),  // ldxdw r3, [r1+80]    account 0 lamports
+            insn(0x07, 3, 0, 0, 100), // add r3, 100
+            insn(0x7b, 1, 3, 80, 0),  // stxdw [r1+80], r3
+            insn(0x7a, 1, 0, 80, 0),  // stdw [r1+80], 0
+            insn(0x95, 0, 0, 0, 0),   // exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let report = analyze(&LintContext::new(&instructions, &cfg, &dataflow));
+
+        let rules: Vec<(&str, u64)> = report.findings.iter().map(|f| (f.rule, f.address)).collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("missing-owner-check", 0x00),
+                ("missing-signer-check", 0x18),
+                ("unchecked-lamports-arithmetic", 0x10),
+                ("unzeroed-account-close", 0x20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bump_checked_per_function() {
+        let find = syscall_hash("sol_try_find_program_address") as i32;
+        let create = syscall_hash("sol_create_program_address") as i32;
+        let program: Vec<u8> = [
+            insn(0x85, 0, 0, 0, find),   // 0x00: call sol_try_find_program_address
+            insn(0x85, 0, 0, 0, create), // 0x08: call sol_create_program_address
+            insn(0x85, 0, 1, 0, 1),      // 0x10: call 0x20
+            insn(0x95, 0, 0, 0, 0),      // 0x18: exit
+            insn(0x85, 0, 0, 0, create), // 0x20: call sol_create_program_address
+            insn(0x95, 0, 0, 0, 0),      // 0x28: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let report = analyze(&LintContext::new(&instructions, &cfg, &dataflow));
+
+        // Only the function that never finds the canonical bump is reported
+        let bumps: Vec<u64> =
+            report.findings.iter().filter(|f| f.rule == "non-canonical-bump").map(|f| f.address).collect();
+        assert_eq!(bumps, vec![0x20]);
+    }
+
+    /// Addresses reported by rule `id` in `program`
+    fn rule_findings(program: &[u8], id: &str) -> Vec<u64> {
+        let instructions = InstructionDecoder::new(None).decode_all(program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let report = analyze(&LintContext::new(&instructions, &cfg, &dataflow));
+        report.findings.iter().filter(|f| f.rule == id).map(|f| f.address).collect()
+    }
+
+    #[test]
+    fn test_missing_signer_check() {
+        let unchecked: Vec<u8> = [
+            insn(0x79, 3, 1, 80, 0),  // 0x00: ldxdw r3, [r1+80]    account 0 lamports
+            insn(0x7b, 1, 3, 80, 0),  // 0x08: stxdw [r1+80], r3
+            insn(0x95, 0, 0, 0, 0),   // 0x10: exit
+        ]
+        .concat();
+        assert_eq!(rule_findings(&unchecked, "missing-signer-check"), vec![0x08]);
+
+        let checked: Vec<u8> = [
+            insn(0x71, 2, 1, 9, 0),   // 0x00: ldxb r2, [r1+9]      account 0 is_signer
+            insn(0x79, 3, 1, 80, 0),  // 0x08: ldxdw r3, [r1+80]
+            insn(0x7b, 1, 3, 80, 0),  // 0x10: stxdw [r1+80], r3
+            insn(0x95, 0, 0, 0, 0),   // 0x18: exit
+        ]
+        .concat();
+        assert_eq!(rule_findings(&checked, "missing-signer-check"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_missing_owner_check() {
+        let unchecked: Vec<u8> = [
+            insn(0x71, 2, 1, 96, 0),  // 0x00: ldxb r2, [r1+96]     account 0 data
+            insn(0x95, 0, 0, 0, 0),   // 0x08: exit
+        ]
+        .concat();
+        assert_eq!(rule_findings(&unchecked, "missing-owner-check"), vec![0x00]);
+
+        let checked: Vec<u8> = [
+            insn(0x79, 3, 1, 48, 0),  // 0x00: ldxdw r3, [r1+48]    account 0 owner
+            insn(0x55, 3, 0, 2, 0),   // 0x08: jne r3, 0, +2
+            insn(0x71, 2, 1, 96, 0),  // 0x10: ldxb r2, [r1+96]
+            insn(0x95, 0, 0, 0, 0),   // 0x18: exit
+            insn(0x95, 0, 0, 0, 0),   // 0x20: exit
+        ]
+        .concat();
+        assert_eq!(rule_findings(&checked, "missing-owner-check"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_arbitrary_cpi() {
+        let invoke = syscall_hash("sol_invoke_signed_rust") as i32;
+        // Copy the key of account 0 into the program id of a
+        // `StableInstruction` at r10-128 and invoke it
+        let cpi = [
+            insn(0x79, 2, 1, 16, 0),   // ldxdw r2, [r1+16]
+            insn(0x7b, 10, 2, -80, 0), // stxdw [r10-80], r2
+            insn(0x79, 2, 1, 24, 0),   // ldxdw r2, [r1+24]
+            insn(0x7b, 10, 2, -72, 0), // stxdw [r10-72], r2
+            insn(0x79, 2, 1, 32, 0),   // ldxdw r2, [r1+32]
+            insn(0x7b, 10, 2, -64, 0), // stxdw [r10-64], r2
+            insn(0x79, 2, 1, 40, 0),   // ldxdw r2, [r1+40]
+            insn(0x7b, 10, 2, -56, 0), // stxdw [r10-56], r2
+            insn(0xbf, 1, 10, 0, 0),   // mov r1, r10
+            insn(0x07, 1, 0, 0, -128), // add r1, -128
+            insn(0x85, 0, 0, 0, invoke), // call sol_invoke_signed_rust
+            insn(0x95, 0, 0, 0, 0),      // exit
+        ];
+        assert_eq!(rule_findings(&cpi.concat(), "arbitrary-cpi"), vec![0x50]);
+
+        let validated: Vec<u8> = [
+            [
+                insn(0x79, 3, 1, 16, 0), // 0x00: ldxdw r3, [r1+16]    account 0 key
+                insn(0x55, 3, 0, 1, 0),  // 0x08: jne r3, 0, +1
+                insn(0x95, 0, 0, 0, 0),  // 0x10: exit
+            ]
+            .as_slice(),
+            &cpi,
+        ]
+        .concat()
+        .concat();
+        assert_eq!(rule_findings(&validated, "arbitrary-cpi"), Vec::<u64>::new());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::analyzer::lints::{AccessKind, Finding, LintContext, LintRule, Severity};
+use sbpf_common::input::AccountField;
+use std::collections::BTreeMap;
+
+/// Account data is used but the account owner is never compared
+///
+/// Without an owner check an attacker can pass an account with crafted data
+/// that is owned by a different program.
+pub struct MissingOwnerCheck;
+
+impl LintRule for MissingOwnerCheck {
+    fn id(&self) -> &'static str {
+        "missing-owner-check"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn description(&self) -> &'static str {
+        "Account data is accessed but the account owner is never compared against the program id"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let accesses = &ctx.field_accesses;
+
+        // First data access of each account
+        let mut data_access = BTreeMap::new();
+        for access in accesses {
+            if access.field == AccountField::Data {
+                data_access.entry(access.account).or_insert(access);
+            }
+        }
+
+        data_access
+            .into_iter()
+            .filter(|(account, _)| {
+                !accesses.iter().any(|a| {
+                    a.account == *account
+                        && a.field == AccountField::Owner
+                        && matches!(a.kind, AccessKind::Compared | AccessKind::Passed)
+                })
+            })
+            .map(|(account, access)| {
+                ctx.finding(
+                    self,
+                    access.function,
+                    access.address,
+                    format!(
+                        "data of account {} is used but its owner is never compared against the program id",
+                        account
+                    ),
+                )
+            })
+            .collect()
+    }
+}
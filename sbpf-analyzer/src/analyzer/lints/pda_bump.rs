@@ -0,0 +1,45 @@
+use crate::analyzer::lints::{Finding, LintContext, LintRule, Severity};
+use std::collections::BTreeSet;
+
+/// Program addresses created from a caller-supplied bump
+///
+/// `sol_create_program_address` accepts any bump that yields an off-curve
+/// address, so unless the function also derives the canonical bump with
+/// `sol_try_find_program_address` several addresses are valid for the same
+/// seeds.
+pub struct NonCanonicalBump;
+
+impl LintRule for NonCanonicalBump {
+    fn id(&self) -> &'static str {
+        "non-canonical-bump"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+
+    fn description(&self) -> &'static str {
+        "PDA is derived with a bump that is never checked to be canonical"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let finds: BTreeSet<usize> = ctx
+            .syscall_sites("sol_try_find_program_address")
+            .into_iter()
+            .map(|(_, function)| function)
+            .collect();
+        ctx.syscall_sites("sol_create_program_address")
+            .into_iter()
+            .filter(|(_, function)| !finds.contains(function))
+            .map(|(address, function)| {
+                ctx.finding(
+                    self,
+                    function,
+                    address,
+                    "program address is created from a bump that is never checked to be canonical"
+                        .to_string(),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,49 @@
+use crate::analyzer::lints::{AccessKind, Finding, LintContext, LintRule, Severity};
+use sbpf_common::input::AccountField;
+
+/// Accounts are modified but no `is_signer` flag is ever read
+pub struct MissingSignerCheck;
+
+impl LintRule for MissingSignerCheck {
+    fn id(&self) -> &'static str {
+        "missing-signer-check"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn description(&self) -> &'static str {
+        "Account state is modified but no account's is_signer flag is ever read"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Finding> {
+        let accesses = &ctx.field_accesses;
+
+        let signer_read = accesses.iter().any(|a| {
+            a.field == AccountField::IsSigner
+                && matches!(a.kind, AccessKind::Read | AccessKind::Compared)
+        });
+        if signer_read {
+            return Vec::new();
+        }
+
+        let first_write = accesses.iter().find(|a| {
+            a.kind == AccessKind::Write
+                && matches!(a.field, AccountField::Lamports | AccountField::Data)
+        });
+        match first_write {
+            Some(write) => vec![ctx.finding(
+                self,
+                write.function,
+                write.address,
+                format!(
+                    "{} of account {} is modified but no account's is_signer flag is ever read",
+                    write.field.name(),
+                    write.account
+                ),
+            )],
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::analyzer::lints::LintContext;
+use sbpf_common::binary::SbpfBinary;
+use sbpf_common::dataflow::Value;
+use sbpf_common::instruction::Instruction;
+use sbpf_common::syscall_args::{ArgValue, SyscallCall};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Size of a `&str` in a `fmt::Arguments` pieces array
+const PIECE_SIZE: u64 = 16;
+
+/// Most pieces read from one format string
+const MAX_PIECES: u64 = 16;
+
+/// Longest literal piece accepted
+const MAX_PIECE_LEN: u64 = 1024;
+
+/// How a message reaches the program log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogKind {
+    /// Constant passed to `sol_log_`
+    Log,
+    /// Constant slice passed to `sol_log_data`
+    Data,
+    /// Literal pieces of a `msg!`/`format!` string
+    Format,
+    /// Panic message or location
+    Panic,
+}
+
+impl LogKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogKind::Log => "log",
+            LogKind::Data => "data",
+            LogKind::Format => "format",
+            LogKind::Panic => "panic",
+        }
+    }
+}
+
+impl fmt::Display for LogKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A message the program can write to its log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    pub kind: LogKind,
+    /// Message text; format strings have `{}` between their pieces, and an
+    /// argument may also follow the last piece
+    pub text: String,
+    /// Address of the emitting call
+    pub address: u64,
+    /// Address of the containing function
+    pub function: u64,
+    pub function_name: String,
+}
+
+/// Check if a labelled function panics with a `&str` message in r1/r2
+pub fn is_panic_function(name: &str) -> bool {
+    name.starts_with("core::panicking::panic")
+        || name.ends_with("::expect_failed")
+        || name.ends_with("::unwrap_failed")
+}
+
+/// Text of the `len`-byte UTF-8 string at `address` in read-only data
+pub fn rodata_str(binary: &SbpfBinary, address: Value, len: Value) -> Option<&str> {
+    let (Value::Const(address), Value::Const(len)) = (address, len) else {
+        return None;
+    };
+    std::str::from_utf8(binary.read_rodata(address, usize::try_from(len).ok()?)?).ok()
+}
+
+/// Literal pieces of a `fmt::Arguments` with `count` pieces at `address`
+pub fn format_pieces(binary: &SbpfBinary, address: u64, count: u64) -> Option<Vec<&str>> {
+    if count == 0 || count > MAX_PIECES {
+        return None;
+    }
+    let pieces = (0..count)
+        .map(|i| {
+            let entry = binary.read_rodata(address + i * PIECE_SIZE, PIECE_SIZE as usize)?;
+            let ptr = u64::from_le_bytes(entry[..8].try_into().ok()?);
+            let len = u64::from_le_bytes(entry[8..].try_into().ok()?);
+            if len > MAX_PIECE_LEN {
+                return None;
+            }
+            rodata_str(binary, Value::Const(ptr), Value::Const(len))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    pieces.iter().any(|p| !p.is_empty()).then_some(pieces)
+}
+
+/// Catalog every message the program can log: constant `sol_log_` and
+/// `sol_log_data` arguments, format strings whose `fmt::Arguments` are
+/// built on the stack before a call, and panic messages and locations
+pub fn analyze(ctx: &LintContext, binary: &SbpfBinary, syscalls: &[SyscallCall]) -> Vec<LogMessage> {
+    let mut messages = Vec::new();
+    let mut push = |kind, text: String, address, function: u64, function_name: &str| {
+        messages.push(LogMessage {
+            kind,
+            text,
+            address,
+            function,
+            function_name: function_name.to_string(),
+        });
+    };
+
+    for call in syscalls {
+        let mut emit = |kind, text: String| push(kind, text, call.address, call.function, &call.function_name);
+        match call.signature.name {
+            "sol_log_" => {
+                if let Some(text) = call.str_arg("message") {
+                    emit(LogKind::Log, text.to_string());
+                }
+            }
+            "sol_log_data" => {
+                if let Some((_, ArgValue::Slices(slices))) = call.args.first() {
+                    for slice in slices {
+                        if let ArgValue::Str(text) = slice {
+                            emit(LogKind::Data, text.clone());
+                        }
+                    }
+                }
+            }
+            "sol_panic_" => {
+                if let Some(file) = call.str_arg("file") {
+                    let position: Vec<String> = call.args[1..]
+                        .iter()
+                        .filter_map(|(_, value)| match value {
+                            ArgValue::Const(n) => Some(n.to_string()),
+                            _ => None,
+                        })
+                        .collect();
+                    emit(LogKind::Panic, std::iter::once(file.to_string()).chain(position).collect::<Vec<_>>().join(":"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let cfg = ctx.cfg;
+    for function in &ctx.dataflow.functions {
+        let cfg_function = &cfg.functions[function.index];
+        if cfg_function.library {
+            continue;
+        }
+        let mut formats: BTreeSet<u64> = BTreeSet::new();
+        function.visit(cfg, ctx.instructions, |_, inst: &Instruction, state| {
+            if inst.call_target().is_none() && inst.syscall_number().is_none() {
+                return;
+            }
+            let mut emit = |kind, text: String| push(kind, text, inst.address, cfg_function.address, &cfg_function.name);
+            let callee = inst.call_target().and_then(|target| cfg.function_at(target));
+            if callee.is_some_and(|f| is_panic_function(&f.name)) {
+                if let Some(text) = rodata_str(binary, state.reg(1), state.reg(2)) {
+                    emit(LogKind::Panic, text.to_string());
+                }
+            }
+            // A `fmt::Arguments` starts with its pieces pointer and count
+            for (&offset, &value) in &state.stack {
+                let (Value::Const(address), Value::Const(count)) = (value, state.stack_slot(offset + 8)) else {
+                    continue;
+                };
+                if formats.contains(&address) {
+                    continue;
+                }
+                if let Some(pieces) = format_pieces(binary, address, count) {
+                    formats.insert(address);
+                    emit(LogKind::Format, pieces.join("{}"));
+                }
+            }
+        });
+    }
+
+    messages.sort_by(|a, b| (a.address, a.kind, &a.text).cmp(&(b.address, b.kind, &b.text)));
+    messages.dedup();
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::tests::insn;
+    use sbpf_common::binary::MetadataSection;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::dataflow::Dataflow;
+    use sbpf_common::instruction::InstructionDecoder;
+    use sbpf_common::syscall_args;
+    use sbpf_common::syscalls::syscall_hash;
+
+    #[test]
+    fn test_log_catalog() {
+        let log = syscall_hash("sol_log_") as u32 as i32;
+        let panic = syscall_hash("sol_panic_") as u32 as i32;
+        let program: Vec<u8> = [
+            insn(0x18, 1, 0, 0, 0x200),   // lddw r1, 0x200      "Instruction: Close"
+            insn(0x00, 0, 0, 0, 0),
+            insn(0xb7, 2, 0, 0, 18),      // mov r2, 18
+            insn(0x85, 0, 0, 0, log),     // call sol_log_
+            insn(0x18, 1, 0, 0, 0x220),   // lddw r1, 0x220      pieces ["amount: "]
+            insn(0x00, 0, 0, 0, 0),
+            insn(0x7b, 10, 1, -48, 0),    // stxdw [r10-48], r1
+            insn(0x7a, 10, 0, -40, 1),    // stdw [r10-40], 1
+            insn(0x85, 0, 0, 0, log),     // call sol_log_
+            insn(0x18, 1, 0, 0, 0x230),   // lddw r1, 0x230      "src/lib.rs"
+            insn(0x00, 0, 0, 0, 0),
+            insn(0xb7, 2, 0, 0, 10),      // mov r2, 10
+            insn(0xb7, 3, 0, 0, 42),      // mov r3, 42
+            insn(0xb7, 4, 0, 0, 5),       // mov r4, 5
+            insn(0x85, 0, 0, 0, panic),   // call sol_panic_
+            insn(0x95, 0, 0, 0, 0),       // exit
+        ]
+        .concat();
+        let mut rodata = b"Instruction: Close\0\0\0\0\0\0amount: ".to_vec();
+        rodata.extend_from_slice(&0x218u64.to_le_bytes());
+        rodata.extend_from_slice(&8u64.to_le_bytes());
+        rodata.extend_from_slice(b"src/lib.rs");
+        let binary = SbpfBinary {
+            bytecode: program.clone(),
+            entry_point: 0,
+            text_address: 0,
+            text_offset: 0,
+            version: None,
+            metadata: vec![MetadataSection {
+                name: ".rodata".to_string(),
+                address: 0x200,
+                data: rodata,
+            }],
+            symbols: Vec::new(),
+        };
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let syscalls = syscall_args::recover(&cfg, &instructions, &dataflow, &binary);
+        let ctx = LintContext::new(&instructions, &cfg, &dataflow);
+        let messages = analyze(&ctx, &binary, &syscalls);
+
+        let catalog: Vec<(u64, LogKind, &str)> =
+            messages.iter().map(|m| (m.address, m.kind, m.text.as_str())).collect();
+        assert_eq!(
+            catalog,
+            vec![
+                (0x18, LogKind::Log, "Instruction: Close"),
+                (0x40, LogKind::Format, "amount: "),
+                (0x70, LogKind::Panic, "src/lib.rs:42:5"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use sbpf_common::cfg::{ControlFlowGraph, DominatorTree, LoopForest};
+use sbpf_common::instruction::Instruction;
+
+/// Loop information
+#[derive(Debug, Clone)]
+pub struct LoopInfo {
+    /// Natural loops across all functions
+    pub loops: Vec<LoopSummary>,
+    /// Deepest loop nesting level
+    pub max_depth: usize,
+}
+
+/// A single natural loop
+#[derive(Debug, Clone)]
+pub struct LoopSummary {
+    /// Address of the containing function
+    pub function: u64,
+    /// Address of the loop header block
+    pub header: u64,
+    /// Nesting depth (1 for outermost loops)
+    pub depth: usize,
+    /// Number of basic blocks in the loop body
+    pub blocks: usize,
+    /// Addresses of the back edge sources
+    pub latches: Vec<u64>,
+    /// Addresses of the blocks control leaves the loop to
+    pub exits: Vec<u64>,
+    /// Upper bound on iterations, if derivable from constant comparisons
+    pub trip_count: Option<u64>,
+}
+
+/// Detect natural loops in every function
+pub fn analyze(cfg: &ControlFlowGraph, instructions: &[Instruction]) -> LoopInfo {
+    let mut loops = Vec::new();
+    
+    for function in &cfg.functions {
+        let domtree = DominatorTree::compute(cfg, function);
+        let forest = LoopForest::compute(cfg, function, &domtree, instructions);
+        
+        for l in &forest.loops {
+            let mut exits: Vec<u64> = l.exits.iter().map(|&(_, to)| cfg.blocks[to].start).collect();
+            exits.sort();
+            exits.dedup();
+            
+            loops.push(LoopSummary {
+                function: function.address,
+                header: cfg.blocks[l.header].start,
+                depth: l.depth,
+                blocks: l.body.len(),
+                latches: l.latches.iter().map(|&b| cfg.blocks[b].start).collect(),
+                exits,
+                trip_count: l.trip_count,
+            });
+        }
+    }
+    
+    LoopInfo {
+        max_depth: loops.iter().map(|l| l.depth).max().unwrap_or(0),
+        loops,
+    }
+}
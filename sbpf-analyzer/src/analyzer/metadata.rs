@@ -8,6 +8,10 @@ pub struct Metadata {
     pub program_size: usize,
     /// Entry point address
     pub entry_point: u64,
+    /// Virtual address of the bytecode
+    pub text_address: u64,
+    /// File offset of the bytecode
+    pub text_offset: u64,
     /// Total number of instructions
     pub instruction_count: usize,
     /// Detected SBPF version
@@ -21,6 +25,8 @@ pub fn extract(binary: &SbpfBinary, instructions: &[Instruction]) -> Metadata {
     Metadata {
         program_size: binary.bytecode.len(),
         entry_point: binary.entry_point,
+        text_address: binary.text_address,
+        text_offset: binary.text_offset,
         instruction_count: instructions.len(),
         version: binary.version,
         metadata_sections: binary.metadata.len(),
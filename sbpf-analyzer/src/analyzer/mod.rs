@@ -1,12 +1,40 @@
 pub mod metadata;
 pub mod instructions;
 pub mod syscalls;
+pub mod loops;
+pub mod lints;
+pub mod diagnostics;
+pub mod functions;
+pub mod diff;
+pub mod anchor;
+pub mod idl;
+pub mod cpi;
+pub mod accounts;
+pub mod logs;
+pub mod errors;
 
 use crate::analyzer::metadata::Metadata;
 use crate::analyzer::instructions::InstructionStats;
 use crate::analyzer::syscalls::SyscallInfo;
+use crate::analyzer::loops::LoopInfo;
+use crate::analyzer::lints::{LintContext, LintReport};
+use crate::analyzer::diagnostics::Diagnostic;
+use crate::analyzer::functions::FunctionInfo;
+use crate::analyzer::anchor::AnchorInfo;
+use crate::analyzer::idl::Idl;
+use crate::analyzer::cpi::CpiSite;
+use crate::analyzer::accounts::HandlerAccounts;
+use crate::analyzer::logs::LogMessage;
+use crate::analyzer::errors::FunctionFailures;
 use sbpf_common::binary::SbpfBinary;
+use sbpf_common::cfg::ControlFlowGraph;
+use sbpf_common::dataflow::Dataflow;
+use sbpf_common::dispatch::{self, Dispatch};
 use sbpf_common::instruction::InstructionDecoder;
+use sbpf_common::pda::{self, PdaDerivation};
+use sbpf_common::pubkey::{self, PubkeyConstant};
+use sbpf_common::syscall_args::{self, SyscallCall};
+use sbpf_common::signatures::SignatureDb;
 
 /// Analysis results for an SBPF binary
 #[derive(Debug, Clone)]
@@ -14,17 +42,89 @@ pub struct Analysis {
     pub metadata: Metadata,
     pub instruction_stats: InstructionStats,
     pub syscall_info: SyscallInfo,
+    pub loop_info: LoopInfo,
+    pub lint_report: LintReport,
+    pub diagnostics: Vec<Diagnostic>,
+    pub functions: Vec<FunctionInfo>,
+    pub anchor: AnchorInfo,
+    pub idl: Idl,
+    /// Native instruction dispatch, for non-Anchor programs
+    pub dispatch: Option<Dispatch>,
+    /// Cross-program invocation sites
+    pub cpi: Vec<CpiSite>,
+    /// Program derived address derivations with their seeds
+    pub pdas: Vec<PdaDerivation>,
+    /// Public keys in read-only data referenced by code
+    pub pubkeys: Vec<PubkeyConstant>,
+    /// Account fields read and written by each instruction handler
+    pub account_access: Vec<HandlerAccounts>,
+    /// Syscalls with their recovered arguments
+    pub syscall_calls: Vec<SyscallCall>,
+    /// Messages the program can write to its log
+    pub logs: Vec<LogMessage>,
+    /// Error codes and panic sites of each function
+    pub failures: Vec<FunctionFailures>,
+}
+
+/// Inputs that refine the analysis
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// Known library function signatures
+    pub signatures: SignatureDb,
+    /// Extra candidate instruction names for discriminator matching
+    pub wordlist: Vec<String>,
 }
 
 /// Analyze an SBPF binary
-pub fn analyze(binary: &SbpfBinary) -> Analysis {
+pub fn analyze(binary: &SbpfBinary, options: &AnalysisOptions) -> Analysis {
     let decoder = InstructionDecoder::new(binary.version);
     let instructions = decoder.decode_all(&binary.bytecode);
+    let mut cfg = ControlFlowGraph::build_with_symbols(&instructions, binary.entry_offset(), &binary.symbols);
+    options.signatures.label(&mut cfg, &instructions);
+    let names = anchor::candidate_names(binary, &options.wordlist);
+    let dataflow = Dataflow::compute(&cfg, &instructions, binary.entry_offset());
+    let anchor = anchor::analyze(&cfg, &instructions, &dataflow, &names);
+    let mut native_dispatch = None;
+    if !anchor.is_anchor {
+        native_dispatch = dispatch::recover(&cfg, &instructions, &dataflow);
+        if let Some(dispatch) = &mut native_dispatch {
+            dispatch.name_handlers(&mut cfg);
+        }
+    }
+    let pdas = pda::recover(&cfg, &instructions, &dataflow, binary, native_dispatch.as_ref());
+    let syscall_calls = syscall_args::recover(&cfg, &instructions, &dataflow, binary);
+    let lint_context = LintContext::new(&instructions, &cfg, &dataflow);
     
     Analysis {
         metadata: metadata::extract(binary, &instructions),
         instruction_stats: instructions::analyze(&instructions),
         syscall_info: syscalls::analyze(&instructions),
+        loop_info: loops::analyze(&cfg, &instructions),
+        lint_report: lints::analyze(&lint_context),
+        diagnostics: diagnostics::analyze(&binary.bytecode, &instructions),
+        functions: functions::analyze(&cfg, &instructions),
+        idl: idl::analyze(&lint_context, &anchor, &names),
+        cpi: cpi::analyze(&lint_context, binary),
+        account_access: accounts::analyze(&lint_context, &anchor, native_dispatch.as_ref(), binary.entry_offset()),
+        pdas,
+        pubkeys: pubkey::recover(&cfg, &instructions, &dataflow, binary),
+        logs: logs::analyze(&lint_context, binary, &syscall_calls),
+        failures: errors::analyze(&lint_context, binary, binary.entry_offset()),
+        syscall_calls,
+        anchor,
+        dispatch: native_dispatch,
     }
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    /// Encode a single instruction slot
+    pub(crate) fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[0] = code;
+        bytes[1] = (src << 4) | dst;
+        bytes[2..4].copy_from_slice(&off.to_le_bytes());
+        bytes[4..8].copy_from_slice(&imm.to_le_bytes());
+        bytes
+    }
+}
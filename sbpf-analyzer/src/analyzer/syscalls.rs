@@ -37,24 +37,5 @@ pub fn analyze(instructions: &[Instruction]) -> SyscallInfo {
 
 /// Map syscall number to name (Solana syscalls)
 pub fn syscall_name(num: u64) -> Option<&'static str> {
-    match num {
-        0 => Some("sol_log"),
-        1 => Some("sol_invoke"),
-        2 => Some("sol_invoke_signed"),
-        3 => Some("sol_create_account"),
-        4 => Some("sol_assign"),
-        5 => Some("sol_transfer"),
-        6 => Some("sol_get_account_data_len"),
-        7 => Some("sol_get_account_data"),
-        8 => Some("sol_set_account_data"),
-        9 => Some("sol_get_clock_sysvar"),
-        10 => Some("sol_get_rent_sysvar"),
-        11 => Some("sol_get_clock_sysvar"),
-        12 => Some("sol_memcpy"),
-        13 => Some("sol_memcmp"),
-        14 => Some("sol_memset"),
-        15 => Some("sol_invoke_signed"),
-        _ => None,
-    }
+    sbpf_common::syscalls::syscall_name(num)
 }
-
@@ -1,11 +1,15 @@
 mod analyzer;
 mod output;
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use sbpf_common::binary::load_binary;
-use crate::analyzer::analyze;
-use crate::output::{JsonFormatter, OutputFormatter, TextFormatter};
+use sbpf_common::cfg::ControlFlowGraph;
+use sbpf_common::instruction::InstructionDecoder;
+use sbpf_common::signatures::SignatureDb;
+use crate::analyzer::{analyze, AnalysisOptions};
+use crate::analyzer::diff::diff;
+use crate::output::{DiffFormatter, IdlFormatter, JsonFormatter, LogsFormatter, OutputFormatter, SarifFormatter, TextFormatter};
 
 /// SBPF Binary Analyzer
 /// 
@@ -15,42 +19,111 @@ use crate::output::{JsonFormatter, OutputFormatter, TextFormatter};
 #[command(name = "sbpf-analyzer")]
 #[command(version = "0.1.0")]
 #[command(about = "Analyze SBPF binaries")]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
     /// Path to the SBPF binary file
     #[arg(required = true)]
-    binary: String,
+    binary: Option<String>,
     
-    /// Output format (text or json)
-    #[arg(short, long, default_value = "text")]
+    /// Output format (text, json, sarif, idl or logs)
+    #[arg(short, long, default_value = "text", global = true)]
     format: String,
     
-    /// Verbose output
+    /// Library signature file used to label statically linked functions
+    /// (may be repeated)
+    #[arg(short, long, global = true)]
+    signatures: Vec<String>,
+    
+    /// File of candidate instruction names, one per line, for matching
+    /// Anchor discriminators
     #[arg(short, long)]
+    wordlist: Option<String>,
+    
+    /// Verbose output
+    #[arg(short, long, global = true)]
     verbose: bool,
+    
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two versions of a program (text or json output)
+    Diff {
+        /// Path to the old SBPF binary
+        old: String,
+        /// Path to the new SBPF binary
+        new: String,
+    },
+    /// Generate library signatures from unstripped builds
+    Signatures {
+        /// Paths to unstripped SBPF binaries
+        #[arg(required = true)]
+        binaries: Vec<String>,
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
     
+    match &args.command {
+        Some(Command::Diff { old, new }) => return run_diff(&args, old, new),
+        Some(Command::Signatures { binaries, output }) => {
+            return run_signatures(&args, binaries, output.as_deref())
+        }
+        None => {}
+    }
+    let path = args.binary.as_deref().unwrap_or_default();
+    
     if args.verbose {
-        eprintln!("Loading binary: {}", args.binary);
+        eprintln!("Loading binary: {}", path);
     }
     
-    let binary = load_binary(&args.binary)
-        .with_context(|| format!("Failed to load binary: {}", args.binary))?;
+    let binary = load_binary(path)
+        .with_context(|| format!("Failed to load binary: {}", path))?;
+    
+    let mut options = AnalysisOptions {
+        signatures: load_signatures(&args)?,
+        ..Default::default()
+    };
+    if let Some(path) = &args.wordlist {
+        let words = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read wordlist: {}", path))?;
+        options.wordlist = words.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect();
+    }
     
     if args.verbose {
         eprintln!("Analyzing binary...");
     }
     
-    let analysis = analyze(&binary);
+    let analysis = analyze(&binary, &options);
     
     let output = match args.format.as_str() {
         "json" => {
             let formatter = JsonFormatter;
             formatter.format(&analysis)
         }
-        "text" | _ => {
+        "sarif" => {
+            let formatter = SarifFormatter::new(path);
+            formatter.format(&analysis)
+        }
+        "logs" => {
+            let formatter = LogsFormatter;
+            formatter.format(&analysis)
+        }
+        "idl" => {
+            let name = std::path::Path::new(path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let formatter = IdlFormatter::new(&name);
+            formatter.format(&analysis)
+        }
+        _ => {
             let formatter = TextFormatter;
             formatter.format(&analysis)
         }
@@ -60,3 +133,66 @@ fn main() -> Result<()> {
     
     Ok(())
 }
+
+fn run_diff(args: &Args, old: &str, new: &str) -> Result<()> {
+    if !matches!(args.format.as_str(), "text" | "json") {
+        bail!("Unsupported format for diff: {} (expected text or json)", args.format);
+    }
+    
+    if args.verbose {
+        eprintln!("Loading binaries: {} {}", old, new);
+    }
+    
+    let old = load_binary(old)
+        .with_context(|| format!("Failed to load binary: {}", old))?;
+    let new = load_binary(new)
+        .with_context(|| format!("Failed to load binary: {}", new))?;
+    
+    let signatures = load_signatures(args)?;
+    let report = diff(&old, &new, &signatures);
+    
+    let output = match args.format.as_str() {
+        "json" => JsonFormatter.format_diff(&report),
+        _ => TextFormatter.format_diff(&report),
+    };
+    
+    println!("{}", output);
+    
+    Ok(())
+}
+
+fn run_signatures(args: &Args, binaries: &[String], output: Option<&str>) -> Result<()> {
+    let mut signatures = SignatureDb::new();
+    for path in binaries {
+        if args.verbose {
+            eprintln!("Collecting signatures: {}", path);
+        }
+        
+        let binary = load_binary(path)
+            .with_context(|| format!("Failed to load binary: {}", path))?;
+        let instructions = InstructionDecoder::new(binary.version).decode_all(&binary.bytecode);
+        let cfg = ControlFlowGraph::build_with_symbols(&instructions, binary.entry_offset(), &binary.symbols);
+        signatures.merge(&SignatureDb::generate(&cfg, &instructions));
+    }
+    
+    if let Some(output_path) = output {
+        std::fs::write(output_path, signatures.to_text())
+            .with_context(|| format!("Failed to write output to: {}", output_path))?;
+        if args.verbose {
+            eprintln!("{} signatures written to: {}", signatures.len(), output_path);
+        }
+    } else {
+        print!("{}", signatures.to_text());
+    }
+    
+    Ok(())
+}
+
+/// Load and merge every `--signatures` file
+fn load_signatures(args: &Args) -> Result<SignatureDb> {
+    let mut signatures = SignatureDb::new();
+    for path in &args.signatures {
+        signatures.merge(&SignatureDb::load(path)?);
+    }
+    Ok(signatures)
+}
@@ -0,0 +1,155 @@
+use crate::analyzer::Analysis;
+use crate::output::OutputFormatter;
+use serde::Serialize;
+use serde_json::json;
+
+/// Version recorded in the IDL metadata
+const IDL_SPEC: &str = "0.1.0";
+
+/// Anchor IDL formatter for recovered instructions and account types
+pub struct IdlFormatter {
+    /// Program name, as reported in the IDL metadata
+    name: String,
+}
+
+impl IdlFormatter {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl OutputFormatter for IdlFormatter {
+    fn format(&self, analysis: &Analysis) -> String {
+        let idl = self.build_idl(analysis);
+        serde_json::to_string_pretty(&idl).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct IdlJson {
+    address: String,
+    metadata: IdlMetadata,
+    instructions: Vec<IdlInstructionJson>,
+    accounts: Vec<IdlAccountTypeJson>,
+    types: Vec<IdlTypeDefJson>,
+}
+
+#[derive(Serialize)]
+struct IdlMetadata {
+    name: String,
+    version: &'static str,
+    spec: &'static str,
+    description: &'static str,
+}
+
+#[derive(Serialize)]
+struct IdlInstructionJson {
+    name: String,
+    discriminator: [u8; 8],
+    accounts: Vec<IdlAccountJson>,
+    args: Vec<IdlArgJson>,
+}
+
+#[derive(Serialize)]
+struct IdlAccountJson {
+    name: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    writable: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    signer: bool,
+}
+
+#[derive(Serialize)]
+struct IdlArgJson {
+    name: String,
+    #[serde(rename = "type")]
+    ty: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct IdlAccountTypeJson {
+    name: String,
+    discriminator: [u8; 8],
+}
+
+/// Type definition referenced by an account type
+#[derive(Serialize)]
+struct IdlTypeDefJson {
+    name: String,
+    docs: Vec<&'static str>,
+    #[serde(rename = "type")]
+    ty: serde_json::Value,
+}
+
+/// IDL type of an argument spanning `size` bytes
+fn arg_type(size: u64) -> serde_json::Value {
+    match size {
+        1 => json!("u8"),
+        2 => json!("u16"),
+        4 => json!("u32"),
+        8 => json!("u64"),
+        _ => json!({ "array": ["u8", size] }),
+    }
+}
+
+impl IdlFormatter {
+    fn build_idl(&self, analysis: &Analysis) -> IdlJson {
+        let accounts: Vec<IdlAccountTypeJson> = analysis.idl.accounts
+            .iter()
+            .map(|a| IdlAccountTypeJson {
+                name: a.name.clone().unwrap_or_else(|| {
+                    let hex: String = a.discriminator.iter().map(|b| format!("{:02x}", b)).collect();
+                    format!("Account{}", hex)
+                }),
+                discriminator: a.discriminator,
+            })
+            .collect();
+        // Every account names a type definition; the layout is unknown
+        let types = accounts
+            .iter()
+            .map(|a| IdlTypeDefJson {
+                name: a.name.clone(),
+                docs: vec!["Fields not recovered"],
+                ty: json!({ "kind": "struct", "fields": [] }),
+            })
+            .collect();
+        IdlJson {
+            address: String::new(),
+            metadata: IdlMetadata {
+                name: self.name.clone(),
+                version: "0.1.0",
+                spec: IDL_SPEC,
+                description: "Recovered by sbpf-analyzer",
+            },
+            instructions: analysis.idl.instructions
+                .iter()
+                .map(|ix| IdlInstructionJson {
+                    name: ix.name.clone(),
+                    discriminator: ix.discriminator,
+                    accounts: ix.accounts
+                        .iter()
+                        .map(|a| IdlAccountJson {
+                            name: format!("account_{}", a.index),
+                            writable: a.writable,
+                            signer: a.signer,
+                        })
+                        .collect(),
+                    args: ix.args
+                        .iter()
+                        .map(|arg| IdlArgJson {
+                            name: match arg.padding {
+                                true => format!("padding_{}", arg.offset),
+                                false => format!("arg_{}", arg.offset),
+                            },
+                            ty: arg_type(arg.size),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            accounts,
+            types,
+        }
+    }
+}
@@ -1,7 +1,12 @@
+use crate::analyzer::cpi::{CpiProgram, SignerSeeds};
+use crate::analyzer::diff::{DiffLine, DiffReport};
+use crate::analyzer::errors::ErrorCode;
+use crate::analyzer::functions::FunctionInfo;
+use crate::analyzer::logs::LogMessage;
 use crate::analyzer::Analysis;
-use crate::output::OutputFormatter;
+use crate::output::{DiffFormatter, OutputFormatter};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct JsonFormatter;
 
@@ -12,11 +17,41 @@ impl OutputFormatter for JsonFormatter {
     }
 }
 
+impl DiffFormatter for JsonFormatter {
+    fn format_diff(&self, report: &DiffReport) -> String {
+        let json = DiffJson::from(report);
+        serde_json::to_string_pretty(&json).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Log message catalog formatter, for matching program logs to code
+pub struct LogsFormatter;
+
+impl OutputFormatter for LogsFormatter {
+    fn format(&self, analysis: &Analysis) -> String {
+        let logs: Vec<LogJson> = analysis.logs.iter().map(LogJson::from).collect();
+        serde_json::to_string_pretty(&logs).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
 #[derive(Serialize)]
 struct AnalysisJson {
     metadata: MetadataJson,
     instructions: InstructionStatsJson,
     syscalls: SyscallInfoJson,
+    loops: LoopInfoJson,
+    findings: Vec<FindingJson>,
+    diagnostics: Vec<DiagnosticJson>,
+    functions: Vec<FunctionJson>,
+    anchor: AnchorJson,
+    dispatch: Option<DispatchJson>,
+    cpi: Vec<CpiJson>,
+    pdas: Vec<PdaJson>,
+    pubkeys: Vec<PubkeyJson>,
+    account_access: Vec<HandlerAccountsJson>,
+    syscall_calls: Vec<SyscallCallJson>,
+    logs: Vec<LogJson>,
+    failures: Vec<FunctionFailuresJson>,
 }
 
 #[derive(Serialize)]
@@ -51,6 +86,286 @@ struct SyscallInfoJson {
     syscalls: Vec<u64>,
 }
 
+#[derive(Serialize)]
+struct LoopInfoJson {
+    max_depth: usize,
+    loops: Vec<LoopJson>,
+}
+
+#[derive(Serialize)]
+struct LoopJson {
+    function: String,
+    header: String,
+    depth: usize,
+    blocks: usize,
+    latches: Vec<String>,
+    exits: Vec<String>,
+    trip_count: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct FindingJson {
+    rule: String,
+    severity: String,
+    address: String,
+    function: String,
+    function_name: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct DiagnosticJson {
+    code: String,
+    category: String,
+    address: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct FunctionJson {
+    address: String,
+    name: String,
+    instructions: usize,
+    blocks: usize,
+    syscalls: BTreeMap<String, usize>,
+    cu_estimate: u64,
+    library: bool,
+    structural_hash: String,
+}
+
+#[derive(Serialize)]
+struct AnchorJson {
+    is_anchor: bool,
+    instructions: Vec<AnchorInstructionJson>,
+}
+
+#[derive(Serialize)]
+struct AnchorInstructionJson {
+    name: Option<String>,
+    discriminator: [u8; 8],
+    dispatcher: String,
+    compare: String,
+    handler: String,
+    handler_name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DispatchJson {
+    function: String,
+    tag_location: String,
+    tag_size: u8,
+    cases: Vec<DispatchCaseJson>,
+}
+
+#[derive(Serialize)]
+struct DispatchCaseJson {
+    tag: u64,
+    compare: String,
+    handler: String,
+    handler_name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CpiJson {
+    address: String,
+    function: String,
+    function_name: String,
+    syscall: &'static str,
+    program: String,
+    /// Constant program id, in base58
+    program_id: Option<String>,
+    /// Account whose key is the program id
+    program_account: Option<u16>,
+    discriminator: Option<Vec<u8>>,
+    instruction: Option<&'static str>,
+    data_len: Option<u64>,
+    /// Number of PDA signers, 0 for plain `invoke`
+    signers: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct PdaJson {
+    address: String,
+    function: String,
+    function_name: String,
+    syscall: &'static str,
+    /// Symbolic seeds, if the seeds array was found
+    seeds: Option<Vec<String>>,
+    program: String,
+    derivation: String,
+}
+
+#[derive(Serialize)]
+struct PubkeyJson {
+    address: String,
+    /// Base58 public key
+    pubkey: String,
+    name: Option<&'static str>,
+    references: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HandlerAccountsJson {
+    name: String,
+    handler: String,
+    accounts: Vec<AccountAccessJson>,
+}
+
+#[derive(Serialize)]
+struct AccountAccessJson {
+    index: u16,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct SyscallCallJson {
+    address: String,
+    function: String,
+    function_name: String,
+    library: bool,
+    syscall: &'static str,
+    /// Rendered arguments by parameter name
+    args: BTreeMap<&'static str, String>,
+    call: String,
+}
+
+/// Entry of the log message catalog
+#[derive(Serialize)]
+struct LogJson {
+    kind: &'static str,
+    text: String,
+    address: String,
+    function: String,
+    function_name: String,
+}
+
+impl From<&LogMessage> for LogJson {
+    fn from(message: &LogMessage) -> Self {
+        LogJson {
+            kind: message.kind.name(),
+            text: message.text.clone(),
+            address: format!("0x{:x}", message.address),
+            function: format!("0x{:x}", message.function),
+            function_name: message.function_name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FunctionFailuresJson {
+    function: String,
+    name: String,
+    codes: Vec<ErrorCodeJson>,
+    panics: Vec<PanicSiteJson>,
+}
+
+#[derive(Serialize)]
+struct ErrorCodeJson {
+    code: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct PanicSiteJson {
+    address: String,
+    block: String,
+    kind: &'static str,
+    callee: String,
+    message: Option<String>,
+    /// `file:line:column`
+    location: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DiffJson {
+    added: Vec<FunctionJson>,
+    removed: Vec<FunctionJson>,
+    modified: Vec<FunctionChangeJson>,
+    unchanged: usize,
+    syscall_changes: Vec<SyscallChangeJson>,
+    old_cu: u64,
+    new_cu: u64,
+}
+
+#[derive(Serialize)]
+struct FunctionChangeJson {
+    name: String,
+    old_address: String,
+    new_address: String,
+    matched_by: String,
+    old_cu: u64,
+    new_cu: u64,
+    lines: Vec<DiffLineJson>,
+}
+
+#[derive(Serialize)]
+struct DiffLineJson {
+    op: &'static str,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct SyscallChangeJson {
+    name: String,
+    old: usize,
+    new: usize,
+}
+
+impl From<&FunctionInfo> for FunctionJson {
+    fn from(f: &FunctionInfo) -> Self {
+        FunctionJson {
+            address: format!("0x{:x}", f.address),
+            name: f.name.clone(),
+            instructions: f.instructions,
+            blocks: f.blocks,
+            syscalls: f.syscalls.clone(),
+            cu_estimate: f.cu_estimate,
+            library: f.library,
+            structural_hash: format!("{:016x}", f.structural_hash),
+        }
+    }
+}
+
+impl From<&DiffReport> for DiffJson {
+    fn from(report: &DiffReport) -> Self {
+        DiffJson {
+            added: report.added.iter().map(FunctionJson::from).collect(),
+            removed: report.removed.iter().map(FunctionJson::from).collect(),
+            modified: report.modified
+                .iter()
+                .map(|c| FunctionChangeJson {
+                    name: c.new.name.clone(),
+                    old_address: format!("0x{:x}", c.old.address),
+                    new_address: format!("0x{:x}", c.new.address),
+                    matched_by: format!("{:?}", c.matched_by).to_lowercase(),
+                    old_cu: c.old.cu_estimate,
+                    new_cu: c.new.cu_estimate,
+                    lines: c.lines
+                        .iter()
+                        .map(|l| match l {
+                            DiffLine::Same(text) => DiffLineJson { op: "same", text: text.clone() },
+                            DiffLine::Removed(text) => DiffLineJson { op: "removed", text: text.clone() },
+                            DiffLine::Added(text) => DiffLineJson { op: "added", text: text.clone() },
+                        })
+                        .collect(),
+                })
+                .collect(),
+            unchanged: report.unchanged,
+            syscall_changes: report.syscall_changes
+                .iter()
+                .map(|c| SyscallChangeJson {
+                    name: c.name.clone(),
+                    old: c.old,
+                    new: c.new,
+                })
+                .collect(),
+            old_cu: report.old_cu,
+            new_cu: report.new_cu,
+        }
+    }
+}
+
 impl From<&Analysis> for AnalysisJson {
     fn from(analysis: &Analysis) -> Self {
         AnalysisJson {
@@ -86,6 +401,181 @@ impl From<&Analysis> for AnalysisJson {
                     .collect(),
                 syscalls: analysis.syscall_info.syscalls.clone(),
             },
+            loops: LoopInfoJson {
+                max_depth: analysis.loop_info.max_depth,
+                loops: analysis.loop_info.loops
+                    .iter()
+                    .map(|l| LoopJson {
+                        function: format!("0x{:x}", l.function),
+                        header: format!("0x{:x}", l.header),
+                        depth: l.depth,
+                        blocks: l.blocks,
+                        latches: l.latches.iter().map(|a| format!("0x{:x}", a)).collect(),
+                        exits: l.exits.iter().map(|a| format!("0x{:x}", a)).collect(),
+                        trip_count: l.trip_count,
+                    })
+                    .collect(),
+            },
+            findings: analysis.lint_report.findings
+                .iter()
+                .map(|f| FindingJson {
+                    rule: f.rule.to_string(),
+                    severity: format!("{:?}", f.severity).to_lowercase(),
+                    address: format!("0x{:x}", f.address),
+                    function: format!("0x{:x}", f.function),
+                    function_name: f.function_name.clone(),
+                    message: f.message.clone(),
+                })
+                .collect(),
+            diagnostics: analysis.diagnostics
+                .iter()
+                .map(|d| DiagnosticJson {
+                    code: d.code.id().to_string(),
+                    category: format!("{:?}", d.code.category()).to_lowercase(),
+                    address: format!("0x{:x}", d.address),
+                    message: d.message.clone(),
+                })
+                .collect(),
+            functions: analysis.functions.iter().map(FunctionJson::from).collect(),
+            anchor: AnchorJson {
+                is_anchor: analysis.anchor.is_anchor,
+                instructions: analysis.anchor.instructions
+                    .iter()
+                    .map(|ix| AnchorInstructionJson {
+                        name: ix.name.clone(),
+                        discriminator: ix.discriminator,
+                        dispatcher: format!("0x{:x}", ix.dispatcher),
+                        compare: format!("0x{:x}", ix.compare),
+                        handler: format!("0x{:x}", ix.handler),
+                        handler_name: ix.handler_name.clone(),
+                    })
+                    .collect(),
+            },
+            dispatch: analysis.dispatch.as_ref().map(|d| DispatchJson {
+                function: format!("0x{:x}", d.function),
+                tag_location: d.tag_location.to_string(),
+                tag_size: d.tag_size,
+                cases: d.cases
+                    .iter()
+                    .map(|c| DispatchCaseJson {
+                        tag: c.tag,
+                        compare: format!("0x{:x}", c.compare),
+                        handler: format!("0x{:x}", c.handler),
+                        handler_name: c.handler_name.clone(),
+                    })
+                    .collect(),
+            }),
+            cpi: analysis
+                .cpi
+                .iter()
+                .map(|site| CpiJson {
+                    address: format!("0x{:x}", site.address),
+                    function: format!("0x{:x}", site.function),
+                    function_name: site.function_name.clone(),
+                    syscall: site.syscall,
+                    program: site.program.to_string(),
+                    program_id: match &site.program {
+                        CpiProgram::Constant(id) => Some(id.to_base58()),
+                        _ => None,
+                    },
+                    program_account: match site.program {
+                        CpiProgram::Account(index) => Some(index),
+                        _ => None,
+                    },
+                    discriminator: site.discriminator.clone(),
+                    instruction: site.instruction,
+                    data_len: site.data_len,
+                    signers: match site.signer_seeds {
+                        SignerSeeds::Absent => Some(0),
+                        SignerSeeds::Present(n) => Some(n),
+                        SignerSeeds::Unknown => None,
+                    },
+                })
+                .collect(),
+            pdas: analysis
+                .pdas
+                .iter()
+                .map(|pda| PdaJson {
+                    address: format!("0x{:x}", pda.address),
+                    function: format!("0x{:x}", pda.function),
+                    function_name: pda.function_name.clone(),
+                    syscall: pda.syscall,
+                    seeds: pda.seeds.as_ref().map(|seeds| seeds.iter().map(|s| s.to_string()).collect()),
+                    program: pda.program.to_string(),
+                    derivation: pda.to_string(),
+                })
+                .collect(),
+            account_access: analysis
+                .account_access
+                .iter()
+                .map(|handler| HandlerAccountsJson {
+                    name: handler.name.clone(),
+                    handler: format!("0x{:x}", handler.handler),
+                    accounts: handler
+                        .accounts
+                        .iter()
+                        .map(|account| AccountAccessJson {
+                            index: account.index,
+                            reads: account.reads.iter().map(|f| f.name()).collect(),
+                            writes: account.writes.iter().map(|f| f.name()).collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            syscall_calls: analysis
+                .syscall_calls
+                .iter()
+                .map(|call| SyscallCallJson {
+                    address: format!("0x{:x}", call.address),
+                    function: format!("0x{:x}", call.function),
+                    function_name: call.function_name.clone(),
+                    library: call.library,
+                    syscall: call.signature.name,
+                    args: call.args.iter().map(|(name, value)| (*name, value.to_string())).collect(),
+                    call: call.to_string(),
+                })
+                .collect(),
+            logs: analysis.logs.iter().map(LogJson::from).collect(),
+            failures: analysis
+                .failures
+                .iter()
+                .map(|function| FunctionFailuresJson {
+                    function: format!("0x{:x}", function.function),
+                    name: function.name.clone(),
+                    codes: function
+                        .codes
+                        .iter()
+                        .filter_map(|&code| {
+                            ErrorCode::from_code(code).map(|error| ErrorCodeJson {
+                                code: format!("0x{:x}", code),
+                                error: error.to_string(),
+                            })
+                        })
+                        .collect(),
+                    panics: function
+                        .panics
+                        .iter()
+                        .map(|site| PanicSiteJson {
+                            address: format!("0x{:x}", site.address),
+                            block: format!("0x{:x}", site.block),
+                            kind: site.kind.name(),
+                            callee: site.callee.clone(),
+                            message: site.message.clone(),
+                            location: site.location.as_ref().map(|l| l.to_string()),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            pubkeys: analysis
+                .pubkeys
+                .iter()
+                .map(|constant| PubkeyJson {
+                    address: format!("0x{:x}", constant.address),
+                    pubkey: constant.pubkey.to_base58(),
+                    name: constant.pubkey.name(),
+                    references: constant.references.iter().map(|r| format!("0x{:x}", r)).collect(),
+                })
+                .collect(),
         }
     }
 }
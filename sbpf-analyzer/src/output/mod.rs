@@ -1,12 +1,21 @@
 pub mod text;
 pub mod json;
+pub mod sarif;
+pub mod idl;
 
+use crate::analyzer::diff::DiffReport;
 use crate::analyzer::Analysis;
 
 pub trait OutputFormatter {
     fn format(&self, analysis: &Analysis) -> String;
 }
 
+pub trait DiffFormatter {
+    fn format_diff(&self, report: &DiffReport) -> String;
+}
+
 pub use text::TextFormatter;
-pub use json::JsonFormatter;
+pub use json::{JsonFormatter, LogsFormatter};
+pub use sarif::SarifFormatter;
+pub use idl::IdlFormatter;
 
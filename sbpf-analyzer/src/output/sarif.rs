@@ -0,0 +1,308 @@
+use crate::analyzer::diagnostics::{DiagnosticCategory, DiagnosticCode};
+use crate::analyzer::lints::{all_rules, Severity};
+use crate::analyzer::Analysis;
+use crate::output::OutputFormatter;
+use serde::Serialize;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// SARIF 2.1.0 formatter for lint findings and diagnostics
+pub struct SarifFormatter {
+    /// URI of the analyzed binary, as reported in result locations
+    artifact_uri: String,
+}
+
+impl SarifFormatter {
+    pub fn new(artifact_uri: &str) -> Self {
+        Self {
+            artifact_uri: artifact_uri.to_string(),
+        }
+    }
+}
+
+impl OutputFormatter for SarifFormatter {
+    fn format(&self, analysis: &Analysis) -> String {
+        let log = self.build_log(analysis);
+        serde_json::to_string_pretty(&log).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    artifacts: Vec<Artifact>,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: String,
+    short_description: Message,
+    default_configuration: Configuration,
+    properties: RuleProperties,
+}
+
+#[derive(Serialize)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Serialize)]
+struct RuleProperties {
+    category: &'static str,
+}
+
+#[derive(Serialize)]
+struct Artifact {
+    location: ArtifactLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: String,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    logical_locations: Vec<LogicalLocation>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+    address: Address,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    byte_offset: u64,
+    byte_length: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Address {
+    absolute_address: u64,
+    relative_address: u64,
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogicalLocation {
+    name: String,
+    kind: &'static str,
+}
+
+/// Map a severity to a SARIF result level
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low => "note",
+    }
+}
+
+impl SarifFormatter {
+    fn build_log(&self, analysis: &Analysis) -> SarifLog {
+        let lint_rules = all_rules();
+        let mut rules: Vec<Rule> = lint_rules
+            .iter()
+            .map(|rule| Rule {
+                id: rule.id().to_string(),
+                short_description: Message {
+                    text: rule.description().to_string(),
+                },
+                default_configuration: Configuration {
+                    level: level(rule.severity()),
+                },
+                properties: RuleProperties { category: "lint" },
+            })
+            .collect();
+        rules.extend(DiagnosticCode::ALL.iter().map(|code| Rule {
+            id: code.id().to_string(),
+            short_description: Message {
+                text: code.description().to_string(),
+            },
+            default_configuration: Configuration {
+                level: level(code.severity()),
+            },
+            properties: RuleProperties {
+                category: match code.category() {
+                    DiagnosticCategory::Decode => "decode",
+                    DiagnosticCategory::Verifier => "verifier",
+                },
+            },
+        }));
+        let rule_index = |id: &str| rules.iter().position(|r| r.id == id).unwrap_or(0);
+
+        let mut results = Vec::new();
+        for finding in &analysis.lint_report.findings {
+            results.push(SarifResult {
+                rule_id: finding.rule.to_string(),
+                rule_index: rule_index(finding.rule),
+                level: level(finding.severity),
+                message: Message {
+                    text: finding.message.clone(),
+                },
+                locations: vec![self.location(
+                    analysis,
+                    finding.address,
+                    finding.size,
+                    Some(finding.function_name.clone()),
+                )],
+            });
+        }
+        for diagnostic in &analysis.diagnostics {
+            results.push(SarifResult {
+                rule_id: diagnostic.code.id().to_string(),
+                rule_index: rule_index(diagnostic.code.id()),
+                level: level(diagnostic.code.severity()),
+                message: Message {
+                    text: diagnostic.message.clone(),
+                },
+                locations: vec![self.location(analysis, diagnostic.address, diagnostic.size, None)],
+            });
+        }
+
+        SarifLog {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "sbpf-analyzer",
+                        version: env!("CARGO_PKG_VERSION"),
+                        rules,
+                    },
+                },
+                artifacts: vec![Artifact {
+                    location: ArtifactLocation {
+                        uri: self.artifact_uri.clone(),
+                        index: None,
+                    },
+                }],
+                results,
+            }],
+        }
+    }
+
+    /// Location of the `size`-byte instruction at bytecode `address`
+    fn location(&self, analysis: &Analysis, address: u64, size: usize, function: Option<String>) -> Location {
+        Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation {
+                    uri: self.artifact_uri.clone(),
+                    index: Some(0),
+                },
+                region: Region {
+                    byte_offset: analysis.metadata.text_offset + address,
+                    byte_length: size as u64,
+                },
+                address: Address {
+                    absolute_address: analysis.metadata.text_address + address,
+                    relative_address: address,
+                    kind: "instruction",
+                },
+            },
+            logical_locations: function
+                .map(|name| LogicalLocation {
+                    name,
+                    kind: "function",
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{analyze, AnalysisOptions};
+    use crate::analyzer::tests::insn;
+    use sbpf_common::binary::parse_binary;
+
+    #[test]
+    fn test_sarif_results() {
+        let program: Vec<u8> = [
+            insn(0x7a, 1, 0, 80, 0), // stdw [r1+80], 0
+            insn(0x37, 2, 0, 0, 0),  // div r2, 0
+            insn(0x18, 10, 0, 0, 0), // lddw r10, 0
+            insn(0x00, 0, 0, 0, 0),
+            insn(0x95, 0, 0, 0, 0),  // exit
+        ]
+        .concat();
+        let analysis = analyze(&parse_binary(&program).unwrap(), &AnalysisOptions::default());
+        let output = SarifFormatter::new("program.so").format(&analysis);
+        let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(log["version"], "2.1.0");
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r["ruleId"].as_str().unwrap()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "missing-signer-check",
+                "unzeroed-account-close",
+                "division-by-zero",
+                "write-to-frame-pointer"
+            ]
+        );
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[2]["level"], "warning");
+        let location = &results[2]["locations"][0]["physicalLocation"];
+        assert_eq!(location["address"]["relativeAddress"], 8);
+        assert_eq!(location["region"]["byteLength"], 8);
+        // lddw spans two slots
+        let location = &results[3]["locations"][0]["physicalLocation"];
+        assert_eq!(location["region"]["byteLength"], 16);
+    }
+}
@@ -1,5 +1,11 @@
+use crate::analyzer::diff::{DiffLine, DiffReport};
+use crate::analyzer::errors::ErrorCode;
 use crate::analyzer::Analysis;
-use crate::output::OutputFormatter;
+use sbpf_common::input::AccountField;
+use crate::output::{DiffFormatter, OutputFormatter};
+
+/// Unchanged lines shown around each change in instruction diffs
+const DIFF_CONTEXT: usize = 3;
 
 pub struct TextFormatter;
 
@@ -21,7 +27,7 @@ impl OutputFormatter for TextFormatter {
             output.push_str("SBPF Version: Unknown\n");
         }
         output.push_str(&format!("Metadata Sections: {}\n", analysis.metadata.metadata_sections));
-        output.push_str("\n");
+        output.push('\n');
         
         // Instruction Statistics
         output.push_str("## Instruction Statistics\n");
@@ -37,7 +43,7 @@ impl OutputFormatter for TextFormatter {
         for (opcode, count) in opcodes.iter().take(10) {
             output.push_str(&format!("  {}: {}\n", opcode, count));
         }
-        output.push_str("\n");
+        output.push('\n');
         
         // Control Flow
         output.push_str("## Control Flow\n");
@@ -45,7 +51,174 @@ impl OutputFormatter for TextFormatter {
         output.push_str(&format!("Calls: {}\n", analysis.instruction_stats.control_flow.calls));
         output.push_str(&format!("Exits: {}\n", analysis.instruction_stats.control_flow.exits));
         output.push_str(&format!("Unique Jump Targets: {}\n", analysis.instruction_stats.control_flow.jump_targets.len()));
-        output.push_str("\n");
+        output.push('\n');
+        
+        // Functions
+        output.push_str("## Functions\n");
+        output.push_str(&format!("Functions: {}\n", analysis.functions.len()));
+        output.push_str(&format!(
+            "Library Functions: {}\n",
+            analysis.functions.iter().filter(|f| f.library).count()
+        ));
+        for f in &analysis.functions {
+            output.push_str(&format!(
+                "  0x{:x} {}{}: {} instructions, {} blocks, ~{} CU\n",
+                f.address,
+                f.name,
+                if f.library { " [library]" } else { "" },
+                f.instructions,
+                f.blocks,
+                f.cu_estimate
+            ));
+        }
+        output.push('\n');
+        
+        // Anchor
+        output.push_str("## Anchor\n");
+        output.push_str(&format!("Anchor Program: {}\n", if analysis.anchor.is_anchor { "yes" } else { "no" }));
+        for ix in &analysis.anchor.instructions {
+            let handler = match &ix.handler_name {
+                Some(name) => format!("0x{:x} ({})", ix.handler, name),
+                None => format!("0x{:x}", ix.handler),
+            };
+            output.push_str(&format!(
+                "  {} {} -> {}\n",
+                hex(&ix.discriminator),
+                ix.name.as_deref().unwrap_or("<unknown>"),
+                handler
+            ));
+        }
+        output.push('\n');
+        
+        // Native dispatch
+        output.push_str("## Instruction Dispatch\n");
+        match &analysis.dispatch {
+            Some(dispatch) => {
+                output.push_str(&format!("Dispatcher: 0x{:x}\n", dispatch.function));
+                output.push_str(&format!(
+                    "Tag: u{} at {}\n",
+                    dispatch.tag_size as u32 * 8,
+                    dispatch.tag_location
+                ));
+                for case in &dispatch.cases {
+                    let handler = match &case.handler_name {
+                        Some(name) => format!("0x{:x} ({})", case.handler, name),
+                        None => format!("0x{:x}", case.handler),
+                    };
+                    output.push_str(&format!("  {} -> {}\n", case.tag, handler));
+                }
+            }
+            None => output.push_str("Dispatcher: not found\n"),
+        }
+        output.push('\n');
+        
+        // Account access
+        output.push_str("## Account Access\n");
+        for handler in &analysis.account_access {
+            output.push_str(&format!("  {} (0x{:x}):\n", handler.name, handler.handler));
+            for account in &handler.accounts {
+                let fields = |set: &std::collections::BTreeSet<_>| {
+                    let names: Vec<&str> = set.iter().map(AccountField::name).collect();
+                    if names.is_empty() { "-".to_string() } else { names.join(", ") }
+                };
+                output.push_str(&format!(
+                    "    accounts[{}]: read {}; write {}\n",
+                    account.index, fields(&account.reads), fields(&account.writes)
+                ));
+            }
+        }
+        output.push('\n');
+        
+        // Cross-program invocations
+        output.push_str("## Cross-Program Invocations\n");
+        output.push_str(&format!("CPI Sites: {}\n", analysis.cpi.len()));
+        for site in &analysis.cpi {
+            let instruction = match (site.instruction, &site.discriminator) {
+                (Some(name), _) => name.to_string(),
+                (None, Some(data)) => hex(data),
+                (None, None) => "<unknown>".to_string(),
+            };
+            output.push_str(&format!(
+                "  0x{:x} in {}: {} {}, signer seeds: {}\n",
+                site.address, site.function_name, site.program, instruction, site.signer_seeds
+            ));
+        }
+        output.push('\n');
+        
+        // PDA derivations
+        output.push_str("## PDA Derivations\n");
+        output.push_str(&format!("Derivations: {}\n", analysis.pdas.len()));
+        for pda in &analysis.pdas {
+            output.push_str(&format!("  0x{:x} in {}: {}\n", pda.address, pda.function_name, pda));
+        }
+        output.push('\n');
+        
+        // Pubkeys
+        output.push_str("## Pubkeys\n");
+        output.push_str(&format!("Pubkey Constants: {}\n", analysis.pubkeys.len()));
+        for constant in &analysis.pubkeys {
+            let references: Vec<String> = constant.references.iter().map(|r| format!("0x{:x}", r)).collect();
+            output.push_str(&format!(
+                "  0x{:x}: {} (referenced at {})\n",
+                constant.address, constant.pubkey, references.join(", ")
+            ));
+        }
+        output.push('\n');
+        
+        // Log messages
+        output.push_str("## Log Messages\n");
+        output.push_str(&format!("Messages: {}\n", analysis.logs.len()));
+        for message in &analysis.logs {
+            output.push_str(&format!(
+                "  0x{:x} in {} [{}]: {:?}\n",
+                message.address, message.function_name, message.kind, message.text
+            ));
+        }
+        output.push('\n');
+        
+        // Failure modes
+        output.push_str("## Failure Modes\n");
+        for function in &analysis.failures {
+            output.push_str(&format!("  {} (0x{:x}):\n", function.name, function.function));
+            if !function.codes.is_empty() {
+                let codes: Vec<String> = function
+                    .codes
+                    .iter()
+                    .filter_map(|&code| ErrorCode::from_code(code))
+                    .map(|code| code.to_string())
+                    .collect();
+                output.push_str(&format!("    returns: {}\n", codes.join(", ")));
+            }
+            for site in &function.panics {
+                output.push_str(&format!("    0x{:x} {} via {}", site.address, site.kind.name(), site.callee));
+                if let Some(message) = &site.message {
+                    output.push_str(&format!(": {:?}", message));
+                }
+                if let Some(location) = &site.location {
+                    output.push_str(&format!(" at {}", location));
+                }
+                output.push('\n');
+            }
+        }
+        output.push('\n');
+        
+        // Loops
+        output.push_str("## Loops\n");
+        output.push_str(&format!("Natural Loops: {}\n", analysis.loop_info.loops.len()));
+        output.push_str(&format!("Max Nesting Depth: {}\n", analysis.loop_info.max_depth));
+        for l in &analysis.loop_info.loops {
+            let indent = "  ".repeat(l.depth);
+            let trip_count = match l.trip_count {
+                Some(n) => format!("<= {} iterations", n),
+                None => "unbounded".to_string(),
+            };
+            let exits: Vec<String> = l.exits.iter().map(|e| format!("0x{:x}", e)).collect();
+            output.push_str(&format!(
+                "{}header 0x{:x} in 0x{:x}: depth {}, {} blocks, exits [{}], {}\n",
+                indent, l.header, l.function, l.depth, l.blocks, exits.join(", "), trip_count
+            ));
+        }
+        output.push('\n');
         
         // Syscalls
         output.push_str("## Syscalls\n");
@@ -59,9 +232,131 @@ impl OutputFormatter for TextFormatter {
                 output.push_str(&format!("  {} ({}): {}\n", name, syscall_num, count));
             }
         }
-        output.push_str("\n");
+        let program_calls: Vec<_> = analysis.syscall_calls.iter().filter(|c| !c.library).collect();
+        if !program_calls.is_empty() {
+            output.push_str("Call Sites:\n");
+            for call in program_calls {
+                output.push_str(&format!("  0x{:x} in {}: {}\n", call.address, call.function_name, call));
+            }
+        }
+        output.push('\n');
+        
+        // Lints
+        output.push_str("## Lints\n");
+        output.push_str(&format!("Findings: {}\n", analysis.lint_report.findings.len()));
+        for finding in &analysis.lint_report.findings {
+            output.push_str(&format!(
+                "  [{:?}] {} at 0x{:x} in {}: {}\n",
+                finding.severity, finding.rule, finding.address, finding.function_name, finding.message
+            ));
+        }
+        output.push('\n');
+        
+        // Diagnostics
+        output.push_str("## Diagnostics\n");
+        output.push_str(&format!("Diagnostics: {}\n", analysis.diagnostics.len()));
+        for diagnostic in &analysis.diagnostics {
+            output.push_str(&format!(
+                "  [{:?}] {} at 0x{:x}: {}\n",
+                diagnostic.code.category(), diagnostic.code.id(), diagnostic.address, diagnostic.message
+            ));
+        }
+        output.push('\n');
         
         output
     }
 }
 
+/// Render bytes as a contiguous hex string
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl DiffFormatter for TextFormatter {
+    fn format_diff(&self, report: &DiffReport) -> String {
+        let mut output = String::new();
+        
+        output.push_str("=== SBPF Binary Diff ===\n\n");
+        
+        // Summary
+        output.push_str("## Summary\n");
+        output.push_str(&format!("Added Functions: {}\n", report.added.len()));
+        output.push_str(&format!("Removed Functions: {}\n", report.removed.len()));
+        output.push_str(&format!("Modified Functions: {}\n", report.modified.len()));
+        output.push_str(&format!("Unchanged Functions: {}\n", report.unchanged));
+        output.push_str(&format!(
+            "CU Estimate: {} -> {} ({:+})\n",
+            report.old_cu,
+            report.new_cu,
+            report.new_cu as i64 - report.old_cu as i64
+        ));
+        output.push('\n');
+        
+        // Syscalls
+        output.push_str("## Syscall Changes\n");
+        for change in &report.syscall_changes {
+            output.push_str(&format!(
+                "  {}: {} -> {} ({:+})\n",
+                change.name,
+                change.old,
+                change.new,
+                change.new as i64 - change.old as i64
+            ));
+        }
+        output.push('\n');
+        
+        // Added / removed
+        output.push_str("## Added Functions\n");
+        for f in &report.added {
+            output.push_str(&format!("  + 0x{:x} {} (~{} CU)\n", f.address, f.name, f.cu_estimate));
+        }
+        output.push('\n');
+        output.push_str("## Removed Functions\n");
+        for f in &report.removed {
+            output.push_str(&format!("  - 0x{:x} {} (~{} CU)\n", f.address, f.name, f.cu_estimate));
+        }
+        output.push('\n');
+        
+        // Modified
+        output.push_str("## Modified Functions\n");
+        for change in &report.modified {
+            output.push_str(&format!(
+                "{} (0x{:x} -> 0x{:x}, matched by {:?}): {} -> {} CU ({:+})\n",
+                change.new.name,
+                change.old.address,
+                change.new.address,
+                change.matched_by,
+                change.old.cu_estimate,
+                change.new.cu_estimate,
+                change.new.cu_estimate as i64 - change.old.cu_estimate as i64
+            ));
+            let changed: Vec<usize> = change.lines
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| !matches!(l, DiffLine::Same(_)))
+                .map(|(i, _)| i)
+                .collect();
+            let mut last_shown = None;
+            for (i, line) in change.lines.iter().enumerate() {
+                let near_change = changed
+                    .iter()
+                    .any(|&c| c.abs_diff(i) <= DIFF_CONTEXT);
+                if !near_change {
+                    continue;
+                }
+                if last_shown.is_some_and(|last| last + 1 != i) {
+                    output.push_str("    ...\n");
+                }
+                last_shown = Some(i);
+                match line {
+                    DiffLine::Same(text) => output.push_str(&format!("      {}\n", text)),
+                    DiffLine::Removed(text) => output.push_str(&format!("    - {}\n", text)),
+                    DiffLine::Added(text) => output.push_str(&format!("    + {}\n", text)),
+                }
+            }
+            output.push('\n');
+        }
+        
+        output
+    }
+}
@@ -1,4 +1,4 @@
-use crate::binary::{MetadataSection, SbpfBinary, SbpfVersion};
+use crate::binary::{MetadataSection, SbpfBinary, SbpfVersion, Symbol};
 use anyhow::{Context, Result};
 use goblin::elf::Elf;
 use goblin::Object;
@@ -16,18 +16,22 @@ pub fn parse_elf(data: &[u8]) -> Result<SbpfBinary> {
 
 fn parse_elf_internal(elf: &Elf, data: &[u8]) -> Result<SbpfBinary> {
     let mut bytecode = Vec::new();
-    let mut entry_point = 0;
+    let mut text_address = 0;
+    let mut text_offset = 0;
+    let mut text_index = None;
     let mut metadata = Vec::new();
     
     // Extract .text section (bytecode)
-    for section in &elf.section_headers {
+    for (index, section) in elf.section_headers.iter().enumerate() {
         if let Some(name) = elf.shdr_strtab.get_at(section.sh_name) {
             if name == ".text" {
                 let start = section.sh_offset as usize;
                 let end = start + section.sh_size as usize;
                 if end <= data.len() {
                     bytecode = data[start..end].to_vec();
-                    entry_point = section.sh_addr;
+                    text_address = section.sh_addr;
+                    text_offset = section.sh_offset;
+                    text_index = Some(index);
                 }
             } else if name.starts_with(".metadata") || name.starts_with(".rodata") {
                 // Extract metadata sections
@@ -36,6 +40,7 @@ fn parse_elf_internal(elf: &Elf, data: &[u8]) -> Result<SbpfBinary> {
                 if end <= data.len() {
                     metadata.push(MetadataSection {
                         name: name.to_string(),
+                        address: section.sh_addr,
                         data: data[start..end].to_vec(),
                     });
                 }
@@ -50,14 +55,54 @@ fn parse_elf_internal(elf: &Elf, data: &[u8]) -> Result<SbpfBinary> {
     // Try to detect SBPF version from the binary
     let version = detect_version(&bytecode);
     
+    let symbols = match text_index {
+        Some(index) => extract_symbols(elf, index, text_address, bytecode.len() as u64),
+        None => Vec::new(),
+    };
+    
     Ok(SbpfBinary {
         bytecode,
-        entry_point,
+        entry_point: elf.entry,
+        text_address,
+        text_offset,
         version,
         metadata,
+        symbols,
     })
 }
 
+/// Collect function symbols defined in the `.text` section from both the
+/// static and dynamic symbol tables
+fn extract_symbols(elf: &Elf, text_index: usize, text_address: u64, text_size: u64) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let tables = [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)];
+    
+    for (table, strtab) in tables {
+        for sym in table.iter() {
+            if !sym.is_function() || sym.st_shndx != text_index {
+                continue;
+            }
+            let Some(name) = strtab.get_at(sym.st_name).filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            let Some(address) = sym.st_value.checked_sub(text_address) else {
+                continue;
+            };
+            if address >= text_size || symbols.iter().any(|s| s.address == address) {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: format!("{:#}", rustc_demangle::demangle(name)),
+                address,
+                size: sym.st_size,
+            });
+        }
+    }
+    
+    symbols.sort_by_key(|s| s.address);
+    symbols
+}
+
 /// Attempt to detect SBPF version from bytecode characteristics
 fn detect_version(_bytecode: &[u8]) -> Option<SbpfVersion> {
     // This is a simplified version detection
@@ -12,10 +12,72 @@ pub struct SbpfBinary {
     pub bytecode: Vec<u8>,
     /// Entry point address
     pub entry_point: u64,
+    /// Virtual address the bytecode is loaded at
+    pub text_address: u64,
+    /// File offset of the bytecode
+    pub text_offset: u64,
     /// SBPF version (if detectable)
     pub version: Option<SbpfVersion>,
     /// Metadata sections (if any)
     pub metadata: Vec<MetadataSection>,
+    /// Function symbols (if not stripped)
+    pub symbols: Vec<Symbol>,
+}
+
+impl SbpfBinary {
+    /// Entry point as a byte offset into the bytecode
+    pub fn entry_offset(&self) -> u64 {
+        let offset = self.entry_point.saturating_sub(self.text_address);
+        if offset < self.bytecode.len() as u64 {
+            offset
+        } else {
+            0
+        }
+    }
+}
+
+/// Virtual address the program image is mapped at by the runtime
+pub const MM_PROGRAM_START: u64 = 0x1_0000_0000;
+
+/// Shortest run of printable characters reported as a string
+pub const MIN_STRING_LENGTH: usize = 4;
+
+impl SbpfBinary {
+    /// Read-only data sections
+    pub fn rodata(&self) -> impl Iterator<Item = &MetadataSection> {
+        self.metadata.iter().filter(|s| s.name.starts_with(".rodata"))
+    }
+
+    /// `len` bytes of read-only data at `address`, which may or may not
+    /// include the runtime's program region base
+    pub fn read_rodata(&self, address: u64, len: usize) -> Option<&[u8]> {
+        let address = address.checked_sub(MM_PROGRAM_START).unwrap_or(address);
+        self.rodata().find_map(|section| {
+            let start = usize::try_from(address.checked_sub(section.address)?).ok()?;
+            section.data.get(start..start.checked_add(len)?)
+        })
+    }
+
+    /// Runs of printable ASCII in read-only data, with their addresses.
+    /// Rust string literals are not NUL-terminated, so adjacent literals
+    /// may be reported as one string.
+    pub fn rodata_strings(&self) -> Vec<(u64, String)> {
+        let mut strings = Vec::new();
+        for section in self.rodata() {
+            let mut start = 0;
+            for (i, &byte) in section.data.iter().chain(std::iter::once(&0)).enumerate() {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    continue;
+                }
+                if i - start >= MIN_STRING_LENGTH {
+                    let text = String::from_utf8_lossy(&section.data[start..i]).into_owned();
+                    strings.push((section.address + start as u64, text));
+                }
+                start = i + 1;
+            }
+        }
+        strings
+    }
 }
 
 /// SBPF version
@@ -31,9 +93,22 @@ pub enum SbpfVersion {
 #[derive(Debug, Clone)]
 pub struct MetadataSection {
     pub name: String,
+    /// Virtual address the section is loaded at
+    pub address: u64,
     pub data: Vec<u8>,
 }
 
+/// Function symbol from the ELF symbol tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// Demangled name
+    pub name: String,
+    /// Offset of the function in the bytecode
+    pub address: u64,
+    /// Size in bytes
+    pub size: u64,
+}
+
 /// Load and parse an SBPF binary from a file path
 pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<SbpfBinary> {
     let data = fs::read(path.as_ref())
@@ -53,8 +128,11 @@ pub fn parse_binary(data: &[u8]) -> Result<SbpfBinary> {
     Ok(SbpfBinary {
         bytecode: data.to_vec(),
         entry_point: 0,
+        text_address: 0,
+        text_offset: 0,
         version: None,
         metadata: Vec::new(),
+        symbols: Vec::new(),
     })
 }
 
@@ -1,11 +1,18 @@
 // SBPF-specific binary structures and utilities
 
+/// Size of a single instruction slot in bytes
+pub const INSN_SIZE: usize = 8;
+
+/// Opcode of the two-slot `lddw` instruction
+pub const LD_DW_IMM: u8 = 0x18;
+
 /// SBPF instruction opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     // Load/Store
     LdAbs,
     LdInd,
+    Lddw,
     Ldx,
     St,
     Stx,
@@ -18,10 +25,13 @@ pub enum Opcode {
     And,
     Lsh,
     Rsh,
+    Neg,
     Mod,
     Xor,
     Mov,
     Arsh,
+    Le,
+    Be,
     // Jump
     Ja,
     Jeq,
@@ -36,6 +46,7 @@ pub enum Opcode {
     Jslt,
     Jsle,
     Call,
+    Callx,
     Exit,
     // Misc
     Unknown(u8),
@@ -43,44 +54,114 @@ pub enum Opcode {
 
 impl Opcode {
     /// Get opcode from instruction byte
+    ///
+    /// The three least significant bits select the instruction class, the
+    /// remaining bits select the operation within that class (SBPF v0/v1
+    /// encoding).
     pub fn from_u8(byte: u8) -> Self {
-        match byte & 0xf0 {
-            0x00 => Opcode::LdAbs,
-            0x10 => Opcode::LdInd,
-            0x20 => Opcode::Ldx,
-            0x30 => Opcode::St,
-            0x40 => Opcode::Stx,
-            0x50 => Opcode::Add,
-            0x60 => Opcode::Sub,
-            0x70 => Opcode::Mul,
-            0x80 => Opcode::Div,
-            0x90 => Opcode::Or,
-            0xa0 => Opcode::And,
-            0xb0 => Opcode::Lsh,
-            0xc0 => Opcode::Rsh,
-            0xd0 => Opcode::Mod,
-            0xe0 => Opcode::Xor,
-            0xf0 => match byte & 0x0f {
-                0x00 => Opcode::Mov,
-                0x01 => Opcode::Arsh,
-                0x02 => Opcode::Ja,
-                0x03 => Opcode::Jeq,
-                0x04 => Opcode::Jgt,
-                0x05 => Opcode::Jge,
-                0x06 => Opcode::Jlt,
-                0x07 => Opcode::Jle,
-                0x08 => Opcode::Jset,
-                0x09 => Opcode::Jne,
-                0x0a => Opcode::Jsgt,
-                0x0b => Opcode::Jsge,
-                0x0c => Opcode::Jslt,
-                0x0d => Opcode::Jsle,
-                0x0e => Opcode::Call,
-                0x0f => Opcode::Exit,
+        match byte & 0x07 {
+            // LD
+            0x00 => match byte {
+                LD_DW_IMM => Opcode::Lddw,
+                _ => match byte & 0xe0 {
+                    0x20 => Opcode::LdAbs,
+                    0x40 => Opcode::LdInd,
+                    _ => Opcode::Unknown(byte),
+                },
+            },
+            0x01 => Opcode::Ldx,
+            0x02 => Opcode::St,
+            0x03 => Opcode::Stx,
+            // ALU32 / ALU64
+            0x04 | 0x07 => match byte & 0xf0 {
+                0x00 => Opcode::Add,
+                0x10 => Opcode::Sub,
+                0x20 => Opcode::Mul,
+                0x30 => Opcode::Div,
+                0x40 => Opcode::Or,
+                0x50 => Opcode::And,
+                0x60 => Opcode::Lsh,
+                0x70 => Opcode::Rsh,
+                0x80 => Opcode::Neg,
+                0x90 => Opcode::Mod,
+                0xa0 => Opcode::Xor,
+                0xb0 => Opcode::Mov,
+                0xc0 => Opcode::Arsh,
+                0xd0 if byte & 0x07 == 0x04 => {
+                    if byte & 0x08 == 0 {
+                        Opcode::Le
+                    } else {
+                        Opcode::Be
+                    }
+                }
+                _ => Opcode::Unknown(byte),
+            },
+            // JMP
+            0x05 => match byte & 0xf0 {
+                0x00 => Opcode::Ja,
+                0x10 => Opcode::Jeq,
+                0x20 => Opcode::Jgt,
+                0x30 => Opcode::Jge,
+                0x40 => Opcode::Jset,
+                0x50 => Opcode::Jne,
+                0x60 => Opcode::Jsgt,
+                0x70 => Opcode::Jsge,
+                0x80 => {
+                    if byte & 0x08 == 0 {
+                        Opcode::Call
+                    } else {
+                        Opcode::Callx
+                    }
+                }
+                0x90 => Opcode::Exit,
+                0xa0 => Opcode::Jlt,
+                0xb0 => Opcode::Jle,
+                0xc0 => Opcode::Jslt,
+                0xd0 => Opcode::Jsle,
                 _ => Opcode::Unknown(byte),
             },
             _ => Opcode::Unknown(byte),
         }
     }
+
+    /// Check if this is a conditional jump
+    pub fn is_conditional_jump(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Jeq
+                | Opcode::Jgt
+                | Opcode::Jge
+                | Opcode::Jlt
+                | Opcode::Jle
+                | Opcode::Jset
+                | Opcode::Jne
+                | Opcode::Jsgt
+                | Opcode::Jsge
+                | Opcode::Jslt
+                | Opcode::Jsle
+        )
+    }
+
+    /// Check if this is a jump (conditional or unconditional)
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Opcode::Ja) || self.is_conditional_jump()
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_classes() {
+        assert_eq!(Opcode::from_u8(0xb7), Opcode::Mov);
+        assert_eq!(Opcode::from_u8(0xbc), Opcode::Mov);
+        assert_eq!(Opcode::from_u8(0x18), Opcode::Lddw);
+        assert_eq!(Opcode::from_u8(0x79), Opcode::Ldx);
+        assert_eq!(Opcode::from_u8(0x7b), Opcode::Stx);
+        assert_eq!(Opcode::from_u8(0x2d), Opcode::Jgt);
+        assert_eq!(Opcode::from_u8(0x85), Opcode::Call);
+        assert_eq!(Opcode::from_u8(0x95), Opcode::Exit);
+        assert_eq!(Opcode::from_u8(0xdc), Opcode::Be);
+    }
+}
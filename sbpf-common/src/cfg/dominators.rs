@@ -0,0 +1,202 @@
+use crate::cfg::{ControlFlowGraph, Function};
+use std::collections::{BTreeSet, HashMap};
+
+/// Dominator tree of a single function
+///
+/// Computed with the iterative algorithm of Cooper, Harvey and Kennedy over
+/// the blocks reachable from the function entry.
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    /// Entry block id
+    pub entry: usize,
+    /// Blocks in reverse postorder
+    pub reverse_postorder: Vec<usize>,
+    /// Immediate dominator of each reachable block (entry maps to itself)
+    idom: HashMap<usize, usize>,
+}
+
+impl DominatorTree {
+    /// Compute the dominator tree for `function`
+    pub fn compute(cfg: &ControlFlowGraph, function: &Function) -> Self {
+        let reverse_postorder = reverse_postorder(cfg, function.entry);
+        let order: HashMap<usize, usize> = reverse_postorder
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| (b, i))
+            .collect();
+
+        let mut idom: HashMap<usize, usize> = HashMap::new();
+        idom.insert(function.entry, function.entry);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in reverse_postorder.iter().skip(1) {
+                let mut new_idom = None;
+                for &pred in &cfg.blocks[block].predecessors {
+                    if !idom.contains_key(&pred) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(current) => intersect(&idom, &order, pred, current),
+                    });
+                }
+                if let Some(new_idom) = new_idom {
+                    if idom.get(&block) != Some(&new_idom) {
+                        idom.insert(block, new_idom);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        DominatorTree {
+            entry: function.entry,
+            reverse_postorder,
+            idom,
+        }
+    }
+
+    /// Immediate dominator of `block` (`None` for the entry and unreachable blocks)
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        match self.idom.get(&block) {
+            Some(&idom) if block != self.entry => Some(idom),
+            _ => None,
+        }
+    }
+
+    /// Check if `a` dominates `b`
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        if !self.idom.contains_key(&b) {
+            return false;
+        }
+        let mut current = b;
+        loop {
+            if current == a {
+                return true;
+            }
+            match self.immediate_dominator(current) {
+                Some(idom) => current = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// Check if `block` is reachable from the entry
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.idom.contains_key(&block)
+    }
+
+    /// Children of each block in the dominator tree
+    pub fn children(&self) -> HashMap<usize, Vec<usize>> {
+        let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &block in &self.reverse_postorder {
+            if let Some(idom) = self.immediate_dominator(block) {
+                children.entry(idom).or_default().push(block);
+            }
+        }
+        children
+    }
+
+    /// Dominance frontier of each reachable block; the entry is also
+    /// entered from outside the function, so a loop back to it is a join
+    pub fn frontiers(&self, cfg: &ControlFlowGraph) -> HashMap<usize, BTreeSet<usize>> {
+        let mut frontiers: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+        for &block in &self.reverse_postorder {
+            let preds: Vec<usize> = cfg.blocks[block]
+                .predecessors
+                .iter()
+                .copied()
+                .filter(|p| self.is_reachable(*p))
+                .collect();
+            let idom = self.immediate_dominator(block);
+            if preds.len() + usize::from(block == self.entry) < 2 {
+                continue;
+            }
+            for pred in preds {
+                let mut runner = Some(pred);
+                while let Some(current) = runner.filter(|&r| Some(r) != idom) {
+                    frontiers.entry(current).or_default().insert(block);
+                    runner = self.immediate_dominator(current);
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+fn intersect(
+    idom: &HashMap<usize, usize>,
+    order: &HashMap<usize, usize>,
+    mut a: usize,
+    mut b: usize,
+) -> usize {
+    while a != b {
+        while order[&a] > order[&b] {
+            a = idom[&a];
+        }
+        while order[&b] > order[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+/// Blocks reachable from `entry` in reverse postorder
+pub fn reverse_postorder(cfg: &ControlFlowGraph, entry: usize) -> Vec<usize> {
+    let mut visited = vec![false; cfg.blocks.len()];
+    let mut postorder = Vec::new();
+    // (block, next successor index)
+    let mut stack = vec![(entry, 0usize)];
+    visited[entry] = true;
+    while let Some((block, next)) = stack.pop() {
+        match cfg.blocks[block].successors.get(next) {
+            Some(&succ) => {
+                stack.push((block, next + 1));
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            }
+            None => postorder.push(block),
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_diamond_dominators() {
+        let program: Vec<u8> = [
+            insn(0x15, 1, 0, 2, 0), // 0x00: jeq r1, 0, +2
+            insn(0xb7, 0, 0, 0, 1), // 0x08: mov r0, 1
+            insn(0x05, 0, 0, 1, 0), // 0x10: ja +1
+            insn(0xb7, 0, 0, 0, 2), // 0x18: mov r0, 2
+            insn(0x95, 0, 0, 0, 0), // 0x20: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let domtree = DominatorTree::compute(&cfg, &cfg.functions[0]);
+
+        let head = cfg.block_id_at(0).unwrap();
+        let left = cfg.block_id_at(0x08).unwrap();
+        let right = cfg.block_id_at(0x18).unwrap();
+        let join = cfg.block_id_at(0x20).unwrap();
+
+        assert_eq!(domtree.immediate_dominator(join), Some(head));
+        assert!(domtree.dominates(head, left));
+        assert!(!domtree.dominates(left, join));
+
+        let frontiers = domtree.frontiers(&cfg);
+        assert!(frontiers[&left].contains(&join));
+        assert!(frontiers[&right].contains(&join));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::binary::sbpf::Opcode;
+use crate::cfg::{ControlFlowGraph, DominatorTree, Function};
+use crate::instruction::Instruction;
+use std::collections::BTreeSet;
+
+/// Maximum number of iterations simulated when bounding a trip count
+const MAX_SIMULATED_ITERATIONS: u64 = 1 << 20;
+
+/// Natural loop identified from one or more back edges to the same header
+#[derive(Debug, Clone)]
+pub struct Loop {
+    /// Header block id (target of the back edges)
+    pub header: usize,
+    /// Source blocks of the back edges
+    pub latches: Vec<usize>,
+    /// Blocks belonging to the loop, including the header
+    pub body: BTreeSet<usize>,
+    /// Edges leaving the loop as (block inside, block outside)
+    pub exits: Vec<(usize, usize)>,
+    /// Index of the innermost enclosing loop
+    pub parent: Option<usize>,
+    /// Nesting depth (1 for outermost loops)
+    pub depth: usize,
+    /// Upper bound on the number of iterations, when derivable from a
+    /// constant induction variable compared against a constant
+    pub trip_count: Option<u64>,
+}
+
+impl Loop {
+    /// Check if `block` belongs to this loop
+    pub fn contains(&self, block: usize) -> bool {
+        self.body.contains(&block)
+    }
+}
+
+/// All natural loops of a function
+#[derive(Debug, Clone, Default)]
+pub struct LoopForest {
+    /// Loops ordered by header address
+    pub loops: Vec<Loop>,
+}
+
+impl LoopForest {
+    /// Find the natural loops of `function`
+    pub fn compute(
+        cfg: &ControlFlowGraph,
+        function: &Function,
+        domtree: &DominatorTree,
+        instructions: &[Instruction],
+    ) -> Self {
+        // Back edges: latch -> header where header dominates latch
+        let mut loops: Vec<Loop> = Vec::new();
+        for &block in &function.blocks {
+            if !domtree.is_reachable(block) {
+                continue;
+            }
+            for &succ in &cfg.blocks[block].successors {
+                if !domtree.dominates(succ, block) {
+                    continue;
+                }
+                let body = natural_loop_body(cfg, domtree, succ, block);
+                match loops.iter_mut().find(|l| l.header == succ) {
+                    Some(existing) => {
+                        existing.latches.push(block);
+                        existing.body.extend(body);
+                    }
+                    None => loops.push(Loop {
+                        header: succ,
+                        latches: vec![block],
+                        body,
+                        exits: Vec::new(),
+                        parent: None,
+                        depth: 1,
+                        trip_count: None,
+                    }),
+                }
+            }
+        }
+        loops.sort_by_key(|l| cfg.blocks[l.header].start);
+
+        // Exit edges
+        for l in &mut loops {
+            for &block in &l.body {
+                for &succ in &cfg.blocks[block].successors {
+                    if !l.body.contains(&succ) {
+                        l.exits.push((block, succ));
+                    }
+                }
+            }
+        }
+
+        // Nesting: the parent is the smallest other loop containing the header
+        for i in 0..loops.len() {
+            loops[i].parent = (0..loops.len())
+                .filter(|&j| j != i && loops[j].body.contains(&loops[i].header))
+                .filter(|&j| loops[j].body.len() > loops[i].body.len())
+                .min_by_key(|&j| loops[j].body.len());
+        }
+        for i in 0..loops.len() {
+            let mut depth = 1;
+            let mut current = loops[i].parent;
+            while let Some(p) = current {
+                depth += 1;
+                current = loops[p].parent;
+            }
+            loops[i].depth = depth;
+        }
+
+        for l in &mut loops {
+            l.trip_count = bound_trip_count(cfg, domtree, l, instructions);
+        }
+
+        LoopForest { loops }
+    }
+
+    /// Innermost loop containing `block`
+    pub fn innermost_containing(&self, block: usize) -> Option<usize> {
+        (0..self.loops.len())
+            .filter(|&i| self.loops[i].contains(block))
+            .max_by_key(|&i| self.loops[i].depth)
+    }
+
+    /// Maximum nesting depth
+    pub fn max_depth(&self) -> usize {
+        self.loops.iter().map(|l| l.depth).max().unwrap_or(0)
+    }
+}
+
+/// Blocks that reach `latch` without passing through `header`; only blocks
+/// of the function dominated by the header can belong to its loop
+fn natural_loop_body(
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    header: usize,
+    latch: usize,
+) -> BTreeSet<usize> {
+    let mut body = BTreeSet::new();
+    body.insert(header);
+    let mut stack = vec![latch];
+    while let Some(block) = stack.pop() {
+        if body.insert(block) {
+            stack.extend(
+                cfg.blocks[block]
+                    .predecessors
+                    .iter()
+                    .copied()
+                    .filter(|&pred| domtree.dominates(header, pred)),
+            );
+        }
+    }
+    body
+}
+
+/// Derive an iteration bound from a `rX += step` induction variable that is
+/// initialised to a constant before the loop and compared against a constant
+/// on an exiting branch; the loop ends at the first exit that fires. Only
+/// exits tested on every iteration bound the loop, since a conditional
+/// break may never be reached
+fn bound_trip_count(
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    l: &Loop,
+    instructions: &[Instruction],
+) -> Option<u64> {
+    l.exits
+        .iter()
+        .filter(|&&(from, _)| runs_every_iteration(domtree, l, from))
+        .filter_map(|&(from, _)| exit_trip_count(cfg, domtree, l, instructions, from))
+        .min()
+}
+
+/// Check if `block` executes on every path around the loop
+fn runs_every_iteration(domtree: &DominatorTree, l: &Loop, block: usize) -> bool {
+    l.latches.iter().all(|&latch| domtree.dominates(block, latch))
+}
+
+/// Number of iterations before the branch ending block `from` leaves the loop
+fn exit_trip_count(
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    l: &Loop,
+    instructions: &[Instruction],
+    from: usize,
+) -> Option<u64> {
+    let block = &cfg.blocks[from];
+    let jump = &instructions[block.instructions.end - 1];
+    if !jump.opcode.is_conditional_jump() || jump.uses_src_reg() {
+        return None;
+    }
+    let reg = jump.dst_reg;
+    let (step, step_block) = induction_step(cfg, l, instructions, reg)?;
+    // A conditional increment may be skipped, leaving the variable unchanged
+    if !runs_every_iteration(domtree, l, step_block) {
+        return None;
+    }
+    // The increment runs before the test in each iteration when its block
+    // is the exiting block or dominates it
+    let step_first = step_block == from || domtree.dominates(step_block, from);
+    let init = initial_value(cfg, l, instructions, reg)?;
+
+    // The loop continues while the condition holds if the branch target
+    // stays inside the loop, otherwise while it does not hold
+    let taken_stays = jump
+        .jump_target()
+        .and_then(|t| cfg.block_id_at(t))
+        .is_some_and(|t| l.body.contains(&t));
+
+    // Count the tests that keep the loop going; when the increment comes
+    // first, the failing test ends an iteration that already ran
+    let mut value = init;
+    let mut iterations = 0;
+    while iterations <= MAX_SIMULATED_ITERATIONS {
+        if step_first {
+            value = value.wrapping_add(step as u64);
+        }
+        let holds = evaluate_condition(jump.opcode, value, jump.imm as u64)?;
+        if holds != taken_stays {
+            return Some(if step_first { iterations + 1 } else { iterations });
+        }
+        if !step_first {
+            value = value.wrapping_add(step as u64);
+        }
+        iterations += 1;
+    }
+    None
+}
+
+/// Constant step of `reg` and the block stepping it, if its only definition
+/// in the loop is `add/sub reg, imm`
+fn induction_step(
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    instructions: &[Instruction],
+    reg: u8,
+) -> Option<(i64, usize)> {
+    let mut step = None;
+    for &block in &l.body {
+        for inst in &instructions[cfg.blocks[block].instructions.clone()] {
+            if !inst.written_registers().contains(&reg) {
+                continue;
+            }
+            let this_step = match inst.opcode {
+                Opcode::Add if !inst.uses_src_reg() && !inst.is_alu32() => inst.imm,
+                Opcode::Sub if !inst.uses_src_reg() && !inst.is_alu32() => -inst.imm,
+                _ => return None,
+            };
+            if step.is_some() || this_step == 0 {
+                return None;
+            }
+            step = Some((this_step, block));
+        }
+    }
+    step
+}
+
+/// Constant value of `reg` on entry to the loop, following the chain of
+/// single predecessors from the unique block entering the header
+fn initial_value(
+    cfg: &ControlFlowGraph,
+    l: &Loop,
+    instructions: &[Instruction],
+    reg: u8,
+) -> Option<u64> {
+    let outside: Vec<usize> = cfg.blocks[l.header]
+        .predecessors
+        .iter()
+        .copied()
+        .filter(|p| !l.body.contains(p))
+        .collect();
+    let [mut block] = outside[..] else {
+        return None;
+    };
+    let mut visited = BTreeSet::new();
+    while visited.insert(block) {
+        for inst in instructions[cfg.blocks[block].instructions.clone()].iter().rev() {
+            if !inst.written_registers().contains(&reg) {
+                continue;
+            }
+            return match inst.opcode {
+                Opcode::Mov if !inst.uses_src_reg() && !inst.is_alu32() => Some(inst.imm as u64),
+                Opcode::Lddw => Some(inst.imm as u64),
+                _ => None,
+            };
+        }
+        match cfg.blocks[block].predecessors[..] {
+            [pred] => block = pred,
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Evaluate a 64-bit jump condition on constant operands
+pub fn evaluate_condition(opcode: Opcode, lhs: u64, rhs: u64) -> Option<bool> {
+    Some(match opcode {
+        Opcode::Jeq => lhs == rhs,
+        Opcode::Jne => lhs != rhs,
+        Opcode::Jgt => lhs > rhs,
+        Opcode::Jge => lhs >= rhs,
+        Opcode::Jlt => lhs < rhs,
+        Opcode::Jle => lhs <= rhs,
+        Opcode::Jset => lhs & rhs != 0,
+        Opcode::Jsgt => (lhs as i64) > (rhs as i64),
+        Opcode::Jsge => (lhs as i64) >= (rhs as i64),
+        Opcode::Jslt => (lhs as i64) < (rhs as i64),
+        Opcode::Jsle => (lhs as i64) <= (rhs as i64),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_nested_counted_loops() {
+        let program: Vec<u8> = [
+            insn(0xb7, 1, 0, 0, 0),   // 0x00: mov r1, 0
+            insn(0xb7, 2, 0, 0, 0),   // 0x08: mov r2, 0        <- outer header
+            insn(0x07, 2, 0, 0, 1),   // 0x10: add r2, 1        <- inner header
+            insn(0xa5, 2, 0, -2, 4),  // 0x18: jlt r2, 4, -2
+            insn(0x07, 1, 0, 0, 1),   // 0x20: add r1, 1
+            insn(0xa5, 1, 0, -5, 10), // 0x28: jlt r1, 10, -5
+            insn(0x95, 0, 0, 0, 0),   // 0x30: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let function = &cfg.functions[0];
+        let domtree = DominatorTree::compute(&cfg, function);
+        let forest = LoopForest::compute(&cfg, function, &domtree, &instructions);
+
+        assert_eq!(forest.loops.len(), 2);
+        let outer = &forest.loops[0];
+        let inner = &forest.loops[1];
+        assert_eq!(cfg.blocks[outer.header].start, 0x08);
+        assert_eq!(cfg.blocks[inner.header].start, 0x10);
+        assert_eq!(outer.depth, 1);
+        assert_eq!(inner.depth, 2);
+        assert_eq!(inner.parent, Some(0));
+        assert_eq!(outer.exits.len(), 1);
+        assert_eq!(outer.trip_count, Some(10));
+        assert_eq!(inner.trip_count, Some(4));
+        assert_eq!(forest.max_depth(), 2);
+    }
+
+    #[test]
+    fn test_body_excludes_unreachable_blocks() {
+        let program: Vec<u8> = [
+            insn(0xb7, 1, 0, 0, 0),    // 0x00: mov r1, 0
+            insn(0x07, 1, 0, 0, 1),    // 0x08: add r1, 1        <- header
+            insn(0x25, 1, 0, 2, 100),  // 0x10: jgt r1, 100, +2
+            insn(0xa5, 1, 0, -3, 10),  // 0x18: jlt r1, 10, -3
+            insn(0x95, 0, 0, 0, 0),    // 0x20: exit
+            insn(0x95, 0, 0, 0, 0),    // 0x28: exit
+            insn(0x05, 0, 0, -4, 0),   // 0x30: ja -4 (unreachable)
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let function = &cfg.functions[0];
+        let domtree = DominatorTree::compute(&cfg, function);
+        let forest = LoopForest::compute(&cfg, function, &domtree, &instructions);
+
+        assert_eq!(forest.loops.len(), 1);
+        let starts: Vec<u64> = forest.loops[0].body.iter().map(|&b| cfg.blocks[b].start).collect();
+        assert_eq!(starts, vec![0x08, 0x18]);
+        // The exit at 10 fires long before the one at 100
+        assert_eq!(forest.loops[0].trip_count, Some(10));
+    }
+
+    #[test]
+    fn test_conditional_step_unbounded() {
+        let program: Vec<u8> = [
+            insn(0xb7, 1, 0, 0, 0),    // 0x00: mov r1, 0
+            insn(0x71, 3, 2, 0, 0),    // 0x08: ldxb r3, [r2+0]  <- header
+            insn(0x15, 3, 0, 1, 0),    // 0x10: jeq r3, 0, +1
+            insn(0x07, 1, 0, 0, 1),    // 0x18: add r1, 1
+            insn(0xa5, 1, 0, -4, 10),  // 0x20: jlt r1, 10, -4
+            insn(0x95, 0, 0, 0, 0),    // 0x28: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let function = &cfg.functions[0];
+        let domtree = DominatorTree::compute(&cfg, function);
+        let forest = LoopForest::compute(&cfg, function, &domtree, &instructions);
+
+        assert_eq!(forest.loops.len(), 1);
+        // The loop never ends while the byte is zero
+        assert_eq!(forest.loops[0].trip_count, None);
+    }
+
+    #[test]
+    fn test_conditional_break_does_not_bound() {
+        let program: Vec<u8> = [
+            insn(0xb7, 1, 0, 0, 0),    // 0x00: mov r1, 0
+            insn(0x07, 1, 0, 0, 1),    // 0x08: add r1, 1        <- header
+            insn(0x71, 3, 2, 0, 0),    // 0x10: ldxb r3, [r2+0]
+            insn(0x55, 3, 0, 2, 0),    // 0x18: jne r3, 0, +2
+            insn(0x25, 1, 0, 2, 5),    // 0x20: jgt r1, 5, +2
+            insn(0x05, 0, 0, -5, 0),   // 0x28: ja -5
+            insn(0x05, 0, 0, -6, 0),   // 0x30: ja -6
+            insn(0x95, 0, 0, 0, 0),    // 0x38: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let function = &cfg.functions[0];
+        let domtree = DominatorTree::compute(&cfg, function);
+        let forest = LoopForest::compute(&cfg, function, &domtree, &instructions);
+
+        assert_eq!(forest.loops.len(), 1);
+        assert_eq!(cfg.blocks[forest.loops[0].header].start, 0x08);
+        // The exit at 5 is skipped whenever the byte is non-zero
+        assert_eq!(forest.loops[0].trip_count, None);
+    }
+}
@@ -0,0 +1,264 @@
+pub mod dominators;
+pub mod loops;
+
+use crate::binary::sbpf::Opcode;
+use crate::binary::Symbol;
+use crate::instruction::Instruction;
+use crate::signatures::is_library_name;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+pub use dominators::DominatorTree;
+pub use loops::{Loop, LoopForest};
+
+/// Basic block in the control flow graph
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    /// Block index in the graph
+    pub id: usize,
+    /// Address of the first instruction
+    pub start: u64,
+    /// Address one past the last instruction
+    pub end: u64,
+    /// Range of indices into the decoded instruction list
+    pub instructions: Range<usize>,
+    /// Successor block ids (branch target first, then fallthrough)
+    pub successors: Vec<usize>,
+    /// Predecessor block ids
+    pub predecessors: Vec<usize>,
+}
+
+/// Function discovered from the entry point and call targets
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// Address of the first instruction
+    pub address: u64,
+    /// Function name
+    pub name: String,
+    /// Entry block id
+    pub entry: usize,
+    /// Block ids reachable from the entry, sorted by address
+    pub blocks: Vec<usize>,
+    /// Statically linked library code (`core`, `alloc`, `solana_program`, ...)
+    pub library: bool,
+}
+
+/// Control flow graph over decoded instructions
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub functions: Vec<Function>,
+    block_index: HashMap<u64, usize>,
+}
+
+impl ControlFlowGraph {
+    /// Build the control flow graph, discovering functions from `entry` and
+    /// every internal call target
+    pub fn build(instructions: &[Instruction], entry: u64) -> Self {
+        Self::build_with_symbols(instructions, entry, &[])
+    }
+
+    /// Build the control flow graph, additionally treating every function
+    /// symbol as a function entry and naming functions after their symbols
+    pub fn build_with_symbols(instructions: &[Instruction], entry: u64, symbols: &[Symbol]) -> Self {
+        let address_index: HashMap<u64, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, inst)| (inst.address, i))
+            .collect();
+
+        // Function entries: program entry, internal call targets and symbols
+        let mut function_entries = BTreeSet::new();
+        if address_index.contains_key(&entry) {
+            function_entries.insert(entry);
+        }
+        for inst in instructions {
+            if let Some(target) = inst.call_target() {
+                if address_index.contains_key(&target) {
+                    function_entries.insert(target);
+                }
+            }
+        }
+        for symbol in symbols {
+            if address_index.contains_key(&symbol.address) {
+                function_entries.insert(symbol.address);
+            }
+        }
+        let symbol_names: HashMap<u64, &str> =
+            symbols.iter().map(|s| (s.address, s.name.as_str())).collect();
+
+        // Block leaders: first instruction, function entries, jump targets
+        // and instructions following a jump or exit
+        let mut leaders: BTreeSet<u64> = function_entries.clone();
+        if let Some(first) = instructions.first() {
+            leaders.insert(first.address);
+        }
+        for inst in instructions {
+            if let Some(target) = inst.jump_target() {
+                if address_index.contains_key(&target) {
+                    leaders.insert(target);
+                }
+            }
+            let ends_block = inst.opcode.is_jump() || matches!(inst.opcode, Opcode::Exit);
+            if ends_block && address_index.contains_key(&inst.next_address()) {
+                leaders.insert(inst.next_address());
+            }
+        }
+
+        // Create blocks
+        let leaders: Vec<u64> = leaders.into_iter().collect();
+        let mut blocks = Vec::new();
+        let mut block_index = HashMap::new();
+        for (idx, &start) in leaders.iter().enumerate() {
+            let first = address_index[&start];
+            let last = match leaders.get(idx + 1) {
+                Some(next) => address_index[next],
+                None => instructions.len(),
+            };
+            let end = instructions[last - 1].next_address();
+            block_index.insert(start, blocks.len());
+            blocks.push(BasicBlock {
+                id: blocks.len(),
+                start,
+                end,
+                instructions: first..last,
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            });
+        }
+
+        // Connect edges
+        for id in 0..blocks.len() {
+            let last = &instructions[blocks[id].instructions.end - 1];
+            let mut successors = Vec::new();
+            if let Some(target) = last.jump_target() {
+                if let Some(&target_id) = block_index.get(&target) {
+                    successors.push(target_id);
+                }
+            }
+            if !last.is_terminator() {
+                if let Some(&next_id) = block_index.get(&last.next_address()) {
+                    if !successors.contains(&next_id) {
+                        successors.push(next_id);
+                    }
+                }
+            }
+            for &succ in &successors {
+                blocks[succ].predecessors.push(id);
+            }
+            blocks[id].successors = successors;
+        }
+
+        // Collect function bodies
+        let functions = function_entries
+            .iter()
+            .map(|&address| {
+                let entry_id = block_index[&address];
+                let mut seen = BTreeSet::new();
+                let mut stack = vec![entry_id];
+                while let Some(id) = stack.pop() {
+                    if seen.insert(id) {
+                        stack.extend(blocks[id].successors.iter().copied());
+                    }
+                }
+                let symbol = symbol_names.get(&address);
+                Function {
+                    address,
+                    name: match symbol {
+                        Some(name) => name.to_string(),
+                        None if address == entry => "entry".to_string(),
+                        None => format!("func_0x{:x}", address),
+                    },
+                    entry: entry_id,
+                    blocks: seen.into_iter().collect(),
+                    library: symbol.is_some_and(|name| is_library_name(name)),
+                }
+            })
+            .collect();
+
+        ControlFlowGraph {
+            blocks,
+            functions,
+            block_index,
+        }
+    }
+
+    /// Get the id of the block starting at `address`
+    pub fn block_id_at(&self, address: u64) -> Option<usize> {
+        self.block_index.get(&address).copied()
+    }
+
+    /// Get the block starting at `address`
+    pub fn block_at(&self, address: u64) -> Option<&BasicBlock> {
+        self.block_id_at(address).map(|id| &self.blocks[id])
+    }
+
+    /// Get the id of the block containing the instruction at `address`
+    pub fn block_containing(&self, address: u64) -> Option<usize> {
+        let idx = self.blocks.partition_point(|b| b.start <= address);
+        if idx == 0 {
+            return None;
+        }
+        let block = &self.blocks[idx - 1];
+        (address < block.end).then_some(block.id)
+    }
+
+    /// Get the function starting at `address`
+    pub fn function_at(&self, address: u64) -> Option<&Function> {
+        self.functions.iter().find(|f| f.address == address)
+    }
+
+    /// Instructions of `function` in address order
+    pub fn function_instructions<'a>(
+        &'a self,
+        function: &'a Function,
+        instructions: &'a [Instruction],
+    ) -> impl Iterator<Item = &'a Instruction> {
+        function
+            .blocks
+            .iter()
+            .flat_map(move |&id| instructions[self.blocks[id].instructions.clone()].iter())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::instruction::InstructionDecoder;
+
+    /// Encode a single instruction slot
+    pub(crate) fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[0] = code;
+        bytes[1] = (src << 4) | dst;
+        bytes[2..4].copy_from_slice(&off.to_le_bytes());
+        bytes[4..8].copy_from_slice(&imm.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_build_blocks_and_functions() {
+        let program: Vec<u8> = [
+            insn(0xb7, 0, 0, 0, 0),  // 0x00: mov r0, 0
+            insn(0x85, 0, 1, 0, 3),  // 0x08: call +3
+            insn(0x15, 0, 0, 1, 0),  // 0x10: jeq r0, 0, +1
+            insn(0x07, 0, 0, 0, 1),  // 0x18: add r0, 1
+            insn(0x95, 0, 0, 0, 0),  // 0x20: exit
+            insn(0xb7, 0, 0, 0, 7),  // 0x28: mov r0, 7
+            insn(0x95, 0, 0, 0, 0),  // 0x30: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+
+        assert_eq!(cfg.functions.len(), 2);
+        assert_eq!(cfg.functions[1].address, 0x28);
+        assert_eq!(cfg.functions[0].blocks.len(), 3);
+
+        let head = cfg.block_id_at(0).unwrap();
+        let exit = cfg.block_id_at(0x20).unwrap();
+        let add = cfg.block_id_at(0x18).unwrap();
+        assert_eq!(cfg.blocks[head].successors, vec![exit, add]);
+        assert_eq!(cfg.block_containing(0x10), Some(head));
+    }
+}
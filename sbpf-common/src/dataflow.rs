@@ -0,0 +1,497 @@
+use crate::binary::sbpf::Opcode;
+use crate::cfg::dominators::reverse_postorder;
+use crate::cfg::ControlFlowGraph;
+use crate::input::{
+    AccountField, ACCOUNT_DATA_OFFSET, FIRST_ACCOUNT_OFFSET, MAX_PERMITTED_DATA_INCREASE,
+    RENT_EPOCH_SIZE,
+};
+use crate::instruction::Instruction;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Number of times argument values are propagated from call sites to callees
+const INTERPROCEDURAL_ROUNDS: usize = 4;
+
+/// Memory location a pointer refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Location {
+    /// Serialized input buffer, before the first account record
+    Input(i64),
+    /// Offset within account record `index`
+    Account(u16, i64),
+    /// Offset past the end of the data of account record `index`
+    AfterData(u16, i64),
+    /// Current stack frame, relative to r10
+    Stack(i64),
+    /// Incoming argument register of the function
+    Arg(u8, i64),
+    /// Fixed virtual address, such as read-only data
+    Absolute(u64),
+}
+
+impl Location {
+    /// Location `delta` bytes further, normalised to account records where possible
+    pub fn offset_by(self, delta: i64) -> Location {
+        match self {
+            Location::Input(off) => {
+                let off = off.wrapping_add(delta);
+                if off >= FIRST_ACCOUNT_OFFSET {
+                    Location::Account(0, off - FIRST_ACCOUNT_OFFSET)
+                } else {
+                    Location::Input(off)
+                }
+            }
+            Location::Account(index, off) => Location::Account(index, off.wrapping_add(delta)),
+            Location::AfterData(index, off) => {
+                let off = off.wrapping_add(delta);
+                let record_end = MAX_PERMITTED_DATA_INCREASE + RENT_EPOCH_SIZE;
+                if off >= record_end {
+                    Location::Account(index + 1, off - record_end)
+                } else {
+                    Location::AfterData(index, off)
+                }
+            }
+            Location::Stack(off) => Location::Stack(off.wrapping_add(delta)),
+            Location::Arg(reg, off) => Location::Arg(reg, off.wrapping_add(delta)),
+            Location::Absolute(addr) => Location::Absolute(addr.wrapping_add(delta as u64)),
+        }
+    }
+
+    /// Account record field this location falls in
+    pub fn account_field(&self) -> Option<(u16, AccountField)> {
+        match *self {
+            Location::Account(index, off) => {
+                AccountField::from_record_offset(off).map(|field| (index, field))
+            }
+            Location::AfterData(index, off) if off >= MAX_PERMITTED_DATA_INCREASE => {
+                Some((index, AccountField::RentEpoch))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if the location is known independently of the current function
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self,
+            Location::Input(_) | Location::Account(..) | Location::AfterData(..) | Location::Absolute(_)
+        )
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Location::Input(off) => write!(f, "input+0x{:x}", off),
+            Location::Account(index, off) => write!(f, "accounts[{}]+0x{:x}", index, off),
+            Location::AfterData(index, off) => write!(f, "accounts[{}].data_end+0x{:x}", index, off),
+            Location::Stack(off) if off < 0 => write!(f, "r10-0x{:x}", -off),
+            Location::Stack(off) => write!(f, "r10+0x{:x}", off),
+            Location::Arg(reg, off) => write!(f, "arg_r{}+0x{:x}", reg, off),
+            Location::Absolute(addr) => write!(f, "0x{:x}", addr),
+        }
+    }
+}
+
+/// Abstract value of a register or stack slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Unknown,
+    Const(u64),
+    /// Pointer to a known location
+    Pointer(Location),
+    /// Value of `size` bytes loaded from a known location
+    Loaded(Location, u8),
+}
+
+impl Value {
+    /// Location a pointer value refers to
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Value::Pointer(loc) => Some(*loc),
+            _ => None,
+        }
+    }
+
+    /// Account record field this pointer refers to
+    pub fn account_field(&self) -> Option<(u16, AccountField)> {
+        self.location().and_then(|loc| loc.account_field())
+    }
+
+    /// Check if the value is meaningful in another function
+    fn is_global(&self) -> bool {
+        match self {
+            Value::Unknown => false,
+            Value::Const(_) => true,
+            Value::Pointer(loc) | Value::Loaded(loc, _) => loc.is_global(),
+        }
+    }
+}
+
+/// Abstract machine state before an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub regs: [Value; 11],
+    /// 8-byte stack slots keyed by r10-relative offset
+    pub stack: BTreeMap<i64, Value>,
+}
+
+impl State {
+    /// State at the entry of a function with the given argument values
+    fn entry(args: &[Value; 5]) -> Self {
+        let mut regs = [Value::Unknown; 11];
+        regs[1..6].copy_from_slice(args);
+        regs[10] = Value::Pointer(Location::Stack(0));
+        State {
+            regs,
+            stack: BTreeMap::new(),
+        }
+    }
+
+    /// Value of register `reg`
+    pub fn reg(&self, reg: u8) -> Value {
+        self.regs.get(reg as usize).copied().unwrap_or(Value::Unknown)
+    }
+
+    /// Value of the source operand (register or immediate) of `inst`
+    pub fn source(&self, inst: &Instruction) -> Value {
+        if inst.uses_src_reg() {
+            self.reg(inst.src_reg)
+        } else {
+            Value::Const(inst.imm as u64)
+        }
+    }
+
+    /// Location accessed by a load or store
+    pub fn memory_location(&self, inst: &Instruction) -> Option<Location> {
+        let base = match inst.opcode {
+            Opcode::Ldx => inst.src_reg,
+            Opcode::St | Opcode::Stx => inst.dst_reg,
+            _ => return None,
+        };
+        match self.reg(base) {
+            Value::Const(addr) => Some(Location::Absolute(addr).offset_by(inst.off as i64)),
+            value => value.location().map(|loc| loc.offset_by(inst.off as i64)),
+        }
+    }
+
+    /// Value stored in the stack slot at `offset`
+    pub fn stack_slot(&self, offset: i64) -> Value {
+        self.stack.get(&offset).copied().unwrap_or(Value::Unknown)
+    }
+
+    /// Merge `other` into this state, returning whether anything changed
+    fn join(&mut self, other: &State) -> bool {
+        let mut changed = false;
+        for (mine, theirs) in self.regs.iter_mut().zip(other.regs.iter()) {
+            if *mine != *theirs && *mine != Value::Unknown {
+                *mine = Value::Unknown;
+                changed = true;
+            }
+        }
+        let before = self.stack.len();
+        self.stack.retain(|off, value| other.stack.get(off) == Some(value));
+        changed || self.stack.len() != before
+    }
+
+    /// Apply the effect of `inst`
+    pub fn transfer(&mut self, inst: &Instruction) {
+        let dst = inst.dst_reg as usize;
+        if dst > 10 {
+            return;
+        }
+        match inst.opcode {
+            Opcode::Lddw => self.regs[dst] = Value::Const(inst.imm as u64),
+            Opcode::Ldx => {
+                let size = inst.mem_size().unwrap_or(8);
+                self.regs[dst] = match self.memory_location(inst) {
+                    Some(Location::Stack(off)) if size == 8 => self.stack_slot(off),
+                    Some(Location::Stack(_)) | None => Value::Unknown,
+                    Some(loc) => Value::Loaded(loc, size),
+                };
+            }
+            Opcode::St | Opcode::Stx => {
+                if let Some(Location::Stack(off)) = self.memory_location(inst) {
+                    let size = inst.mem_size().unwrap_or(8) as i64;
+                    self.stack.retain(|&slot, _| slot + 8 <= off || slot >= off + size);
+                    if size == 8 {
+                        let value = match inst.opcode {
+                            Opcode::St => Value::Const(inst.imm as u64),
+                            _ => self.reg(inst.src_reg),
+                        };
+                        self.stack.insert(off, value);
+                    }
+                }
+            }
+            Opcode::Call | Opcode::Callx => {
+                for reg in inst.written_registers() {
+                    self.regs[reg as usize] = Value::Unknown;
+                }
+            }
+            Opcode::LdAbs | Opcode::LdInd => self.regs[0] = Value::Unknown,
+            _ => {
+                if !inst.written_registers().contains(&inst.dst_reg) {
+                    return;
+                }
+                let lhs = self.reg(inst.dst_reg);
+                let rhs = self.source(inst);
+                let value = evaluate_alu(inst.opcode, lhs, rhs);
+                self.regs[dst] = if inst.is_alu32() {
+                    match value {
+                        Value::Const(c) => Value::Const(c & 0xffff_ffff),
+                        Value::Loaded(loc, size) if size <= 4 => Value::Loaded(loc, size),
+                        _ => Value::Unknown,
+                    }
+                } else {
+                    value
+                };
+            }
+        }
+    }
+}
+
+/// Evaluate an ALU operation on abstract values
+fn evaluate_alu(opcode: Opcode, lhs: Value, rhs: Value) -> Value {
+    use Value::*;
+    match (opcode, lhs, rhs) {
+        (Opcode::Mov, _, value) => value,
+        (Opcode::Add, Pointer(loc), Const(c)) | (Opcode::Add, Const(c), Pointer(loc)) => {
+            Pointer(loc.offset_by(c as i64))
+        }
+        (Opcode::Sub, Pointer(loc), Const(c)) => Pointer(loc.offset_by((c as i64).wrapping_neg())),
+        // Skipping over the data of an account record
+        (Opcode::Add, Pointer(Location::Account(i, off)), Loaded(Location::Account(j, 80), 8))
+        | (Opcode::Add, Loaded(Location::Account(j, 80), 8), Pointer(Location::Account(i, off)))
+            if i == j =>
+        {
+            Pointer(Location::AfterData(i, off - ACCOUNT_DATA_OFFSET))
+        }
+        // Aligning a pointer down to 8 bytes
+        (Opcode::And, Pointer(loc), Const(mask)) if mask as i64 == -8 => Pointer(match loc {
+            Location::Account(i, off) => Location::Account(i, off & !7),
+            Location::AfterData(i, off) => Location::AfterData(i, off & !7),
+            other => other,
+        }),
+        (_, Const(a), Const(b)) => match fold_constant(opcode, a, b) {
+            Some(c) => Const(c),
+            None => Unknown,
+        },
+        _ => Unknown,
+    }
+}
+
+/// Fold a 64-bit ALU operation on constant operands
+pub fn fold_constant(opcode: Opcode, a: u64, b: u64) -> Option<u64> {
+    Some(match opcode {
+        Opcode::Add => a.wrapping_add(b),
+        Opcode::Sub => a.wrapping_sub(b),
+        Opcode::Mul => a.wrapping_mul(b),
+        Opcode::Div => a.checked_div(b)?,
+        Opcode::Mod => a.checked_rem(b)?,
+        Opcode::Or => a | b,
+        Opcode::And => a & b,
+        Opcode::Xor => a ^ b,
+        Opcode::Lsh => a.wrapping_shl(b as u32),
+        Opcode::Rsh => a.wrapping_shr(b as u32),
+        Opcode::Arsh => (a as i64).wrapping_shr(b as u32) as u64,
+        Opcode::Neg => (a as i64).wrapping_neg() as u64,
+        _ => return None,
+    })
+}
+
+/// Dataflow facts for a single function
+#[derive(Debug, Clone)]
+pub struct FunctionDataflow {
+    /// Function address
+    pub address: u64,
+    /// Function name
+    pub name: String,
+    /// Index of the function in the control flow graph
+    pub index: usize,
+    /// State at the entry of each reachable block
+    block_states: HashMap<usize, State>,
+}
+
+impl FunctionDataflow {
+    /// Visit every reachable instruction in address order together with the
+    /// state before it
+    pub fn visit<F>(&self, cfg: &ControlFlowGraph, instructions: &[Instruction], mut f: F)
+    where
+        F: FnMut(usize, &Instruction, &State),
+    {
+        for &block in &cfg.functions[self.index].blocks {
+            let Some(entry_state) = self.block_states.get(&block) else {
+                continue;
+            };
+            let mut state = entry_state.clone();
+            for idx in cfg.blocks[block].instructions.clone() {
+                f(idx, &instructions[idx], &state);
+                state.transfer(&instructions[idx]);
+            }
+        }
+    }
+}
+
+/// Abstract interpretation of register and stack values across the program
+#[derive(Debug, Clone)]
+pub struct Dataflow {
+    pub functions: Vec<FunctionDataflow>,
+}
+
+impl Dataflow {
+    /// Track values in every function, starting the program entry with r1
+    /// pointing at the serialized input buffer and propagating constant and
+    /// input-buffer arguments from call sites to callees
+    pub fn compute(cfg: &ControlFlowGraph, instructions: &[Instruction], entry: u64) -> Self {
+        let mut seeds: HashMap<u64, [Value; 5]> = cfg
+            .functions
+            .iter()
+            .map(|f| {
+                let mut args = [Value::Unknown; 5];
+                for (i, arg) in args.iter_mut().enumerate() {
+                    *arg = Value::Pointer(Location::Arg(i as u8 + 1, 0));
+                }
+                if f.address == entry {
+                    args[0] = Value::Pointer(Location::Input(0));
+                }
+                (f.address, args)
+            })
+            .collect();
+
+        let mut functions = Vec::new();
+        for _ in 0..INTERPROCEDURAL_ROUNDS {
+            functions = cfg
+                .functions
+                .iter()
+                .enumerate()
+                .map(|(index, f)| analyze_function(cfg, instructions, index, &seeds[&f.address]))
+                .collect();
+
+            // Argument values that agree across every call site
+            let mut incoming: HashMap<u64, [Value; 5]> = HashMap::new();
+            for function in &functions {
+                function.visit(cfg, instructions, |_, inst, state| {
+                    let Some(target) = inst.call_target() else {
+                        return;
+                    };
+                    let args: [Value; 5] = std::array::from_fn(|i| state.reg(i as u8 + 1));
+                    incoming
+                        .entry(target)
+                        .and_modify(|existing| {
+                            for (mine, theirs) in existing.iter_mut().zip(args.iter()) {
+                                if mine != theirs {
+                                    *mine = Value::Unknown;
+                                }
+                            }
+                        })
+                        .or_insert(args);
+                });
+            }
+
+            let mut changed = false;
+            for (target, args) in incoming {
+                let Some(seed) = seeds.get_mut(&target) else {
+                    continue;
+                };
+                if target == entry {
+                    continue;
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    let value = if arg.is_global() {
+                        *arg
+                    } else {
+                        Value::Pointer(Location::Arg(i as u8 + 1, 0))
+                    };
+                    if seed[i] != value {
+                        seed[i] = value;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        Dataflow { functions }
+    }
+}
+
+fn analyze_function(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    index: usize,
+    args: &[Value; 5],
+) -> FunctionDataflow {
+    let function = &cfg.functions[index];
+    let order = reverse_postorder(cfg, function.entry);
+    let mut block_states: HashMap<usize, State> = HashMap::new();
+    block_states.insert(function.entry, State::entry(args));
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in &order {
+            let Some(mut state) = block_states.get(&block).cloned() else {
+                continue;
+            };
+            for inst in &instructions[cfg.blocks[block].instructions.clone()] {
+                state.transfer(inst);
+            }
+            for &succ in &cfg.blocks[block].successors {
+                match block_states.get_mut(&succ) {
+                    Some(existing) => changed |= existing.join(&state),
+                    None => {
+                        block_states.insert(succ, state.clone());
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    FunctionDataflow {
+        address: function.address,
+        name: function.name.clone(),
+        index,
+        block_states,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_walks_to_second_account() {
+        let program: Vec<u8> = [
+            insn(0x79, 2, 1, 88, 0),   // ldxdw r2, [r1+88]   account 0 data_len
+            insn(0xbf, 3, 1, 0, 0),    // mov r3, r1
+            insn(0x07, 3, 0, 0, 8),    // add r3, 8           account 0 record
+            insn(0x0f, 3, 2, 0, 0),    // add r3, r2          skip data
+            insn(0x07, 3, 0, 0, 10335), // add r3, 88 + 10240 + 7
+            insn(0x57, 3, 0, 0, -8),   // and r3, -8
+            insn(0x07, 3, 0, 0, 8),    // add r3, 8           skip rent epoch
+            insn(0x71, 4, 3, 1, 0),    // ldxb r4, [r3+1]     account 1 is_signer
+            insn(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+
+        let mut last = None;
+        dataflow.functions[0].visit(&cfg, &instructions, |idx, inst, state| {
+            if idx == 7 {
+                last = state.memory_location(inst);
+            }
+        });
+        assert_eq!(last, Some(Location::Account(1, 1)));
+        assert_eq!(
+            last.and_then(|l| l.account_field()),
+            Some((1, AccountField::IsSigner))
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Recovery of native instruction dispatch
+//
+// Native programs select the instruction to run by switching on a tag at
+// the start of the instruction data: a `u8` for hand-written enums and
+// Borsh, a `u32` for bincode. With the `entrypoint!` macro the data reaches
+// `process_instruction(program_id, accounts, instruction_data)` as the
+// slice in r4/r5. The compiler lowers the `match` to a chain of equality
+// compares against the loaded tag, each branching to (or calling) a handler,
+// or for larger enums to a binary search that splits the tag range with
+// `jgt`/`jlt` before the equality leaves.
+
+use crate::binary::sbpf::Opcode;
+use crate::cfg::ControlFlowGraph;
+use crate::dataflow::{Dataflow, Location, Value};
+use crate::instruction::Instruction;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Blocks followed from a matching comparison when looking for the handler call
+pub const MAX_HANDLER_BLOCKS: usize = 8;
+
+/// Argument register holding the instruction data pointer in `process_instruction`
+pub const INSTRUCTION_DATA_ARG: u8 = 4;
+
+/// Largest value accepted as an instruction tag
+const MAX_TAG: u64 = 255;
+
+/// Instruction selected by one tag value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispatchCase {
+    pub tag: u64,
+    /// Address of the comparison against the tag
+    pub compare: u64,
+    /// Address of the handler function, or of the matching branch when the
+    /// handler is inlined into the dispatcher
+    pub handler: u64,
+    /// Name of the handler function, if it is a separate function
+    pub handler_name: Option<String>,
+}
+
+/// Switch on the instruction tag
+#[derive(Debug, Clone)]
+pub struct Dispatch {
+    /// Address of the function containing the switch
+    pub function: u64,
+    /// Where the tag is loaded from
+    pub tag_location: Location,
+    /// Width of the tag in bytes
+    pub tag_size: u8,
+    /// Cases sorted by tag
+    pub cases: Vec<DispatchCase>,
+}
+
+impl Dispatch {
+    /// Name given to the handler of `tag`
+    pub fn handler_name(tag: u64) -> String {
+        format!("handle_ix_{}", tag)
+    }
+
+    /// Name every separate, otherwise unnamed handler function after its tag
+    pub fn name_handlers(&mut self, cfg: &mut ControlFlowGraph) {
+        let mut tags: HashMap<u64, Vec<u64>> = HashMap::new();
+        for case in &self.cases {
+            tags.entry(case.handler).or_default().push(case.tag);
+        }
+        for function in cfg.functions.iter_mut() {
+            // Handlers shared by several tags keep their generic name
+            if let Some([tag]) = tags.get(&function.address).map(Vec::as_slice) {
+                if !function.library && function.name.starts_with("func_0x") {
+                    function.name = Self::handler_name(*tag);
+                }
+            }
+        }
+        for case in &mut self.cases {
+            if case.handler_name.is_some() {
+                case.handler_name = cfg.function_at(case.handler).map(|f| f.name.clone());
+            }
+        }
+    }
+}
+
+/// Follow the matching branch to the first internal call
+pub fn resolve_handler(cfg: &ControlFlowGraph, branch: usize, instructions: &[Instruction]) -> (u64, Option<String>) {
+    let mut id = branch;
+    for _ in 0..MAX_HANDLER_BLOCKS {
+        let block = &cfg.blocks[id];
+        let callee = instructions[block.instructions.clone()]
+            .iter()
+            .find_map(|inst| inst.call_target());
+        if let Some(target) = callee {
+            let name = cfg.function_at(target).map(|f| f.name.clone());
+            return (target, name);
+        }
+        match block.successors.as_slice() {
+            [next] => id = *next,
+            _ => break,
+        }
+    }
+    (cfg.blocks[branch].start, None)
+}
+
+/// Inclusive range of tag values reaching a block of the decision tree
+type TagRange = (u64, u64);
+
+/// Find the switch on the instruction tag, preferring one on the
+/// `process_instruction` data argument, then the one with the most cases
+pub fn recover(cfg: &ControlFlowGraph, instructions: &[Instruction], dataflow: &Dataflow) -> Option<Dispatch> {
+    let mut best: Option<(bool, Dispatch)> = None;
+    let records = instruction_data_records(cfg, instructions, dataflow);
+
+    for function in &dataflow.functions {
+        let cfg_function = &cfg.functions[function.index];
+        if cfg_function.library {
+            continue;
+        }
+
+        // (location, size) -> block -> branch ending it on the tag
+        let mut trees: BTreeMap<(Location, u8), BTreeMap<usize, &Instruction>> = BTreeMap::new();
+        function.visit(cfg, instructions, |idx, inst, state| {
+            if !inst.opcode.is_conditional_jump() || inst.uses_src_reg() {
+                return;
+            }
+            let Value::Loaded(loc, size @ (1 | 4)) = state.reg(inst.dst_reg) else {
+                return;
+            };
+            if !starts_data(loc, &records) {
+                return;
+            }
+            if let Some(block) = cfg.block_containing(inst.address) {
+                if cfg.blocks[block].instructions.end == idx + 1 {
+                    trees.entry((loc, size)).or_default().insert(block, &instructions[idx]);
+                }
+            }
+        });
+
+        // (location, size) -> tag -> (compare, branch block)
+        let switches: BTreeMap<(Location, u8), BTreeMap<u64, (u64, usize)>> = trees
+            .into_iter()
+            .map(|(key, branches)| (key, tree_leaves(cfg, &branches, key.1)))
+            .collect();
+
+        for ((loc, size), cases) in switches {
+            if cases.len() < 2 {
+                continue;
+            }
+            let on_data_arg = loc == Location::Arg(INSTRUCTION_DATA_ARG, 0);
+            let better = match &best {
+                Some((best_on_arg, best)) => {
+                    (on_data_arg, cases.len()) > (*best_on_arg, best.cases.len())
+                }
+                None => true,
+            };
+            if !better {
+                continue;
+            }
+            let cases = cases
+                .into_iter()
+                .map(|(tag, (compare, branch))| {
+                    let (handler, handler_name) = resolve_handler(cfg, branch, instructions);
+                    DispatchCase {
+                        tag,
+                        compare,
+                        handler,
+                        handler_name,
+                    }
+                })
+                .collect();
+            best = Some((
+                on_data_arg,
+                Dispatch {
+                    function: function.address,
+                    tag_location: loc,
+                    tag_size: size,
+                    cases,
+                },
+            ));
+        }
+    }
+
+    best.map(|(_, dispatch)| dispatch)
+}
+
+/// Walk the tag's decision tree from its roots, narrowing the range of tag
+/// values along each branch, and collect the blocks the tree leaves with a
+/// single tag value as the cases
+fn tree_leaves(
+    cfg: &ControlFlowGraph,
+    branches: &BTreeMap<usize, &Instruction>,
+    size: u8,
+) -> BTreeMap<u64, (u64, usize)> {
+    let full: TagRange = (0, if size == 1 { u8::MAX as u64 } else { u32::MAX as u64 });
+    let mut ranges: BTreeMap<usize, TagRange> = branches
+        .keys()
+        .filter(|&&block| !cfg.blocks[block].predecessors.iter().any(|p| branches.contains_key(p)))
+        .map(|&block| (block, full))
+        .collect();
+    let mut worklist: Vec<usize> = ranges.keys().copied().collect();
+    let mut cases: BTreeMap<u64, (u64, usize)> = BTreeMap::new();
+
+    while let Some(block) = worklist.pop() {
+        let range = ranges[&block];
+        let branch = branches[&block];
+        // Successors are ordered target first, then fallthrough
+        let [taken, fallthrough] = cfg.blocks[block].successors[..] else {
+            continue;
+        };
+        let Some((taken_range, fallthrough_range)) = split_range(branch, range) else {
+            continue;
+        };
+        for (succ, succ_range) in [(taken, taken_range), (fallthrough, fallthrough_range)] {
+            let Some((lo, hi)) = succ_range else {
+                continue;
+            };
+            if !branches.contains_key(&succ) {
+                if lo == hi && lo <= MAX_TAG {
+                    cases.entry(lo).or_insert((branch.address, succ));
+                }
+                continue;
+            }
+            // Joins take the hull of the incoming ranges
+            let merged = match ranges.get(&succ) {
+                Some(&(old_lo, old_hi)) => (old_lo.min(lo), old_hi.max(hi)),
+                None => (lo, hi),
+            };
+            if ranges.insert(succ, merged) != Some(merged) {
+                worklist.push(succ);
+            }
+        }
+    }
+    cases
+}
+
+/// Ranges of tag values taking and falling through `branch` from `range`,
+/// `None` for a side no value in `range` reaches
+fn split_range(branch: &Instruction, (lo, hi): TagRange) -> Option<(Option<TagRange>, Option<TagRange>)> {
+    let v = branch.imm as u64;
+    let clamp = |lo: u64, hi: u64| (lo <= hi).then_some((lo, hi));
+    let below = |v: u64| v.checked_sub(1).and_then(|v| clamp(lo, hi.min(v)));
+    let above = |v: u64| v.checked_add(1).and_then(|v| clamp(lo.max(v), hi));
+    // Values other than `v` keep the range unless `v` is at one end
+    let except = || match v {
+        v if v == lo && v == hi => None,
+        v if v == lo => clamp(lo + 1, hi),
+        v if v == hi => clamp(lo, hi - 1),
+        _ => Some((lo, hi)),
+    };
+    let equal = (lo <= v && v <= hi).then_some((v, v));
+    Some(match branch.opcode {
+        Opcode::Jeq => (equal, except()),
+        Opcode::Jne => (except(), equal),
+        Opcode::Jgt => (above(v), clamp(lo, hi.min(v))),
+        Opcode::Jge => (clamp(lo.max(v), hi), below(v)),
+        Opcode::Jlt => (below(v), clamp(lo.max(v), hi)),
+        Opcode::Jle => (clamp(lo, hi.min(v)), above(v)),
+        _ => return None,
+    })
+}
+
+/// Account records whose first word is loaded as a `u64`. Past the last
+/// account the input holds the instruction data length there, followed by
+/// the data; in a record the same offsets hold flags and the key.
+pub fn instruction_data_records(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    dataflow: &Dataflow,
+) -> BTreeSet<u16> {
+    let mut records = BTreeSet::new();
+    for function in &dataflow.functions {
+        function.visit(cfg, instructions, |_, inst, state| {
+            if inst.opcode == Opcode::Ldx && inst.mem_size() == Some(8) {
+                if let Some(Location::Account(index, 0)) = state.memory_location(inst) {
+                    records.insert(index);
+                }
+            }
+        });
+    }
+    records
+}
+
+/// Check if `loc` is the start of the instruction data: the data argument
+/// of `process_instruction`, or the bytes following the data length after
+/// the last account in the raw input buffer
+pub fn is_instruction_data(loc: Location, records: &BTreeSet<u16>) -> bool {
+    match loc {
+        Location::Arg(reg, off) => reg == INSTRUCTION_DATA_ARG && off == 0,
+        Location::Account(index, off) => off == 8 && records.contains(&index),
+        _ => false,
+    }
+}
+
+/// Tags are read from the first byte of a buffer
+fn starts_data(loc: Location, records: &BTreeSet<u16>) -> bool {
+    match loc {
+        Location::Arg(_, off) => off == 0,
+        // Raw entrypoints read the data that follows the last account
+        Location::Account(..) => is_instruction_data(loc, records),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_recover_tag_switch() {
+        let program: Vec<u8> = [
+            insn(0x85, 0, 1, 0, 1),  // 0x00: call 0x10 (process_instruction)
+            insn(0x95, 0, 0, 0, 0),  // 0x08: exit
+            insn(0x71, 6, 4, 0, 0),  // 0x10: ldxb r6, [r4+0]
+            insn(0x15, 6, 0, 2, 0),  // 0x18: jeq r6, 0, +2
+            insn(0x15, 6, 0, 3, 1),  // 0x20: jeq r6, 1, +3
+            insn(0x95, 0, 0, 0, 0),  // 0x28: exit
+            insn(0x85, 0, 1, 0, 2),  // 0x30: call 0x48
+            insn(0x95, 0, 0, 0, 0),  // 0x38: exit
+            insn(0x85, 0, 1, 0, 2),  // 0x40: call 0x58
+            insn(0x95, 0, 0, 0, 0),  // 0x48: exit
+            insn(0xb7, 0, 0, 0, 1),  // 0x50: mov r0, 1 (unreachable padding)
+            insn(0x95, 0, 0, 0, 0),  // 0x58: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let mut cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let mut dispatch = recover(&cfg, &instructions, &dataflow).unwrap();
+
+        assert_eq!(dispatch.function, 0x10);
+        assert_eq!(dispatch.tag_location, Location::Arg(4, 0));
+        let handlers: Vec<(u64, u64)> = dispatch.cases.iter().map(|c| (c.tag, c.handler)).collect();
+        assert_eq!(handlers, vec![(0, 0x48), (1, 0x58)]);
+
+        dispatch.name_handlers(&mut cfg);
+        assert_eq!(cfg.function_at(0x58).unwrap().name, "handle_ix_1");
+        assert_eq!(dispatch.cases[0].handler_name.as_deref(), Some("handle_ix_0"));
+    }
+
+    #[test]
+    fn test_recover_binary_search_switch() {
+        let program: Vec<u8> = [
+            insn(0x85, 0, 1, 0, 1),  // 0x00: call 0x10 (process_instruction)
+            insn(0x95, 0, 0, 0, 0),  // 0x08: exit
+            insn(0x71, 6, 4, 0, 0),  // 0x10: ldxb r6, [r4+0]
+            insn(0x25, 6, 0, 5, 1),  // 0x18: jgt r6, 1, +5
+            insn(0x15, 6, 0, 2, 0),  // 0x20: jeq r6, 0, +2
+            insn(0x85, 0, 1, 0, 9),  // 0x28: call 0x78 (tag 1)
+            insn(0x95, 0, 0, 0, 0),  // 0x30: exit
+            insn(0x85, 0, 1, 0, 5),  // 0x38: call 0x68 (tag 0)
+            insn(0x95, 0, 0, 0, 0),  // 0x40: exit
+            insn(0x55, 6, 0, 2, 2),  // 0x48: jne r6, 2, +2
+            insn(0x85, 0, 1, 0, 6),  // 0x50: call 0x88 (tag 2)
+            insn(0x95, 0, 0, 0, 0),  // 0x58: exit
+            insn(0x95, 0, 0, 0, 0),  // 0x60: exit
+            insn(0xb7, 0, 0, 0, 0),  // 0x68: mov r0, 0
+            insn(0x95, 0, 0, 0, 0),  // 0x70: exit
+            insn(0xb7, 0, 0, 0, 1),  // 0x78: mov r0, 1
+            insn(0x95, 0, 0, 0, 0),  // 0x80: exit
+            insn(0xb7, 0, 0, 0, 2),  // 0x88: mov r0, 2
+            insn(0x95, 0, 0, 0, 0),  // 0x90: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let dispatch = recover(&cfg, &instructions, &dataflow).unwrap();
+
+        // Tag 1 is only told apart by the range split
+        let handlers: Vec<(u64, u64)> = dispatch.cases.iter().map(|c| (c.tag, c.handler)).collect();
+        assert_eq!(handlers, vec![(0, 0x68), (1, 0x78), (2, 0x88)]);
+    }
+
+    #[test]
+    fn test_raw_switch_needs_data_length() {
+        let program = |first: [u8; 8]| -> Vec<u8> {
+            [
+                first,                    // 0x00
+                insn(0x71, 6, 1, 16, 0),  // 0x08: ldxb r6, [r1+16]
+                insn(0x15, 6, 0, 2, 0),   // 0x10: jeq r6, 0, +2
+                insn(0x15, 6, 0, 2, 1),   // 0x18: jeq r6, 1, +2
+                insn(0x95, 0, 0, 0, 0),   // 0x20: exit
+                insn(0x95, 0, 0, 0, 0),   // 0x28: exit
+                insn(0x95, 0, 0, 0, 0),   // 0x30: exit
+            ]
+            .concat()
+        };
+        let recover_from = |program: Vec<u8>| {
+            let instructions = InstructionDecoder::new(None).decode_all(&program);
+            let cfg = ControlFlowGraph::build(&instructions, 0);
+            let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+            recover(&cfg, &instructions, &dataflow)
+        };
+
+        // Without the length, the byte is the key of the first account
+        assert!(recover_from(program(insn(0xb7, 2, 0, 0, 0))).is_none());
+        // ldxdw r2, [r1+8] reads the length of data following no accounts
+        let dispatch = recover_from(program(insn(0x79, 2, 1, 8, 0))).unwrap();
+        assert_eq!(dispatch.tag_location, Location::Account(0, 8));
+        assert_eq!(dispatch.cases.len(), 2);
+    }
+}
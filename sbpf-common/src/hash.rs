@@ -0,0 +1,131 @@
+// Stable hashing for fingerprints that must match across runs and builds,
+// and SHA-256 for values derived on-chain
+
+use sha2::{Digest, Sha256};
+use std::hash::Hasher;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// 64-bit FNV-1a hasher
+///
+/// Unlike `DefaultHasher`, the output does not depend on the Rust release or
+/// a per-process seed, so hashes can be stored and compared later. Integers
+/// are hashed as little-endian bytes, and `usize`/`isize` as 64 bits, so the
+/// host's byte order and pointer width do not matter either.
+#[derive(Debug, Clone)]
+pub struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+/// SHA-256 digest, as used for Anchor discriminators and program addresses
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_vectors() {
+        let mut hasher = StableHasher::new();
+        assert_eq!(hasher.finish(), 0xcbf29ce484222325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_integers_hash_little_endian() {
+        let hash = |write: &dyn Fn(&mut StableHasher)| {
+            let mut hasher = StableHasher::new();
+            write(&mut hasher);
+            hasher.finish()
+        };
+        let bytes = hash(&|h| h.write(&[0x01, 0x02, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(hash(&|h| h.write_u64(0x0201)), bytes);
+        assert_eq!(hash(&|h| h.write_i64(0x0201)), bytes);
+        assert_eq!(hash(&|h| h.write_usize(0x0201)), bytes);
+        assert_eq!(hash(&|h| h.write_i16(-2)), hash(&|h| h.write(&[0xfe, 0xff])));
+    }
+
+    #[test]
+    fn test_sha256_vectors() {
+        let hex = |d: [u8; 32]| d.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        assert_eq!(
+            hex(sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(sha256(&[b'a'; 64])),
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"
+        );
+    }
+}
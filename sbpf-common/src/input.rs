@@ -0,0 +1,122 @@
+// Layout of the serialized input buffer passed to the program entrypoint in r1
+//
+// +----------------------+
+// | num_accounts: u64    |
+// +----------------------+
+// | account record 0..n  |  (see below, duplicates are 8 bytes)
+// +----------------------+
+// | data_len: u64        |
+// | instruction data     |
+// | program_id: [u8; 32] |
+// +----------------------+
+//
+// Account record (non-duplicate):
+//
+// 0   dup_info: u8 (0xff when not a duplicate)
+// 1   is_signer: u8
+// 2   is_writable: u8
+// 3   executable: u8
+// 4   original_data_len: u32
+// 8   key: [u8; 32]
+// 40  owner: [u8; 32]
+// 72  lamports: u64
+// 80  data_len: u64
+// 88  data: [u8; data_len]
+//     realloc padding: [u8; MAX_PERMITTED_DATA_INCREASE], aligned to 8
+//     rent_epoch: u64
+
+/// Offset of the first account record in the input buffer
+pub const FIRST_ACCOUNT_OFFSET: i64 = 8;
+
+/// Offset of the account data within an account record
+pub const ACCOUNT_DATA_OFFSET: i64 = 88;
+
+/// Extra space reserved after account data for reallocation
+pub const MAX_PERMITTED_DATA_INCREASE: i64 = 10 * 1024;
+
+/// Distance from the end of the aligned realloc padding to the next record
+pub const RENT_EPOCH_SIZE: i64 = 8;
+
+/// Field of a serialized account record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AccountField {
+    DupInfo,
+    IsSigner,
+    IsWritable,
+    Executable,
+    OriginalDataLen,
+    Key,
+    Owner,
+    Lamports,
+    DataLen,
+    Data,
+    RentEpoch,
+}
+
+impl AccountField {
+    /// Classify a byte offset relative to the start of an account record
+    pub fn from_record_offset(offset: i64) -> Option<Self> {
+        Some(match offset {
+            0 => AccountField::DupInfo,
+            1 => AccountField::IsSigner,
+            2 => AccountField::IsWritable,
+            3 => AccountField::Executable,
+            4..=7 => AccountField::OriginalDataLen,
+            8..=39 => AccountField::Key,
+            40..=71 => AccountField::Owner,
+            72..=79 => AccountField::Lamports,
+            80..=87 => AccountField::DataLen,
+            o if o >= ACCOUNT_DATA_OFFSET => AccountField::Data,
+            _ => return None,
+        })
+    }
+
+    /// Byte offset of the field within an account record
+    pub fn record_offset(&self) -> Option<i64> {
+        Some(match self {
+            AccountField::DupInfo => 0,
+            AccountField::IsSigner => 1,
+            AccountField::IsWritable => 2,
+            AccountField::Executable => 3,
+            AccountField::OriginalDataLen => 4,
+            AccountField::Key => 8,
+            AccountField::Owner => 40,
+            AccountField::Lamports => 72,
+            AccountField::DataLen => 80,
+            AccountField::Data => ACCOUNT_DATA_OFFSET,
+            AccountField::RentEpoch => return None,
+        })
+    }
+
+    /// Field name as used in `AccountInfo`
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccountField::DupInfo => "dup_info",
+            AccountField::IsSigner => "is_signer",
+            AccountField::IsWritable => "is_writable",
+            AccountField::Executable => "executable",
+            AccountField::OriginalDataLen => "original_data_len",
+            AccountField::Key => "key",
+            AccountField::Owner => "owner",
+            AccountField::Lamports => "lamports",
+            AccountField::DataLen => "data_len",
+            AccountField::Data => "data",
+            AccountField::RentEpoch => "rent_epoch",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_offsets_roundtrip() {
+        for offset in [0, 1, 2, 3, 8, 40, 72, 80, 88] {
+            let field = AccountField::from_record_offset(offset).unwrap();
+            assert_eq!(field.record_offset(), Some(offset));
+        }
+        assert_eq!(AccountField::from_record_offset(50), Some(AccountField::Owner));
+        assert_eq!(AccountField::from_record_offset(-1), None);
+    }
+}
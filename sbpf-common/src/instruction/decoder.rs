@@ -1,5 +1,5 @@
 use crate::binary::SbpfVersion;
-use crate::binary::sbpf::Opcode;
+use crate::binary::sbpf::{Opcode, INSN_SIZE, LD_DW_IMM};
 
 /// A decoded SBPF instruction
 #[derive(Debug, Clone)]
@@ -8,6 +8,8 @@ pub struct Instruction {
     pub address: u64,
     /// Opcode
     pub opcode: Opcode,
+    /// Raw opcode byte
+    pub code: u8,
     /// Destination register (0-10)
     pub dst_reg: u8,
     /// Source register (0-10)
@@ -34,18 +36,20 @@ impl Instruction {
     /// Get the category of this instruction
     pub fn category(&self) -> InstructionCategory {
         match self.opcode {
-            Opcode::LdAbs | Opcode::LdInd | Opcode::Ldx | Opcode::St | Opcode::Stx => {
+            Opcode::LdAbs | Opcode::LdInd | Opcode::Lddw | Opcode::Ldx | Opcode::St | Opcode::Stx => {
                 InstructionCategory::LoadStore
             }
-            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Mod | Opcode::Mov | Opcode::Arsh => {
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Mod | Opcode::Mov
+            | Opcode::Arsh | Opcode::Neg => {
                 InstructionCategory::Arithmetic
             }
-            Opcode::Or | Opcode::And | Opcode::Xor | Opcode::Lsh | Opcode::Rsh => {
+            Opcode::Or | Opcode::And | Opcode::Xor | Opcode::Lsh | Opcode::Rsh | Opcode::Le
+            | Opcode::Be => {
                 InstructionCategory::Logic
             }
             Opcode::Ja | Opcode::Jeq | Opcode::Jgt | Opcode::Jge | Opcode::Jlt | Opcode::Jle
             | Opcode::Jset | Opcode::Jne | Opcode::Jsgt | Opcode::Jsge | Opcode::Jslt
-            | Opcode::Jsle | Opcode::Call | Opcode::Exit => {
+            | Opcode::Jsle | Opcode::Call | Opcode::Callx | Opcode::Exit => {
                 InstructionCategory::ControlFlow
             }
             Opcode::Unknown(_) => InstructionCategory::Misc,
@@ -53,18 +57,132 @@ impl Instruction {
     }
     
     /// Check if this is a syscall instruction
+    ///
+    /// Syscalls are encoded as `call imm` with `src = 0`, where `imm` is the
+    /// murmur3 hash of the syscall name. Internal calls use `src = 1`.
     pub fn is_syscall(&self) -> bool {
-        matches!(self.opcode, Opcode::Call) && self.imm > 0
+        matches!(self.opcode, Opcode::Call) && self.src_reg == 0
     }
     
     /// Get syscall number if this is a syscall
     pub fn syscall_number(&self) -> Option<u64> {
         if self.is_syscall() {
-            Some(self.imm as u64)
+            Some(self.imm as u32 as u64)
         } else {
             None
         }
     }
+    
+    /// Check if this is an ALU operation on the lower 32 bits
+    pub fn is_alu32(&self) -> bool {
+        self.code & 0x07 == 0x04
+    }
+    
+    /// Check if the source operand is a register rather than the immediate
+    pub fn uses_src_reg(&self) -> bool {
+        match self.category() {
+            InstructionCategory::Arithmetic
+            | InstructionCategory::Logic
+            | InstructionCategory::ControlFlow => self.code & 0x08 != 0,
+            _ => matches!(self.opcode, Opcode::Ldx | Opcode::Stx),
+        }
+    }
+    
+    /// Access width in bytes for load/store instructions
+    pub fn mem_size(&self) -> Option<u8> {
+        match self.opcode {
+            Opcode::Ldx | Opcode::St | Opcode::Stx | Opcode::LdAbs | Opcode::LdInd => {
+                Some(match self.code & 0x18 {
+                    0x00 => 4,
+                    0x08 => 2,
+                    0x10 => 1,
+                    _ => 8,
+                })
+            }
+            _ => None,
+        }
+    }
+    
+    /// Address of the instruction following this one
+    pub fn next_address(&self) -> u64 {
+        self.address + self.size as u64
+    }
+    
+    /// Target address of a jump instruction
+    pub fn jump_target(&self) -> Option<u64> {
+        if self.opcode.is_jump() {
+            Some(slot_offset(self.address, self.off as i64))
+        } else {
+            None
+        }
+    }
+    
+    /// Target address of an internal (pc-relative) call
+    pub fn call_target(&self) -> Option<u64> {
+        if matches!(self.opcode, Opcode::Call) && self.src_reg == 1 {
+            Some(slot_offset(self.address, self.imm))
+        } else {
+            None
+        }
+    }
+    
+    /// Registers read by this instruction
+    ///
+    /// Calls are assumed to read all argument registers r1-r5.
+    pub fn read_registers(&self) -> Vec<u8> {
+        match self.opcode {
+            Opcode::Mov => {
+                if self.uses_src_reg() {
+                    vec![self.src_reg]
+                } else {
+                    Vec::new()
+                }
+            }
+            Opcode::Neg | Opcode::Le | Opcode::Be => vec![self.dst_reg],
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Or | Opcode::And
+            | Opcode::Lsh | Opcode::Rsh | Opcode::Mod | Opcode::Xor | Opcode::Arsh
+            | Opcode::Jeq | Opcode::Jgt | Opcode::Jge | Opcode::Jlt | Opcode::Jle
+            | Opcode::Jset | Opcode::Jne | Opcode::Jsgt | Opcode::Jsge | Opcode::Jslt
+            | Opcode::Jsle => {
+                if self.uses_src_reg() {
+                    vec![self.dst_reg, self.src_reg]
+                } else {
+                    vec![self.dst_reg]
+                }
+            }
+            Opcode::Ldx | Opcode::LdInd => vec![self.src_reg],
+            Opcode::St => vec![self.dst_reg],
+            Opcode::Stx => vec![self.dst_reg, self.src_reg],
+            Opcode::Call | Opcode::Callx => vec![1, 2, 3, 4, 5],
+            Opcode::Exit => vec![0],
+            Opcode::LdAbs | Opcode::Lddw | Opcode::Ja | Opcode::Unknown(_) => Vec::new(),
+        }
+    }
+    
+    /// Registers written by this instruction
+    ///
+    /// Calls clobber the return register r0 and the argument registers r1-r5.
+    pub fn written_registers(&self) -> Vec<u8> {
+        match self.category() {
+            InstructionCategory::Arithmetic | InstructionCategory::Logic => vec![self.dst_reg],
+            _ => match self.opcode {
+                Opcode::Ldx | Opcode::Lddw => vec![self.dst_reg],
+                Opcode::LdAbs | Opcode::LdInd => vec![0],
+                Opcode::Call | Opcode::Callx => vec![0, 1, 2, 3, 4, 5],
+                _ => Vec::new(),
+            },
+        }
+    }
+    
+    /// Check if execution never continues to the next instruction
+    pub fn is_terminator(&self) -> bool {
+        matches!(self.opcode, Opcode::Ja | Opcode::Exit)
+    }
+}
+
+/// Address of the slot `offset + 1` instructions after `address`
+fn slot_offset(address: u64, offset: i64) -> u64 {
+    (address as i64 + (offset + 1) * INSN_SIZE as i64) as u64
 }
 
 /// Decode SBPF instructions from bytecode
@@ -111,20 +229,33 @@ impl InstructionDecoder {
         let opcode_byte = bytes[0];
         let opcode = Opcode::from_u8(opcode_byte);
         
-        let dst_reg = (bytes[1] & 0x0f) as u8;
-        let src_reg = ((bytes[1] >> 4) & 0x0f) as u8;
+        let dst_reg = bytes[1] & 0x0f;
+        let src_reg = (bytes[1] >> 4) & 0x0f;
         
         let off = i16::from_le_bytes([bytes[2], bytes[3]]);
-        let imm = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+        let mut imm = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as i64;
+        let mut size = INSN_SIZE;
+        
+        // lddw spans two slots, the second one holds the upper 32 bits
+        if opcode_byte == LD_DW_IMM {
+            if offset + 2 * INSN_SIZE > bytecode.len() {
+                return None;
+            }
+            let high = &bytecode[offset + 12..offset + 16];
+            let high = u32::from_le_bytes([high[0], high[1], high[2], high[3]]) as u64;
+            imm = ((imm as u64 & 0xffff_ffff) | (high << 32)) as i64;
+            size = 2 * INSN_SIZE;
+        }
         
         Some(Instruction {
             address: offset as u64,
             opcode,
+            code: opcode_byte,
             dst_reg,
             src_reg,
             imm,
             off,
-            size: 8,
+            size,
         })
     }
 }
@@ -156,7 +287,30 @@ mod tests {
         bytes[4..8].copy_from_slice(&42i32.to_le_bytes());
         
         let inst = decoder.decode_at(&bytes, 0).unwrap();
+        assert_eq!(inst.opcode, Opcode::Mov);
         assert_eq!(inst.dst_reg, 1);
         assert_eq!(inst.imm, 42);
     }
+
+    #[test]
+    fn test_decode_lddw_and_jump() {
+        let decoder = InstructionDecoder::new(None);
+        let bytecode = [
+            // lddw r1, 0x1_0000_0002
+            0x18, 0x01, 0, 0, 0x02, 0, 0, 0,
+            0x00, 0x00, 0, 0, 0x01, 0, 0, 0,
+            // jeq r1, 0, +1
+            0x15, 0x01, 0x01, 0x00, 0, 0, 0, 0,
+            // exit
+            0x95, 0, 0, 0, 0, 0, 0, 0,
+            // exit
+            0x95, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        
+        let insts = decoder.decode_all(&bytecode);
+        assert_eq!(insts.len(), 4);
+        assert_eq!(insts[0].imm, 0x1_0000_0002);
+        assert_eq!(insts[1].address, 16);
+        assert_eq!(insts[1].jump_target(), Some(32));
+    }
 }
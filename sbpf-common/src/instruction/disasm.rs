@@ -1,5 +1,6 @@
-use sbpf_common::instruction::Instruction;
-use sbpf_common::binary::sbpf::Opcode;
+use crate::instruction::Instruction;
+use crate::binary::sbpf::Opcode;
+use std::collections::BTreeMap;
 
 /// Disassembled instruction
 #[derive(Debug, Clone)]
@@ -7,7 +8,6 @@ pub struct DisassembledInstruction {
     pub address: u64,
     pub mnemonic: String,
     pub operands: String,
-    #[allow(dead_code)]
     pub comment: Option<String>,
 }
 
@@ -27,10 +27,25 @@ pub fn disassemble(instructions: &[Instruction]) -> Vec<DisassembledInstruction>
     }).collect()
 }
 
-fn opcode_to_mnemonic(opcode: Opcode) -> String {
+/// Attach notes to the instructions at their addresses, after any
+/// existing comment
+pub fn annotate(assembly: &mut [DisassembledInstruction], notes: &BTreeMap<u64, String>) {
+    for line in assembly.iter_mut() {
+        if let Some(note) = notes.get(&line.address) {
+            line.comment = Some(match line.comment.take() {
+                Some(comment) => format!("{}; {}", comment, note),
+                None => note.clone(),
+            });
+        }
+    }
+}
+
+/// Assembly mnemonic for an opcode
+pub fn opcode_to_mnemonic(opcode: Opcode) -> String {
     match opcode {
         Opcode::LdAbs => "ldabs".to_string(),
         Opcode::LdInd => "ldind".to_string(),
+        Opcode::Lddw => "lddw".to_string(),
         Opcode::Ldx => "ldx".to_string(),
         Opcode::St => "st".to_string(),
         Opcode::Stx => "stx".to_string(),
@@ -42,10 +57,13 @@ fn opcode_to_mnemonic(opcode: Opcode) -> String {
         Opcode::And => "and".to_string(),
         Opcode::Lsh => "lsh".to_string(),
         Opcode::Rsh => "rsh".to_string(),
+        Opcode::Neg => "neg".to_string(),
         Opcode::Mod => "mod".to_string(),
         Opcode::Xor => "xor".to_string(),
         Opcode::Mov => "mov".to_string(),
         Opcode::Arsh => "arsh".to_string(),
+        Opcode::Le => "le".to_string(),
+        Opcode::Be => "be".to_string(),
         Opcode::Ja => "ja".to_string(),
         Opcode::Jeq => "jeq".to_string(),
         Opcode::Jgt => "jgt".to_string(),
@@ -59,6 +77,7 @@ fn opcode_to_mnemonic(opcode: Opcode) -> String {
         Opcode::Jslt => "jslt".to_string(),
         Opcode::Jsle => "jsle".to_string(),
         Opcode::Call => "call".to_string(),
+        Opcode::Callx => "callx".to_string(),
         Opcode::Exit => "exit".to_string(),
         Opcode::Unknown(b) => format!("unknown_{:02x}", b),
     }
@@ -75,8 +94,17 @@ fn format_operands(inst: &Instruction) -> String {
         }
         Opcode::Ja | Opcode::Jeq | Opcode::Jgt | Opcode::Jge | Opcode::Jlt | Opcode::Jle
         | Opcode::Jset | Opcode::Jne | Opcode::Jsgt | Opcode::Jsge | Opcode::Jslt | Opcode::Jsle => {
-            let target = (inst.address as i64 + inst.imm + 1) as u64;
-            format!("r{}, r{}, 0x{:x}", inst.dst_reg, inst.src_reg, target)
+            let target = inst.jump_target().unwrap_or_default();
+            match inst.opcode {
+                Opcode::Ja => format!("0x{:x}", target),
+                _ if inst.uses_src_reg() => {
+                    format!("r{}, r{}, 0x{:x}", inst.dst_reg, inst.src_reg, target)
+                }
+                _ => format!("r{}, {}, 0x{:x}", inst.dst_reg, inst.imm, target),
+            }
+        }
+        Opcode::Lddw => {
+            format!("r{}, 0x{:x}", inst.dst_reg, inst.imm)
         }
         Opcode::LdAbs | Opcode::LdInd => {
             format!("r{}, [{}]", inst.dst_reg, inst.imm)
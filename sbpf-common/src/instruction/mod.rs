@@ -1,4 +1,4 @@
 pub mod decoder;
+pub mod disasm;
 
 pub use decoder::*;
-
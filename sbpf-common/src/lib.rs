@@ -1,2 +1,12 @@
 pub mod binary;
 pub mod instruction;
+pub mod cfg;
+pub mod dataflow;
+pub mod dispatch;
+pub mod pda;
+pub mod pubkey;
+pub mod input;
+pub mod syscalls;
+pub mod syscall_args;
+pub mod hash;
+pub mod signatures;
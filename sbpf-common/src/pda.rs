@@ -0,0 +1,316 @@
+// Recovery of program derived address seeds
+//
+// Both PDA syscalls take the seeds as an array of byte slices:
+//
+//   sol_create_program_address(seeds: *const [*const u8, u64], seeds_len,
+//                              program_id: *const Pubkey, address: *mut Pubkey)
+//   sol_try_find_program_address(seeds, seeds_len, program_id, address,
+//                                bump: *mut u8)
+//
+// The array is usually built on the stack right before the call, so each
+// slice's pointer and length can be read back from the tracked stack slots.
+
+use crate::binary::SbpfBinary;
+use crate::cfg::ControlFlowGraph;
+use crate::dataflow::{Dataflow, Location, State, Value};
+use crate::dispatch::{Dispatch, INSTRUCTION_DATA_ARG};
+use crate::input::AccountField;
+use crate::instruction::Instruction;
+use crate::pubkey::{Pubkey, PUBKEY_SIZE};
+use crate::syscalls::syscall_name;
+use std::fmt;
+
+/// Size of a `&[u8]` seed in the seeds array
+const SEED_SIZE: i64 = 16;
+
+/// Largest number of seeds accepted by the runtime
+pub const MAX_SEEDS: u64 = 16;
+
+/// Argument register holding the program id in `process_instruction`
+const PROGRAM_ID_ARG: u8 = 1;
+
+/// Symbolic value of a seed or program id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Seed {
+    /// Constant bytes from read-only data
+    Bytes(Vec<u8>),
+    /// Bytes of a field of an account record
+    Account {
+        index: u16,
+        field: AccountField,
+        /// Offset within the field
+        offset: i64,
+        len: Option<u64>,
+    },
+    /// Slice of the instruction data
+    InstructionData { offset: i64, len: Option<u64> },
+    /// Buffer passed in an argument register
+    Arg { reg: u8, offset: i64, len: Option<u64> },
+    /// The executing program's id
+    ProgramId,
+    /// Trailing single-byte seed of `create_program_address`
+    Bump,
+    Unknown,
+}
+
+/// Render `[offset..offset+len]`, or `[offset..]` with an unknown length
+fn slice(offset: i64, len: Option<u64>) -> String {
+    match len {
+        Some(len) => format!("[{}..{}]", offset, offset + len as i64),
+        None => format!("[{}..]", offset),
+    }
+}
+
+/// Name of the argument in register `reg`
+fn arg_name(reg: u8) -> String {
+    format!("arg{}", reg)
+}
+
+impl Seed {
+    /// Render with the function argument in register `reg` named `arg(reg)`
+    pub fn render(&self, arg: &dyn Fn(u8) -> String) -> String {
+        match self {
+            Seed::Bytes(bytes) if !bytes.is_empty() && bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') => {
+                format!("{:?}", String::from_utf8_lossy(bytes))
+            }
+            Seed::Bytes(bytes) if bytes.len() == PUBKEY_SIZE => {
+                Pubkey::from_slice(bytes).expect("length checked").to_string()
+            }
+            Seed::Bytes(bytes) => {
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("0x{}", hex)
+            }
+            Seed::Account { index, field, offset, len } => {
+                let whole = match field {
+                    AccountField::Key | AccountField::Owner => *offset == 0 && *len == Some(PUBKEY_SIZE as u64),
+                    _ => false,
+                };
+                let range = if whole { String::new() } else { slice(*offset, *len) };
+                format!("accounts[{}].{}{}", index, field.name(), range)
+            }
+            Seed::InstructionData { offset, len } => format!("instruction_data{}", slice(*offset, *len)),
+            Seed::Arg { reg, offset, len } => format!("{}{}", arg(*reg), slice(*offset, *len)),
+            Seed::ProgramId => "program_id".to_string(),
+            Seed::Bump => "bump".to_string(),
+            Seed::Unknown => "?".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(&arg_name))
+    }
+}
+
+/// A call to one of the PDA syscalls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdaDerivation {
+    /// Address of the syscall
+    pub address: u64,
+    /// Address of the containing function
+    pub function: u64,
+    pub function_name: String,
+    pub syscall: &'static str,
+    /// Seeds in order, if the seeds array could be located
+    pub seeds: Option<Vec<Seed>>,
+    pub program: Seed,
+}
+
+impl PdaDerivation {
+    /// Whether the canonical bump is searched for
+    pub fn finds_bump(&self) -> bool {
+        self.syscall == "sol_try_find_program_address"
+    }
+
+    /// Render with the function argument in register `reg` named `arg(reg)`
+    pub fn render(&self, arg: &dyn Fn(u8) -> String) -> String {
+        let name = if self.finds_bump() { "find_program_address" } else { "create_program_address" };
+        let seeds = match &self.seeds {
+            Some(seeds) => seeds.iter().map(|seed| seed.render(arg)).collect::<Vec<_>>().join(", "),
+            None => "?".to_string(),
+        };
+        format!("{}([{}], {})", name, seeds, self.program.render(arg))
+    }
+}
+
+impl fmt::Display for PdaDerivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(&arg_name))
+    }
+}
+
+/// How pointers into the instruction data and the program id look in a
+/// function
+struct Context<'a> {
+    binary: &'a SbpfBinary,
+    dispatch: Option<&'a Dispatch>,
+    function: u64,
+}
+
+impl Context<'_> {
+    /// Classify the buffer at `ptr` of `len` bytes
+    fn seed(&self, ptr: Value, len: Option<u64>) -> Seed {
+        let loc = match ptr {
+            Value::Const(address) => Location::Absolute(address),
+            Value::Pointer(loc) => loc,
+            _ => return Seed::Unknown,
+        };
+        match loc {
+            Location::Absolute(address) => {
+                let bytes = len.and_then(|len| self.binary.read_rodata(address, len as usize));
+                bytes.map_or(Seed::Unknown, |bytes| Seed::Bytes(bytes.to_vec()))
+            }
+            Location::Arg(reg, offset) => match self.dispatch {
+                // Arguments of `process_instruction`
+                Some(dispatch) if dispatch.function == self.function => {
+                    match (dispatch.tag_location, reg, offset) {
+                        (Location::Arg(r, _), _, _) if r == reg => Seed::InstructionData { offset, len },
+                        (Location::Arg(INSTRUCTION_DATA_ARG, _), PROGRAM_ID_ARG, 0) => Seed::ProgramId,
+                        _ => Seed::Arg { reg, offset, len },
+                    }
+                }
+                _ => Seed::Arg { reg, offset, len },
+            },
+            Location::Account(index, off) => {
+                // Raw entrypoints find the instruction data after the last record
+                if let Some(Location::Account(data_index, data_off)) = self.dispatch.map(|d| d.tag_location) {
+                    if index == data_index && off >= data_off {
+                        return Seed::InstructionData { offset: off - data_off, len };
+                    }
+                }
+                match AccountField::from_record_offset(off) {
+                    Some(field) => Seed::Account {
+                        index,
+                        field,
+                        offset: off - field.record_offset().unwrap_or(off),
+                        len,
+                    },
+                    None => Seed::Unknown,
+                }
+            }
+            _ => Seed::Unknown,
+        }
+    }
+
+    /// Read the seeds array at `array` with `count` entries
+    fn seeds(&self, state: &State, array: Value, count: Value, creates: bool) -> Option<Vec<Seed>> {
+        let (Some(Location::Stack(base)), Value::Const(count)) = (array.location(), count) else {
+            return None;
+        };
+        if count > MAX_SEEDS {
+            return None;
+        }
+        let seeds = (0..count as i64)
+            .map(|i| {
+                let ptr = state.stack_slot(base + i * SEED_SIZE);
+                let len = match state.stack_slot(base + i * SEED_SIZE + 8) {
+                    Value::Const(len) => Some(len),
+                    _ => None,
+                };
+                match self.seed(ptr, len) {
+                    // The bump is a byte on the stack
+                    Seed::Unknown if creates && i == count as i64 - 1 && len == Some(1) => Seed::Bump,
+                    seed => seed,
+                }
+            })
+            .collect();
+        Some(seeds)
+    }
+}
+
+/// Reconstruct the seeds of every PDA derivation. The dispatch, if known,
+/// identifies pointers to the instruction data and the program id.
+pub fn recover(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    dataflow: &Dataflow,
+    binary: &SbpfBinary,
+    dispatch: Option<&Dispatch>,
+) -> Vec<PdaDerivation> {
+    let mut derivations = Vec::new();
+    for function in &dataflow.functions {
+        let cfg_function = &cfg.functions[function.index];
+        if cfg_function.library {
+            continue;
+        }
+        let ctx = Context {
+            binary,
+            dispatch,
+            function: function.address,
+        };
+        function.visit(cfg, instructions, |_, inst, state| {
+            let syscall = match inst.syscall_number().and_then(syscall_name) {
+                Some(name @ ("sol_create_program_address" | "sol_try_find_program_address")) => name,
+                _ => return,
+            };
+            let creates = syscall == "sol_create_program_address";
+            let program = match ctx.seed(state.reg(3), Some(32)) {
+                Seed::Bytes(bytes) if bytes.len() == 32 => Seed::Bytes(bytes),
+                Seed::Bytes(_) => Seed::Unknown,
+                seed => seed,
+            };
+            derivations.push(PdaDerivation {
+                address: inst.address,
+                function: cfg_function.address,
+                function_name: cfg_function.name.clone(),
+                syscall,
+                seeds: ctx.seeds(state, state.reg(1), state.reg(2), creates),
+                program,
+            });
+        });
+    }
+    derivations.sort_by_key(|d| d.address);
+    derivations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::binary::MetadataSection;
+    use crate::instruction::InstructionDecoder;
+    use crate::syscalls::syscall_hash;
+
+    #[test]
+    fn test_recover_seeds() {
+        let find = syscall_hash("sol_try_find_program_address") as u32 as i32;
+        let program: Vec<u8> = [
+            insn(0x18, 2, 0, 0, 0x100),  // 0x00: lddw r2, 0x100 ("vault")
+            insn(0x00, 0, 0, 0, 0),
+            insn(0x7b, 10, 2, -32, 0),   // 0x10: stxdw [r10-32], r2
+            insn(0x7a, 10, 0, -24, 5),   // 0x18: stdw [r10-24], 5
+            insn(0xbf, 3, 1, 0, 0),      // 0x20: mov r3, r1
+            insn(0x07, 3, 0, 0, 16),     // 0x28: add r3, 16       accounts[0].key
+            insn(0x7b, 10, 3, -16, 0),   // 0x30: stxdw [r10-16], r3
+            insn(0x7a, 10, 0, -8, 32),   // 0x38: stdw [r10-8], 32
+            insn(0xbf, 1, 10, 0, 0),     // 0x40: mov r1, r10
+            insn(0x07, 1, 0, 0, -32),    // 0x48: add r1, -32
+            insn(0xb7, 2, 0, 0, 2),      // 0x50: mov r2, 2
+            insn(0xbf, 3, 6, 0, 0),      // 0x58: mov r3, r6
+            insn(0x85, 0, 0, 0, find),   // 0x60: call sol_try_find_program_address
+            insn(0x95, 0, 0, 0, 0),      // 0x68: exit
+        ]
+        .concat();
+        let binary = SbpfBinary {
+            bytecode: program.clone(),
+            entry_point: 0,
+            text_address: 0,
+            text_offset: 0,
+            version: None,
+            metadata: vec![MetadataSection {
+                name: ".rodata".to_string(),
+                address: 0x100,
+                data: b"vault".to_vec(),
+            }],
+            symbols: Vec::new(),
+        };
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let derivations = recover(&cfg, &instructions, &dataflow, &binary, None);
+
+        assert_eq!(derivations.len(), 1);
+        assert_eq!(derivations[0].to_string(), r#"find_program_address(["vault", accounts[0].key], ?)"#);
+    }
+}
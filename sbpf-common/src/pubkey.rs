@@ -0,0 +1,249 @@
+// Solana public keys: base58 rendering and well-known addresses
+
+use crate::binary::sbpf::Opcode;
+use crate::binary::SbpfBinary;
+use crate::cfg::ControlFlowGraph;
+use crate::dataflow::{Dataflow, Location, Value};
+use crate::instruction::Instruction;
+use crate::syscalls::syscall_name;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Size of a public key in bytes
+pub const PUBKEY_SIZE: usize = 32;
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Well-known programs, sysvars and mints as (address, name)
+pub const KNOWN_ADDRESSES: &[(&str, &str)] = &[
+    ("11111111111111111111111111111111", "System Program"),
+    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "Token Program"),
+    ("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb", "Token-2022 Program"),
+    ("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL", "Associated Token Account Program"),
+    ("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "Memo Program"),
+    ("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo", "Memo Program v1"),
+    ("BPFLoader1111111111111111111111111111111111", "BPF Loader (deprecated)"),
+    ("BPFLoader2111111111111111111111111111111111", "BPF Loader"),
+    ("BPFLoaderUpgradeab1e11111111111111111111111", "BPF Upgradeable Loader"),
+    ("LoaderV411111111111111111111111111111111111", "Loader v4"),
+    ("NativeLoader1111111111111111111111111111111", "Native Loader"),
+    ("ComputeBudget111111111111111111111111111111", "Compute Budget Program"),
+    ("AddressLookupTab1e1111111111111111111111111", "Address Lookup Table Program"),
+    ("Config1111111111111111111111111111111111111", "Config Program"),
+    ("Stake11111111111111111111111111111111111111", "Stake Program"),
+    ("StakeConfig11111111111111111111111111111111", "Stake Config"),
+    ("Vote111111111111111111111111111111111111111", "Vote Program"),
+    ("Ed25519SigVerify111111111111111111111111111", "Ed25519 Program"),
+    ("KeccakSecp256k11111111111111111111111111111", "Secp256k1 Program"),
+    ("Sysvar1111111111111111111111111111111111111", "Sysvar Owner"),
+    ("SysvarC1ock11111111111111111111111111111111", "Clock Sysvar"),
+    ("SysvarRent111111111111111111111111111111111", "Rent Sysvar"),
+    ("SysvarEpochSchedu1e111111111111111111111111", "Epoch Schedule Sysvar"),
+    ("SysvarFees111111111111111111111111111111111", "Fees Sysvar"),
+    ("SysvarRecentB1ockHashes11111111111111111111", "Recent Blockhashes Sysvar"),
+    ("SysvarS1otHashes111111111111111111111111111", "Slot Hashes Sysvar"),
+    ("SysvarS1otHistory11111111111111111111111111", "Slot History Sysvar"),
+    ("SysvarStakeHistory1111111111111111111111111", "Stake History Sysvar"),
+    ("Sysvar1nstructions1111111111111111111111111", "Instructions Sysvar"),
+    ("SysvarEpochRewards1111111111111111111111111", "Epoch Rewards Sysvar"),
+    ("SysvarLastRestartS1ot1111111111111111111111", "Last Restart Slot Sysvar"),
+    ("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s", "Metaplex Token Metadata"),
+    ("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY", "Metaplex Bubblegum"),
+    ("CndyV3LdqHUfDLmE5naZjVN8rBZz4tqhdefbAnjHG3JR", "Metaplex Candy Machine v3"),
+    ("auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg", "Metaplex Token Auth Rules"),
+    ("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK", "SPL Account Compression"),
+    ("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV", "SPL Noop"),
+    ("namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX", "SPL Name Service"),
+    ("So11111111111111111111111111111111111111112", "Wrapped SOL Mint"),
+    ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "USDC Mint"),
+];
+
+/// Encode bytes in Bitcoin-alphabet base58, as Solana renders addresses
+pub fn to_base58(bytes: &[u8]) -> String {
+    // Little-endian base-58 digits of the big-endian number
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    // Each leading zero byte is written as a leading '1'
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat_n('1', zeros)
+        .chain(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize] as char))
+        .collect()
+}
+
+/// Decode a base58 string, or `None` if it contains other characters
+pub fn from_base58(text: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in text.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let ones = text.bytes().take_while(|&c| c == b'1').count();
+    bytes.extend(std::iter::repeat_n(0, ones));
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// Decoded known addresses
+fn known_addresses() -> &'static HashMap<[u8; PUBKEY_SIZE], &'static str> {
+    static KNOWN: OnceLock<HashMap<[u8; PUBKEY_SIZE], &'static str>> = OnceLock::new();
+    KNOWN.get_or_init(|| {
+        KNOWN_ADDRESSES
+            .iter()
+            .filter_map(|&(address, name)| {
+                let bytes: [u8; PUBKEY_SIZE] = from_base58(address)?.try_into().ok()?;
+                Some((bytes, name))
+            })
+            .collect()
+    })
+}
+
+/// A 32-byte public key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pubkey(pub [u8; PUBKEY_SIZE]);
+
+impl Pubkey {
+    /// Key from a slice of exactly 32 bytes
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(Pubkey)
+    }
+
+    /// Key from its base58 address
+    pub fn from_base58(address: &str) -> Option<Self> {
+        Self::from_slice(&from_base58(address)?)
+    }
+
+    pub fn to_base58(&self) -> String {
+        to_base58(&self.0)
+    }
+
+    /// Friendly name of a well-known address
+    pub fn name(&self) -> Option<&'static str> {
+        known_addresses().get(&self.0).copied()
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pubkey(\"{}\")", self.to_base58())?;
+        if let Some(name) = self.name() {
+            write!(f, " /* {} */", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pubkey constant in read-only data
+#[derive(Debug, Clone)]
+pub struct PubkeyConstant {
+    /// Address of the key in read-only data
+    pub address: u64,
+    pub pubkey: Pubkey,
+    /// Instructions loading the key's address
+    pub references: Vec<u64>,
+}
+
+/// 32-byte read-only constants that code uses as public keys: well-known
+/// addresses, keys read as four 8-byte words and keys passed to a 32-byte
+/// `sol_memcmp_`
+pub fn recover(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    dataflow: &Dataflow,
+    binary: &SbpfBinary,
+) -> Vec<PubkeyConstant> {
+    let read = |address: u64| binary.read_rodata(address, PUBKEY_SIZE).and_then(Pubkey::from_slice);
+
+    // Addresses used as keys, normalised to how they appear in lddw
+    let mut used: BTreeSet<u64> = BTreeSet::new();
+    let mut word_loads: BTreeSet<u64> = BTreeSet::new();
+    for function in &dataflow.functions {
+        function.visit(cfg, instructions, |_, inst, state| match inst.opcode {
+            Opcode::Ldx if inst.mem_size() == Some(8) => {
+                if let Some(Location::Absolute(address)) = state.memory_location(inst) {
+                    word_loads.insert(address);
+                }
+            }
+            Opcode::Call
+                if inst.syscall_number().and_then(syscall_name) == Some("sol_memcmp_")
+                    && state.reg(3) == Value::Const(PUBKEY_SIZE as u64) =>
+            {
+                for reg in [1, 2] {
+                    if let Value::Const(address) = state.reg(reg) {
+                        used.insert(address);
+                    }
+                }
+            }
+            _ => {}
+        });
+    }
+    used.extend(
+        word_loads
+            .iter()
+            .filter(|&&a| (1..4).all(|i| a.checked_add(i * 8).is_some_and(|word| word_loads.contains(&word))))
+            .copied(),
+    );
+
+    let mut constants: BTreeMap<u64, PubkeyConstant> = BTreeMap::new();
+    for inst in instructions.iter().filter(|i| i.opcode == Opcode::Lddw) {
+        let address = inst.imm as u64;
+        let Some(pubkey) = read(address) else {
+            continue;
+        };
+        if pubkey.name().is_none() && !used.contains(&address) {
+            continue;
+        }
+        constants
+            .entry(address)
+            .or_insert(PubkeyConstant {
+                address,
+                pubkey,
+                references: Vec::new(),
+            })
+            .references
+            .push(inst.address);
+    }
+    constants.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base58_roundtrip() {
+        for &(address, _) in KNOWN_ADDRESSES {
+            let bytes = from_base58(address).unwrap();
+            assert_eq!(bytes.len(), PUBKEY_SIZE, "{}", address);
+            assert_eq!(to_base58(&bytes), address);
+        }
+        assert_eq!(from_base58("0OIl"), None);
+    }
+
+    #[test]
+    fn test_known_name() {
+        let token = Pubkey::from_base58("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap();
+        assert_eq!(token.0[..4], [0x06, 0xdd, 0xf6, 0xe1]);
+        assert_eq!(token.name(), Some("Token Program"));
+        assert_eq!(Pubkey([0; PUBKEY_SIZE]).to_string(), "Pubkey(\"11111111111111111111111111111111\") /* System Program */");
+    }
+}
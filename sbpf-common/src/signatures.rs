@@ -0,0 +1,358 @@
+// Signatures for identifying statically linked library functions
+//
+// A signature is a hash of a function's instructions with the fields that
+// change between links masked out: the immediates of `lddw` (rodata and
+// function addresses patched by relocations) and of internal calls
+// (relative to wherever the callee ended up). Jump offsets are relative to
+// the instruction and survive relinking, so they are kept.
+//
+// Signature files are plain text, one function per line:
+//
+//     <hash> <instruction count> <name>
+//
+// Blank lines and lines starting with `#` are ignored.
+
+use crate::binary::sbpf::Opcode;
+use crate::cfg::{ControlFlowGraph, Function};
+use crate::hash::StableHasher;
+use crate::instruction::Instruction;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::Hasher;
+use std::path::Path;
+
+/// Functions shorter than this are too generic to identify reliably
+pub const MIN_SIGNATURE_INSTRUCTIONS: usize = 8;
+
+/// Crate prefixes of code linked into every Solana program
+const LIBRARY_CRATES: &[&str] = &[
+    "core::",
+    "alloc::",
+    "std::",
+    "compiler_builtins::",
+    "solana_program::",
+    "solana_program_entrypoint::",
+    "solana_program_error::",
+    "solana_pubkey::",
+    "solana_instruction::",
+    "solana_account_info::",
+    "solana_msg::",
+    "solana_cpi::",
+    "borsh::",
+    "hashbrown::",
+];
+
+/// Unmangled symbols of compiler builtins and the allocator and memory shims
+/// the Solana toolchain links in
+const LIBRARY_SYMBOLS: &[&str] = &[
+    // Memory intrinsics
+    "memcpy",
+    "memmove",
+    "memset",
+    "memcmp",
+    "bcmp",
+    "abort",
+    // Global allocator shims
+    "__rust_alloc",
+    "__rust_dealloc",
+    "__rust_realloc",
+    "__rust_alloc_zeroed",
+    "__rust_alloc_error_handler",
+    "__rg_alloc",
+    "__rg_dealloc",
+    "__rg_realloc",
+    "__rg_alloc_zeroed",
+    "__rdl_alloc",
+    "__rdl_dealloc",
+    "__rdl_realloc",
+    "__rdl_alloc_zeroed",
+    // Integer arithmetic
+    "__ashldi3",
+    "__ashrdi3",
+    "__lshrdi3",
+    "__ashlti3",
+    "__ashrti3",
+    "__lshrti3",
+    "__muldi3",
+    "__multi3",
+    "__mulodi4",
+    "__muloti4",
+    "__divdi3",
+    "__udivdi3",
+    "__moddi3",
+    "__umoddi3",
+    "__udivmoddi4",
+    "__divti3",
+    "__udivti3",
+    "__modti3",
+    "__umodti3",
+    "__udivmodti4",
+    "__clzdi2",
+    "__ctzdi2",
+    "__popcountdi2",
+    "__bswapdi2",
+    // Floating point arithmetic, comparison and conversion
+    "__addsf3",
+    "__adddf3",
+    "__subsf3",
+    "__subdf3",
+    "__mulsf3",
+    "__muldf3",
+    "__divsf3",
+    "__divdf3",
+    "__powisf2",
+    "__powidf2",
+    "__eqsf2",
+    "__eqdf2",
+    "__nesf2",
+    "__nedf2",
+    "__ltsf2",
+    "__ltdf2",
+    "__lesf2",
+    "__ledf2",
+    "__gtsf2",
+    "__gtdf2",
+    "__gesf2",
+    "__gedf2",
+    "__unordsf2",
+    "__unorddf2",
+    "__extendsfdf2",
+    "__truncdfsf2",
+    "__fixsfsi",
+    "__fixsfdi",
+    "__fixdfsi",
+    "__fixdfdi",
+    "__fixunssfsi",
+    "__fixunssfdi",
+    "__fixunsdfsi",
+    "__fixunsdfdi",
+    "__floatsisf",
+    "__floatdisf",
+    "__floatsidf",
+    "__floatdidf",
+    "__floatunsisf",
+    "__floatundisf",
+    "__floatunsidf",
+    "__floatundidf",
+];
+
+/// Check if a demangled symbol name belongs to a library crate
+pub fn is_library_name(name: &str) -> bool {
+    // Trait impls demangle as `<Type as Trait>::method`
+    let name = name.trim_start_matches('<');
+    LIBRARY_CRATES.iter().any(|prefix| name.starts_with(prefix)) || LIBRARY_SYMBOLS.contains(&name)
+}
+
+/// Key of a function body: normalized hash and instruction count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SignatureKey {
+    pub hash: u64,
+    pub instructions: usize,
+}
+
+impl SignatureKey {
+    /// Compute the normalized key of a function
+    pub fn of(cfg: &ControlFlowGraph, function: &Function, instructions: &[Instruction]) -> Self {
+        let mut hasher = StableHasher::new();
+        let mut count = 0;
+        for inst in cfg.function_instructions(function, instructions) {
+            let relocated = matches!(inst.opcode, Opcode::Lddw) || inst.call_target().is_some();
+            hasher.write_u8(inst.code);
+            hasher.write_u8(inst.dst_reg);
+            hasher.write_u8(inst.src_reg);
+            hasher.write_i16(inst.off);
+            hasher.write_i64(if relocated { 0 } else { inst.imm });
+            count += 1;
+        }
+        SignatureKey {
+            hash: hasher.finish(),
+            instructions: count,
+        }
+    }
+}
+
+/// Database of known library function signatures
+#[derive(Debug, Clone, Default)]
+pub struct SignatureDb {
+    /// Key -> name, or `None` when different functions share the key
+    entries: BTreeMap<SignatureKey, Option<String>>,
+}
+
+impl SignatureDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a signature file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read signatures: {:?}", path.as_ref()))?;
+        Self::parse(&text)
+    }
+
+    /// Parse signatures in the text format
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut db = Self::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, ' ');
+            let (Some(hash), Some(count), Some(name)) = (parts.next(), parts.next(), parts.next()) else {
+                bail!("Malformed signature on line {}", line_no + 1);
+            };
+            let key = SignatureKey {
+                hash: u64::from_str_radix(hash, 16)
+                    .with_context(|| format!("Invalid hash on line {}", line_no + 1))?,
+                instructions: count
+                    .parse()
+                    .with_context(|| format!("Invalid instruction count on line {}", line_no + 1))?,
+            };
+            db.insert(key, name.trim());
+        }
+        Ok(db)
+    }
+
+    /// Add a signature, marking the key ambiguous if it already names a
+    /// different function
+    pub fn insert(&mut self, key: SignatureKey, name: &str) {
+        self.entries
+            .entry(key)
+            .and_modify(|existing| {
+                if existing.as_deref() != Some(name) {
+                    *existing = None;
+                }
+            })
+            .or_insert_with(|| Some(name.to_string()));
+    }
+
+    /// Add every signature of `other`
+    pub fn merge(&mut self, other: &SignatureDb) {
+        for (&key, name) in &other.entries {
+            match name {
+                Some(name) => self.insert(key, name),
+                None => {
+                    self.entries.insert(key, None);
+                }
+            }
+        }
+    }
+
+    /// Number of signatures, including ambiguous ones
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Name of the library function with this key, if it is unambiguous
+    pub fn lookup(&self, key: &SignatureKey) -> Option<&str> {
+        self.entries.get(key)?.as_deref()
+    }
+
+    /// Collect signatures of the symbolized library functions in a CFG
+    pub fn generate(cfg: &ControlFlowGraph, instructions: &[Instruction]) -> Self {
+        let mut db = Self::new();
+        for function in cfg.functions.iter().filter(|f| f.library) {
+            let key = SignatureKey::of(cfg, function, instructions);
+            if key.instructions >= MIN_SIGNATURE_INSTRUCTIONS {
+                db.insert(key, &function.name);
+            }
+        }
+        db
+    }
+
+    /// Name and mark as library every unnamed function matching a signature,
+    /// returning the number of functions identified
+    pub fn label(&self, cfg: &mut ControlFlowGraph, instructions: &[Instruction]) -> usize {
+        let matches: Vec<(usize, String)> = cfg
+            .functions
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| !f.library)
+            .filter_map(|(idx, f)| {
+                let key = SignatureKey::of(cfg, f, instructions);
+                if key.instructions < MIN_SIGNATURE_INSTRUCTIONS {
+                    return None;
+                }
+                self.lookup(&key).map(|name| (idx, name.to_string()))
+            })
+            .collect();
+        for (idx, name) in &matches {
+            let function = &mut cfg.functions[*idx];
+            function.name = name.clone();
+            function.library = true;
+        }
+        matches.len()
+    }
+
+    /// Serialize in the text format, skipping ambiguous keys
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# sbpf signatures: <hash> <instruction count> <name>\n");
+        for (key, name) in &self.entries {
+            if let Some(name) = name {
+                text.push_str(&format!("{:016x} {} {}\n", key.hash, key.instructions, name));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::binary::Symbol;
+    use crate::instruction::InstructionDecoder;
+
+    /// Program calling a library function loaded at `padding` slots later
+    fn program(padding: usize, rodata: i32) -> Vec<u8> {
+        let mut slots = vec![insn(0x85, 0, 1, 0, padding as i32 + 1), insn(0x95, 0, 0, 0, 0)];
+        slots.extend((0..padding).map(|_| insn(0xb7, 0, 0, 0, 0)));
+        slots.extend([
+            insn(0x18, 1, 0, 0, rodata),
+            insn(0, 0, 0, 0, 0),
+        ]);
+        slots.extend((0..MIN_SIGNATURE_INSTRUCTIONS).map(|i| insn(0x07, 1, 0, 0, i as i32)));
+        slots.push(insn(0x95, 0, 0, 0, 0));
+        slots.concat()
+    }
+
+    #[test]
+    fn test_generate_and_label() {
+        let unstripped = program(0, 0x1000);
+        let instructions = InstructionDecoder::new(None).decode_all(&unstripped);
+        let symbols = [Symbol {
+            name: "core::fmt::write".to_string(),
+            address: 0x10,
+            size: 0,
+        }];
+        let cfg = ControlFlowGraph::build_with_symbols(&instructions, 0, &symbols);
+        let db = SignatureDb::parse(&SignatureDb::generate(&cfg, &instructions).to_text()).unwrap();
+        assert_eq!(db.len(), 1);
+
+        // Relinked at a different address with different rodata
+        let stripped = program(3, 0x2000);
+        let instructions = InstructionDecoder::new(None).decode_all(&stripped);
+        let mut cfg = ControlFlowGraph::build(&instructions, 0);
+        assert_eq!(db.label(&mut cfg, &instructions), 1);
+        let function = cfg.function_at(0x28).unwrap();
+        assert_eq!(function.name, "core::fmt::write");
+        assert!(function.library);
+        assert!(!cfg.function_at(0).unwrap().library);
+    }
+
+    #[test]
+    fn test_library_names() {
+        assert!(is_library_name("<alloc::vec::Vec<T> as core::clone::Clone>::clone"));
+        assert!(is_library_name("__udivti3"));
+        assert!(is_library_name("memcpy"));
+        // Program code may use the same prefixes
+        assert!(!is_library_name("__process_instruction"));
+        assert!(!is_library_name("sol_transfer_tokens"));
+    }
+}
@@ -0,0 +1,297 @@
+// Recovery of syscall arguments at call sites
+//
+// The dataflow state before a syscall gives the abstract value of r1-r5;
+// the syscall's signature says how to read them, e.g. `sol_log_` takes a
+// pointer and length that usually point at a string in read-only data.
+
+use crate::binary::SbpfBinary;
+use crate::cfg::ControlFlowGraph;
+use crate::dataflow::{Dataflow, Location, State, Value};
+use crate::input::AccountField;
+use crate::instruction::Instruction;
+use crate::pubkey::{Pubkey, PUBKEY_SIZE};
+use crate::syscalls::{syscall_name, syscall_signature, ArgKind, SyscallSignature};
+use std::fmt;
+
+/// Size of a `&[u8]` element in a slice array
+const SLICE_SIZE: i64 = 16;
+
+/// Longest slice array that is expanded
+const MAX_SLICES: u64 = 16;
+
+/// Largest constant printed in decimal
+const MAX_DECIMAL: u64 = 0xffff;
+
+/// Recovered value of a syscall argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgValue {
+    Const(u64),
+    /// UTF-8 text from read-only data
+    Str(String),
+    /// Other bytes from read-only data
+    Bytes(Vec<u8>),
+    Pubkey(Pubkey),
+    /// Buffer at a tracked location
+    Pointer(Location),
+    /// Value loaded from a tracked location
+    Loaded(Location),
+    Slices(Vec<ArgValue>),
+    /// Unknown; shown as the register holding it
+    Register(u8),
+}
+
+impl ArgValue {
+    /// Whether the value is known without reference to a register or memory
+    pub fn is_constant(&self) -> bool {
+        match self {
+            ArgValue::Const(_) | ArgValue::Str(_) | ArgValue::Bytes(_) | ArgValue::Pubkey(_) => true,
+            ArgValue::Slices(slices) => slices.iter().all(ArgValue::is_constant),
+            _ => false,
+        }
+    }
+}
+
+/// Name of the memory at `loc`, e.g. `accounts[1].lamports` or `local_0x20`
+pub fn place(loc: Location) -> String {
+    match loc {
+        Location::Stack(off) if off < 0 => format!("local_0x{:x}", -off),
+        Location::Account(index, off) => match AccountField::from_record_offset(off) {
+            Some(field) => {
+                let within = off - field.record_offset().unwrap_or(off);
+                if within == 0 {
+                    format!("accounts[{}].{}", index, field.name())
+                } else {
+                    format!("accounts[{}].{}[{}..]", index, field.name(), within)
+                }
+            }
+            None => loc.to_string(),
+        },
+        _ => match loc.account_field() {
+            Some((index, field)) => format!("accounts[{}].{}", index, field.name()),
+            None => loc.to_string(),
+        },
+    }
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgValue::Const(c) if *c <= MAX_DECIMAL => write!(f, "{}", c),
+            ArgValue::Const(c) => write!(f, "0x{:x}", c),
+            ArgValue::Str(text) => write!(f, "{:?}", text),
+            ArgValue::Bytes(bytes) => {
+                write!(f, "0x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            ArgValue::Pubkey(pubkey) => write!(f, "&{}", pubkey),
+            // Incoming arguments are values rather than places
+            ArgValue::Pointer(Location::Arg(reg, 0)) => write!(f, "arg{}", reg),
+            ArgValue::Pointer(Location::Arg(reg, off)) => write!(f, "arg{} + {}", reg, off),
+            ArgValue::Loaded(Location::Arg(reg, off)) => write!(f, "*(arg{} + {})", reg, off),
+            ArgValue::Pointer(loc) => write!(f, "&{}", place(*loc)),
+            ArgValue::Loaded(loc) => write!(f, "{}", place(*loc)),
+            ArgValue::Slices(slices) => {
+                write!(f, "[")?;
+                for (i, slice) in slices.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", slice)?;
+                }
+                write!(f, "]")
+            }
+            ArgValue::Register(reg) => write!(f, "r{}", reg),
+        }
+    }
+}
+
+/// A syscall with its recovered arguments
+#[derive(Debug, Clone)]
+pub struct SyscallCall {
+    /// Address of the call instruction
+    pub address: u64,
+    /// Address of the containing function
+    pub function: u64,
+    pub function_name: String,
+    /// Whether the call is in library code
+    pub library: bool,
+    pub signature: &'static SyscallSignature,
+    /// Arguments by parameter name
+    pub args: Vec<(&'static str, ArgValue)>,
+}
+
+impl SyscallCall {
+    /// Text of the argument named `name`, if it was recovered as a string
+    pub fn str_arg(&self, name: &str) -> Option<&str> {
+        self.args.iter().find_map(|(param, value)| match value {
+            ArgValue::Str(text) if *param == name => Some(text.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for SyscallCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.signature.name)?;
+        for (i, (_, value)) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Contents of the buffer at `ptr` with `len` bytes
+fn buffer(binary: &SbpfBinary, ptr: Value, len: Value, reg: u8) -> ArgValue {
+    let address = match ptr {
+        Value::Const(address) | Value::Pointer(Location::Absolute(address)) => address,
+        Value::Pointer(loc) => return ArgValue::Pointer(loc),
+        _ => return ArgValue::Register(reg),
+    };
+    let bytes = match len {
+        Value::Const(len) => binary.read_rodata(address, len as usize),
+        _ => None,
+    };
+    match bytes {
+        Some(bytes) => match std::str::from_utf8(bytes) {
+            Ok(text) => ArgValue::Str(text.to_string()),
+            Err(_) => ArgValue::Bytes(bytes.to_vec()),
+        },
+        None => ArgValue::Const(address),
+    }
+}
+
+/// Value of a single-register argument
+fn scalar(binary: &SbpfBinary, kind: ArgKind, value: Value, reg: u8) -> ArgValue {
+    match (kind, value) {
+        (ArgKind::Pubkey, Value::Const(address)) => binary
+            .read_rodata(address, PUBKEY_SIZE)
+            .and_then(Pubkey::from_slice)
+            .map_or(ArgValue::Const(address), ArgValue::Pubkey),
+        (_, Value::Const(c)) => ArgValue::Const(c),
+        (_, Value::Pointer(loc)) => ArgValue::Pointer(loc),
+        (_, Value::Loaded(loc, _)) => ArgValue::Loaded(loc),
+        (_, Value::Unknown) => ArgValue::Register(reg),
+    }
+}
+
+/// Slices of an array of `&[u8]` on the stack
+fn slices(binary: &SbpfBinary, state: &State, ptr: Value, count: Value, reg: u8) -> ArgValue {
+    let (Some(Location::Stack(base)), Value::Const(count)) = (ptr.location(), count) else {
+        return scalar(binary, ArgKind::Ptr, ptr, reg);
+    };
+    if count > MAX_SLICES {
+        return ArgValue::Pointer(Location::Stack(base));
+    }
+    let slices = (0..count as i64)
+        .map(|i| {
+            let slot = base + i * SLICE_SIZE;
+            buffer(binary, state.stack_slot(slot), state.stack_slot(slot + 8), reg)
+        })
+        .collect();
+    ArgValue::Slices(slices)
+}
+
+/// Arguments of a call to `signature` in `state`
+pub fn arguments(
+    binary: &SbpfBinary,
+    signature: &'static SyscallSignature,
+    state: &State,
+) -> Vec<(&'static str, ArgValue)> {
+    let mut reg = 1u8;
+    let mut args = Vec::new();
+    for &(name, kind) in signature.params {
+        let value = match kind {
+            ArgKind::Str | ArgKind::Bytes => buffer(binary, state.reg(reg), state.reg(reg + 1), reg),
+            ArgKind::Slices => slices(binary, state, state.reg(reg), state.reg(reg + 1), reg),
+            _ => scalar(binary, kind, state.reg(reg), reg),
+        };
+        args.push((name, value));
+        reg += kind.registers() as u8;
+    }
+    args
+}
+
+/// Recover the arguments of every reachable syscall
+pub fn recover(
+    cfg: &ControlFlowGraph,
+    instructions: &[Instruction],
+    dataflow: &Dataflow,
+    binary: &SbpfBinary,
+) -> Vec<SyscallCall> {
+    let mut calls = Vec::new();
+    for function in &dataflow.functions {
+        let cfg_function = &cfg.functions[function.index];
+        function.visit(cfg, instructions, |_, inst, state| {
+            let Some(signature) = inst.syscall_number().and_then(syscall_name).and_then(syscall_signature) else {
+                return;
+            };
+            calls.push(SyscallCall {
+                address: inst.address,
+                function: cfg_function.address,
+                function_name: cfg_function.name.clone(),
+                library: cfg_function.library,
+                signature,
+                args: arguments(binary, signature, state),
+            });
+        });
+    }
+    calls.sort_by_key(|c| c.address);
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::tests::insn;
+    use crate::binary::MetadataSection;
+    use crate::instruction::InstructionDecoder;
+    use crate::syscalls::syscall_hash;
+
+    #[test]
+    fn test_recover_log_arguments() {
+        let log = syscall_hash("sol_log_") as u32 as i32;
+        let log64 = syscall_hash("sol_log_64_") as u32 as i32;
+        let program: Vec<u8> = [
+            insn(0xbf, 6, 1, 0, 0),     // mov r6, r1
+            insn(0x18, 1, 0, 0, 0x200), // lddw r1, 0x200
+            insn(0x00, 0, 0, 0, 0),
+            insn(0xb7, 2, 0, 0, 23),    // mov r2, 23
+            insn(0x85, 0, 0, 0, log),   // call sol_log_
+            insn(0x79, 1, 6, 80, 0),    // ldxdw r1, [r6+80]   accounts[0].lamports
+            insn(0xb7, 2, 0, 0, 7),     // mov r2, 7
+            insn(0x85, 0, 0, 0, log64), // call sol_log_64_
+            insn(0x95, 0, 0, 0, 0),     // exit
+        ]
+        .concat();
+        let binary = SbpfBinary {
+            bytecode: program.clone(),
+            entry_point: 0,
+            text_address: 0,
+            text_offset: 0,
+            version: None,
+            metadata: vec![MetadataSection {
+                name: ".rodata".to_string(),
+                address: 0x200,
+                data: b"Instruction: Initialize".to_vec(),
+            }],
+            symbols: Vec::new(),
+        };
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let dataflow = Dataflow::compute(&cfg, &instructions, 0);
+        let calls = recover(&cfg, &instructions, &dataflow, &binary);
+
+        let rendered: Vec<String> = calls.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                r#"sol_log_("Instruction: Initialize")"#,
+                "sol_log_64_(accounts[0].lamports, 7, r3, r4, r5)",
+            ]
+        );
+        assert_eq!(calls[0].str_arg("message"), Some("Instruction: Initialize"));
+    }
+}
@@ -0,0 +1,209 @@
+// Solana syscall identification
+
+use solana_sbpf::ebpf::hash_symbol_name;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Syscalls registered by the Solana runtime
+pub const SYSCALLS: &[&str] = &[
+    "abort",
+    "sol_panic_",
+    "sol_log_",
+    "sol_log_64_",
+    "sol_log_compute_units_",
+    "sol_log_pubkey",
+    "sol_log_data",
+    "sol_create_program_address",
+    "sol_try_find_program_address",
+    "sol_sha256",
+    "sol_keccak256",
+    "sol_blake3",
+    "sol_secp256k1_recover",
+    "sol_poseidon",
+    "sol_curve_validate_point",
+    "sol_curve_group_op",
+    "sol_curve_multiscalar_mul",
+    "sol_curve_pairing_map",
+    "sol_alt_bn128_group_op",
+    "sol_alt_bn128_compression",
+    "sol_big_mod_exp",
+    "sol_get_clock_sysvar",
+    "sol_get_epoch_schedule_sysvar",
+    "sol_get_fees_sysvar",
+    "sol_get_rent_sysvar",
+    "sol_get_last_restart_slot",
+    "sol_get_epoch_rewards_sysvar",
+    "sol_get_sysvar",
+    "sol_get_epoch_stake",
+    "sol_memcpy_",
+    "sol_memmove_",
+    "sol_memcmp_",
+    "sol_memset_",
+    "sol_invoke_signed_c",
+    "sol_invoke_signed_rust",
+    "sol_alloc_free_",
+    "sol_set_return_data",
+    "sol_get_return_data",
+    "sol_get_processed_sibling_instruction",
+    "sol_get_stack_height",
+    "sol_remaining_compute_units",
+];
+
+/// Syscall number (murmur3 hash of the name) used in `call imm`
+pub fn syscall_hash(name: &str) -> u64 {
+    hash_symbol_name(name.as_bytes()) as u64
+}
+
+/// Map syscall number to name
+pub fn syscall_name(num: u64) -> Option<&'static str> {
+    static TABLE: OnceLock<HashMap<u64, &'static str>> = OnceLock::new();
+    TABLE
+        .get_or_init(|| SYSCALLS.iter().map(|&name| (syscall_hash(name), name)).collect())
+        .get(&num)
+        .copied()
+}
+
+/// Check if `num` is one of the cross-program invocation syscalls
+pub fn is_invoke(num: u64) -> bool {
+    matches!(syscall_name(num), Some("sol_invoke_signed_c" | "sol_invoke_signed_rust"))
+}
+
+/// Default charge for a syscall that has no dedicated cost
+pub const SYSCALL_BASE_COST: u64 = 100;
+
+/// Base compute units charged for a syscall, before any length-dependent
+/// charges, using the runtime's default compute budget
+pub fn syscall_cost(name: &str) -> u64 {
+    match name {
+        "abort" | "sol_panic_" => 0,
+        "sol_memcpy_" | "sol_memmove_" | "sol_memcmp_" | "sol_memset_" => 10,
+        "sol_sha256" | "sol_keccak256" | "sol_blake3" => 85,
+        "sol_create_program_address" | "sol_try_find_program_address" => 1500,
+        "sol_invoke_signed_c" | "sol_invoke_signed_rust" => 1000,
+        "sol_secp256k1_recover" => 25_000,
+        "sol_curve_validate_point" => 159,
+        "sol_curve_group_op" => 473,
+        "sol_curve_multiscalar_mul" => 2303,
+        "sol_alt_bn128_group_op" => 334,
+        "sol_get_epoch_stake" => 200,
+        _ => SYSCALL_BASE_COST,
+    }
+}
+
+/// How a syscall interprets its argument registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// Integer in one register
+    U64,
+    /// Pointer in one register
+    Ptr,
+    /// Pointer to a 32-byte public key
+    Pubkey,
+    /// UTF-8 text as pointer and length registers
+    Str,
+    /// Byte buffer as pointer and length registers
+    Bytes,
+    /// Array of byte slices as pointer and count registers
+    Slices,
+}
+
+impl ArgKind {
+    /// Number of argument registers the argument occupies
+    pub fn registers(&self) -> usize {
+        match self {
+            ArgKind::Str | ArgKind::Bytes | ArgKind::Slices => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Parameters of a syscall
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallSignature {
+    pub name: &'static str,
+    /// Named parameters, filling r1-r5 in order
+    pub params: &'static [(&'static str, ArgKind)],
+    /// Whether r0 carries a meaningful result
+    pub returns: bool,
+}
+
+macro_rules! signature {
+    ($name:literal, [$($param:literal: $kind:ident),*], $returns:literal) => {
+        SyscallSignature {
+            name: $name,
+            params: &[$(($param, ArgKind::$kind)),*],
+            returns: $returns,
+        }
+    };
+}
+
+/// Signatures of the syscalls in [`SYSCALLS`]
+pub const SYSCALL_SIGNATURES: &[SyscallSignature] = &[
+    signature!("abort", [], false),
+    signature!("sol_panic_", ["file": Str, "line": U64, "column": U64], false),
+    signature!("sol_log_", ["message": Str], false),
+    signature!("sol_log_64_", ["arg1": U64, "arg2": U64, "arg3": U64, "arg4": U64, "arg5": U64], false),
+    signature!("sol_log_compute_units_", [], false),
+    signature!("sol_log_pubkey", ["pubkey": Pubkey], false),
+    signature!("sol_log_data", ["data": Slices], false),
+    signature!("sol_create_program_address", ["seeds": Slices, "program_id": Pubkey, "address": Ptr], true),
+    signature!("sol_try_find_program_address", ["seeds": Slices, "program_id": Pubkey, "address": Ptr, "bump": Ptr], true),
+    signature!("sol_sha256", ["vals": Slices, "result": Ptr], true),
+    signature!("sol_keccak256", ["vals": Slices, "result": Ptr], true),
+    signature!("sol_blake3", ["vals": Slices, "result": Ptr], true),
+    signature!("sol_secp256k1_recover", ["hash": Ptr, "recovery_id": U64, "signature": Ptr, "result": Ptr], true),
+    signature!("sol_poseidon", ["parameters": U64, "endianness": U64, "vals": Slices, "result": Ptr], true),
+    signature!("sol_curve_validate_point", ["curve_id": U64, "point": Ptr], true),
+    signature!("sol_curve_group_op", ["curve_id": U64, "group_op": U64, "left": Ptr, "right": Ptr, "result": Ptr], true),
+    signature!("sol_curve_multiscalar_mul", ["curve_id": U64, "scalars": Ptr, "points": Ptr, "points_len": U64, "result": Ptr], true),
+    signature!("sol_curve_pairing_map", ["curve_id": U64, "point": Ptr, "result": Ptr], true),
+    signature!("sol_alt_bn128_group_op", ["group_op": U64, "input": Bytes, "result": Ptr], true),
+    signature!("sol_alt_bn128_compression", ["op": U64, "input": Bytes, "result": Ptr], true),
+    signature!("sol_big_mod_exp", ["params": Ptr, "result": Ptr], true),
+    signature!("sol_get_clock_sysvar", ["clock": Ptr], true),
+    signature!("sol_get_epoch_schedule_sysvar", ["epoch_schedule": Ptr], true),
+    signature!("sol_get_fees_sysvar", ["fees": Ptr], true),
+    signature!("sol_get_rent_sysvar", ["rent": Ptr], true),
+    signature!("sol_get_last_restart_slot", ["slot": Ptr], true),
+    signature!("sol_get_epoch_rewards_sysvar", ["epoch_rewards": Ptr], true),
+    signature!("sol_get_sysvar", ["sysvar_id": Pubkey, "result": Ptr, "offset": U64, "length": U64], true),
+    signature!("sol_get_epoch_stake", ["vote_address": Pubkey], true),
+    signature!("sol_memcpy_", ["dst": Ptr, "src": Ptr, "n": U64], false),
+    signature!("sol_memmove_", ["dst": Ptr, "src": Ptr, "n": U64], false),
+    signature!("sol_memcmp_", ["s1": Ptr, "s2": Ptr, "n": U64, "result": Ptr], false),
+    signature!("sol_memset_", ["s": Ptr, "c": U64, "n": U64], false),
+    signature!("sol_invoke_signed_c", ["instruction": Ptr, "account_infos": Ptr, "account_infos_len": U64, "signers_seeds": Ptr, "signers_seeds_len": U64], true),
+    signature!("sol_invoke_signed_rust", ["instruction": Ptr, "account_infos": Ptr, "account_infos_len": U64, "signers_seeds": Ptr, "signers_seeds_len": U64], true),
+    signature!("sol_alloc_free_", ["size": U64, "free_addr": Ptr], true),
+    signature!("sol_set_return_data", ["data": Bytes], false),
+    signature!("sol_get_return_data", ["data": Ptr, "length": U64, "program_id": Ptr], true),
+    signature!("sol_get_processed_sibling_instruction", ["index": U64, "meta": Ptr, "program_id": Ptr, "data": Ptr, "accounts": Ptr], true),
+    signature!("sol_get_stack_height", [], true),
+    signature!("sol_remaining_compute_units", [], true),
+];
+
+/// Signature of the syscall `name`
+pub fn syscall_signature(name: &str) -> Option<&'static SyscallSignature> {
+    SYSCALL_SIGNATURES.iter().find(|s| s.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syscall_roundtrip() {
+        assert_eq!(syscall_hash("abort"), 0xb6fc1a11);
+        assert_eq!(syscall_name(syscall_hash("sol_log_")), Some("sol_log_"));
+        assert_eq!(syscall_name(1), None);
+    }
+
+    #[test]
+    fn test_signatures_cover_syscalls() {
+        for name in SYSCALLS {
+            let signature = syscall_signature(name).unwrap();
+            let registers: usize = signature.params.iter().map(|(_, kind)| kind.registers()).sum();
+            assert!(registers <= 5, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,344 @@
+// Dataflow analysis over the blocks of a function
+//
+// A `Problem` describes the facts, how they merge and how a block transforms
+// them; `solve` iterates to a fixpoint with a worklist in the problem's
+// direction. Liveness, reaching definitions and def-use chains over the
+// register IR are provided on top of it.
+
+// Not every analysis has a consumer in the decompiler yet
+#![allow(dead_code)]
+
+use crate::decompiler::ir::IR;
+use sbpf_common::cfg::dominators::reverse_postorder;
+use sbpf_common::cfg::{ControlFlowGraph, Function};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// Number of SBPF registers
+pub const NUM_REGS: u8 = 11;
+
+/// Set of registers r0-r10
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RegSet(u16);
+
+impl RegSet {
+    pub fn new() -> Self {
+        RegSet(0)
+    }
+
+    /// Registers `from..=to`
+    pub fn range(from: u8, to: u8) -> Self {
+        (from..=to).collect()
+    }
+
+    pub fn insert(&mut self, reg: u8) {
+        if reg < NUM_REGS {
+            self.0 |= 1 << reg;
+        }
+    }
+
+    pub fn contains(&self, reg: u8) -> bool {
+        reg < NUM_REGS && self.0 & (1 << reg) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn union(&self, other: RegSet) -> RegSet {
+        RegSet(self.0 | other.0)
+    }
+
+    pub fn difference(&self, other: RegSet) -> RegSet {
+        RegSet(self.0 & !other.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..NUM_REGS).filter(|&reg| self.contains(reg))
+    }
+}
+
+impl FromIterator<u8> for RegSet {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        let mut set = RegSet::new();
+        for reg in iter {
+            set.insert(reg);
+        }
+        set
+    }
+}
+
+impl fmt::Display for RegSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let regs: Vec<String> = self.iter().map(|r| format!("r{}", r)).collect();
+        write!(f, "{{{}}}", regs.join(", "))
+    }
+}
+
+/// Direction facts flow in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A dataflow problem over the blocks of one function
+pub trait Problem {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    /// Fact entering the function (forward) or leaving it (backward)
+    fn boundary(&self) -> Self::Fact;
+
+    /// Starting fact of every other block
+    fn initial(&self) -> Self::Fact;
+
+    /// Merge `other` into `into`
+    fn join(&self, into: &mut Self::Fact, other: &Self::Fact);
+
+    /// Fact after `block` given the fact before it, in the problem's direction
+    fn transfer(&self, block: usize, fact: &Self::Fact) -> Self::Fact;
+}
+
+/// Fixpoint of a problem, keyed by block id
+#[derive(Debug, Clone)]
+pub struct Solution<F> {
+    /// Fact at the start of each block
+    pub before: HashMap<usize, F>,
+    /// Fact at the end of each block
+    pub after: HashMap<usize, F>,
+}
+
+/// Solve `problem` over the blocks of `function` reachable from its entry
+pub fn solve<P: Problem>(cfg: &ControlFlowGraph, function: &Function, problem: &P) -> Solution<P::Fact> {
+    let order = reverse_postorder(cfg, function.entry);
+    let members: BTreeSet<usize> = order.iter().copied().collect();
+    let forward = problem.direction() == Direction::Forward;
+    let order: Vec<usize> = if forward { order } else { order.into_iter().rev().collect() };
+
+    // Facts flowing into each block in the problem's direction
+    let mut input: HashMap<usize, P::Fact> = order.iter().map(|&b| (b, problem.initial())).collect();
+    let mut output: HashMap<usize, P::Fact> = HashMap::new();
+    let mut worklist: VecDeque<usize> = order.iter().copied().collect();
+    let mut queued: BTreeSet<usize> = members.clone();
+
+    while let Some(block) = worklist.pop_front() {
+        queued.remove(&block);
+        let (sources, sinks) = if forward {
+            (&cfg.blocks[block].predecessors, &cfg.blocks[block].successors)
+        } else {
+            (&cfg.blocks[block].successors, &cfg.blocks[block].predecessors)
+        };
+        let boundary = if forward {
+            block == function.entry
+        } else {
+            !sources.iter().any(|s| members.contains(s))
+        };
+        let mut fact = if boundary { problem.boundary() } else { problem.initial() };
+        for source in sources.iter().filter(|s| members.contains(s)) {
+            if let Some(out) = output.get(source) {
+                problem.join(&mut fact, out);
+            }
+        }
+        let out = problem.transfer(block, &fact);
+        input.insert(block, fact);
+        if output.get(&block) != Some(&out) {
+            output.insert(block, out);
+            for &sink in sinks.iter().filter(|s| members.contains(s)) {
+                if queued.insert(sink) {
+                    worklist.push_back(sink);
+                }
+            }
+        }
+    }
+
+    if forward {
+        Solution {
+            before: input,
+            after: output,
+        }
+    } else {
+        Solution {
+            before: output,
+            after: input,
+        }
+    }
+}
+
+/// Registers live at each point, backward over the register IR
+pub struct Liveness<'a> {
+    pub ir: &'a IR,
+}
+
+impl Liveness<'_> {
+    /// Registers live before instruction `index` of `block`, given the
+    /// registers live at the end of the block
+    pub fn live_before(&self, block: usize, index: usize, live_out: RegSet) -> RegSet {
+        self.ir.basic_blocks[block].instructions[index..]
+            .iter()
+            .rev()
+            .fold(live_out, |live, inst| live.difference(inst.defs()).union(inst.uses()))
+    }
+}
+
+impl Problem for Liveness<'_> {
+    type Fact = RegSet;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self) -> RegSet {
+        RegSet::new()
+    }
+
+    fn initial(&self) -> RegSet {
+        RegSet::new()
+    }
+
+    fn join(&self, into: &mut RegSet, other: &RegSet) {
+        *into = into.union(*other);
+    }
+
+    fn transfer(&self, block: usize, fact: &RegSet) -> RegSet {
+        self.live_before(block, 0, *fact)
+    }
+}
+
+/// Where a register value is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Site {
+    /// Value on entry to the function
+    Entry,
+    /// Instruction `index` of `block`
+    At { block: usize, index: usize },
+}
+
+/// A definition of a register
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Definition {
+    pub reg: u8,
+    pub site: Site,
+}
+
+/// Definitions reaching each point, forward over the register IR
+pub struct ReachingDefinitions<'a> {
+    pub ir: &'a IR,
+}
+
+impl ReachingDefinitions<'_> {
+    /// Apply instruction `index` of `block` to the reaching definitions
+    pub fn step(&self, block: usize, index: usize, defs: &mut BTreeSet<Definition>) {
+        let killed = self.ir.basic_blocks[block].instructions[index].defs();
+        if killed.is_empty() {
+            return;
+        }
+        defs.retain(|d| !killed.contains(d.reg));
+        defs.extend(killed.iter().map(|reg| Definition {
+            reg,
+            site: Site::At { block, index },
+        }));
+    }
+}
+
+impl Problem for ReachingDefinitions<'_> {
+    type Fact = BTreeSet<Definition>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        (0..NUM_REGS).map(|reg| Definition { reg, site: Site::Entry }).collect()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn join(&self, into: &mut Self::Fact, other: &Self::Fact) {
+        into.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, block: usize, fact: &Self::Fact) -> Self::Fact {
+        let mut defs = fact.clone();
+        for index in 0..self.ir.basic_blocks[block].instructions.len() {
+            self.step(block, index, &mut defs);
+        }
+        defs
+    }
+}
+
+/// Use of a register by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Use {
+    pub reg: u8,
+    pub block: usize,
+    pub index: usize,
+}
+
+/// Links between definitions and the uses they reach
+#[derive(Debug, Clone, Default)]
+pub struct DefUseChains {
+    /// Uses reached by each definition
+    pub uses: BTreeMap<Definition, BTreeSet<Use>>,
+    /// Definitions reaching each use
+    pub defs: BTreeMap<Use, BTreeSet<Definition>>,
+}
+
+impl DefUseChains {
+    /// Build the chains of `function` from its reaching definitions
+    pub fn compute(cfg: &ControlFlowGraph, function: &Function, ir: &IR) -> Self {
+        let problem = ReachingDefinitions { ir };
+        let solution = solve(cfg, function, &problem);
+        let mut chains = DefUseChains::default();
+        for (&block, reaching) in &solution.before {
+            let mut reaching = reaching.clone();
+            for (index, inst) in ir.basic_blocks[block].instructions.iter().enumerate() {
+                for reg in inst.uses().iter() {
+                    let site = Use { reg, block, index };
+                    for &def in reaching.iter().filter(|d| d.reg == reg) {
+                        chains.uses.entry(def).or_default().insert(site);
+                        chains.defs.entry(site).or_default().insert(def);
+                    }
+                }
+                problem.step(block, index, &mut reaching);
+            }
+        }
+        chains
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_liveness_and_chains() {
+        let program: Vec<u8> = [
+            insn(0xb7, 0, 0, 0, 0),     // 0x00: mov r0, 0
+            insn(0x15, 1, 0, 1, 0),     // 0x08: jeq r1, 0, 0x18
+            insn(0xbf, 0, 2, 0, 0),     // 0x10: mov r0, r2
+            insn(0x95, 0, 0, 0, 0),     // 0x18: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let function = &cfg.functions[0];
+
+        let liveness = solve(&cfg, function, &Liveness { ir: &ir });
+        assert_eq!(liveness.before[&function.entry], RegSet::range(1, 2));
+
+        // Both definitions of r0 reach the exit
+        let chains = DefUseChains::compute(&cfg, function, &ir);
+        let exit = cfg.block_id_at(0x18).unwrap();
+        let reaching = &chains.defs[&Use { reg: 0, block: exit, index: 0 }];
+        assert_eq!(reaching.len(), 2);
+        assert!(reaching.iter().all(|d| d.site != Site::Entry));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::decompiler::dataflow::RegSet;
+use sbpf_common::pda::PdaDerivation;
+use sbpf_common::pubkey::Pubkey;
+use sbpf_common::syscall_args::ArgValue;
+use sbpf_common::syscalls::{syscall_name, syscall_signature, SyscallSignature};
+use std::fmt;
+
+/// Intermediate representation for decompilation
+#[derive(Debug, Clone)]
+pub struct IR {
+    pub basic_blocks: Vec<BasicBlock>,
+    pub functions: Vec<Function>,
+}
+
+/// Basic block in the IR
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub address: u64,
+    pub instructions: Vec<IRInstruction>,
+}
+
+/// Function in the IR
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub address: u64,
+    pub name: String,
+    pub basic_blocks: Vec<u64>,
+    /// Statically linked library code
+    pub library: bool,
+}
+
+/// Register or immediate operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Reg(u8),
+    Imm(i64),
+}
+
+/// Largest immediate printed in decimal
+const MAX_DECIMAL: i64 = 0xffff;
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Reg(reg) => write!(f, "r{}", reg),
+            Operand::Imm(imm) if (-MAX_DECIMAL..=MAX_DECIMAL).contains(&imm) => write!(f, "{}", imm),
+            Operand::Imm(imm) if imm < 0 => write!(f, "-0x{:x}", imm.unsigned_abs()),
+            Operand::Imm(imm) => write!(f, "0x{:x}", imm),
+        }
+    }
+}
+
+/// Width of an ALU operation; 32-bit results are zero-extended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Width {
+    W32,
+    W64,
+}
+
+impl Width {
+    /// Unsigned type of the operation
+    pub fn unsigned(&self) -> &'static str {
+        match self {
+            Width::W32 => "u32",
+            Width::W64 => "u64",
+        }
+    }
+
+    /// Signed type of the operation
+    pub fn signed(&self) -> &'static str {
+        match self {
+            Width::W32 => "i32",
+            Width::W64 => "i64",
+        }
+    }
+
+    /// Width in bits
+    pub fn bits(&self) -> u8 {
+        match self {
+            Width::W32 => 32,
+            Width::W64 => 64,
+        }
+    }
+}
+
+/// Two-operand ALU operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    /// Unsigned division
+    Div,
+    /// Unsigned remainder
+    Mod,
+    Or,
+    And,
+    Xor,
+    Lsh,
+    /// Logical right shift
+    Rsh,
+    /// Arithmetic right shift
+    Arsh,
+}
+
+impl BinaryOp {
+    /// Rust operator for the operation
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Or => "|",
+            BinaryOp::And => "&",
+            BinaryOp::Xor => "^",
+            BinaryOp::Lsh => "<<",
+            BinaryOp::Rsh | BinaryOp::Arsh => ">>",
+        }
+    }
+}
+
+/// One-operand ALU operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    /// Convert the low `bits` to little endian, zeroing the rest
+    ToLe(u8),
+    /// Convert the low `bits` to big endian, zeroing the rest
+    ToBe(u8),
+}
+
+/// Size of a memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemSize {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl MemSize {
+    pub fn from_bytes(bytes: u8) -> Self {
+        match bytes {
+            1 => MemSize::U8,
+            2 => MemSize::U16,
+            4 => MemSize::U32,
+            _ => MemSize::U64,
+        }
+    }
+
+    /// Width of the access in bits
+    pub fn bits(&self) -> u8 {
+        match self {
+            MemSize::U8 => 8,
+            MemSize::U16 => 16,
+            MemSize::U32 => 32,
+            MemSize::U64 => 64,
+        }
+    }
+
+    /// Rust type of the accessed value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MemSize::U8 => "u8",
+            MemSize::U16 => "u16",
+            MemSize::U32 => "u32",
+            MemSize::U64 => "u64",
+        }
+    }
+}
+
+/// Comparison of a conditional branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Sgt,
+    Sge,
+    Slt,
+    Sle,
+    /// `(lhs & rhs) != 0`
+    Set,
+    /// `(lhs & rhs) == 0`, the negation of `Set`
+    Clear,
+}
+
+impl Condition {
+    /// Condition that holds exactly when this one does not
+    pub fn negate(&self) -> Self {
+        match self {
+            Condition::Eq => Condition::Ne,
+            Condition::Ne => Condition::Eq,
+            Condition::Gt => Condition::Le,
+            Condition::Ge => Condition::Lt,
+            Condition::Lt => Condition::Ge,
+            Condition::Le => Condition::Gt,
+            Condition::Sgt => Condition::Sle,
+            Condition::Sge => Condition::Slt,
+            Condition::Slt => Condition::Sge,
+            Condition::Sle => Condition::Sgt,
+            Condition::Set => Condition::Clear,
+            Condition::Clear => Condition::Set,
+        }
+    }
+
+    /// Whether the operands are compared as signed
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Condition::Sgt | Condition::Sge | Condition::Slt | Condition::Sle)
+    }
+
+    /// Rust comparison operator; bit tests compare the masked value with 0
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Condition::Eq | Condition::Clear => "==",
+            Condition::Ne | Condition::Set => "!=",
+            Condition::Gt | Condition::Sgt => ">",
+            Condition::Ge | Condition::Sge => ">=",
+            Condition::Lt | Condition::Slt => "<",
+            Condition::Le | Condition::Sle => "<=",
+        }
+    }
+
+    /// Render `lhs <cond> rhs` over rendered operands
+    pub fn render(&self, lhs: &str, rhs: &str) -> String {
+        self.render_typed((lhs, None), (rhs, None))
+    }
+
+    /// Render `lhs <cond> rhs` over rendered operands and the integer types
+    /// they are declared with, casting those of the wrong signedness
+    pub fn render_typed(&self, lhs: (&str, Option<&str>), rhs: (&str, Option<&str>)) -> String {
+        let operand = |(operand, ty): (&str, Option<&str>)| {
+            // Literals take the type of the other operand
+            if operand.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+                return operand.to_string();
+            }
+            match ty {
+                Some("i64") if self.is_signed() => operand.to_string(),
+                _ if self.is_signed() => format!("({} as i64)", operand),
+                Some(ty) if ty.starts_with('i') => format!("({} as u64)", operand),
+                _ => operand.to_string(),
+            }
+        };
+        match self {
+            Condition::Set | Condition::Clear => format!("({} & {}) {} 0", lhs.0, rhs.0, self.symbol()),
+            _ => format!("{} {} {}", operand(lhs), self.symbol(), operand(rhs)),
+        }
+    }
+}
+
+/// IR instruction representation
+#[derive(Debug, Clone, PartialEq)]
+pub enum IRInstruction {
+    /// `dst = src`; 32-bit moves zero-extend and 64-bit immediates are
+    /// sign-extended
+    Move { dst: u8, src: Operand, width: Width },
+    /// `dst = value` from a two-slot `lddw`
+    LoadImm { dst: u8, value: u64 },
+    /// `dst = &pubkey` from an `lddw` of a recovered pubkey constant
+    LoadPubkey { dst: u8, pubkey: Pubkey },
+    /// `dst = dst op src`
+    Binary { op: BinaryOp, dst: u8, src: Operand, width: Width },
+    /// `dst = op dst`
+    Unary { op: UnaryOp, dst: u8, width: Width },
+    /// `dst = *(base + offset)`
+    Load { dst: u8, base: u8, offset: i16, size: MemSize },
+    /// `*(base + offset) = value`
+    Store { base: u8, offset: i16, value: Operand, size: MemSize },
+    /// Legacy packet load into r0 from `index + offset`
+    LoadPacket { index: Option<u8>, offset: i64, size: MemSize },
+    Jump { target: u64 },
+    /// `if lhs cond rhs { goto target }`
+    Branch { cond: Condition, lhs: u8, rhs: Operand, target: u64 },
+    /// Call of a function in the program
+    Call { target: u64 },
+    /// Call of the function whose address is in `reg`
+    CallIndirect { reg: u8 },
+    /// Syscall by murmur3 hash of its name, with the arguments recovered
+    /// for each parameter of its signature
+    Syscall { hash: u32, recovered: Option<Vec<ArgValue>> },
+    Exit,
+    /// PDA derivation performed by the following syscall
+    Pda(PdaDerivation),
+    /// Byte that does not decode to an instruction
+    Invalid { code: u8 },
+}
+
+/// Signature of the syscall with murmur3 hash `hash`
+pub fn syscall_hash_signature(hash: u32) -> Option<&'static SyscallSignature> {
+    syscall_name(hash as u64).and_then(syscall_signature)
+}
+
+/// Render a call of the syscall `hash` whose argument registers r1.. are
+/// rendered as `args`; parameters recovered as constants or strings are
+/// shown by value instead of their registers
+pub fn render_syscall(hash: u32, recovered: Option<&[ArgValue]>, args: &[String]) -> String {
+    let Some(signature) = syscall_hash_signature(hash) else {
+        return format!("syscall_0x{:x}({})", hash, args.join(", "));
+    };
+    let mut rendered = Vec::new();
+    let mut reg = 0;
+    for (i, (_, kind)) in signature.params.iter().enumerate() {
+        let registers = kind.registers();
+        match recovered.and_then(|values| values.get(i)).filter(|value| value.is_constant()) {
+            Some(value) => rendered.push(value.to_string()),
+            None => rendered.extend(args.iter().skip(reg).take(registers).cloned()),
+        }
+        reg += registers;
+    }
+    format!("{}({})", signature.name, rendered.join(", "))
+}
+
+impl IRInstruction {
+    /// Registers written; calls clobber r0-r5
+    pub fn defs(&self) -> RegSet {
+        match *self {
+            IRInstruction::Move { dst, .. }
+            | IRInstruction::LoadImm { dst, .. }
+            | IRInstruction::LoadPubkey { dst, .. }
+            | IRInstruction::Binary { dst, .. }
+            | IRInstruction::Unary { dst, .. }
+            | IRInstruction::Load { dst, .. } => [dst].into_iter().collect(),
+            IRInstruction::LoadPacket { .. } => [0].into_iter().collect(),
+            IRInstruction::Call { .. } | IRInstruction::CallIndirect { .. } | IRInstruction::Syscall { .. } => {
+                RegSet::range(0, 5)
+            }
+            _ => RegSet::new(),
+        }
+    }
+
+    /// Registers read; calls read the argument registers, all of r1-r5
+    /// unless the syscall's signature says otherwise
+    pub fn uses(&self) -> RegSet {
+        let operand = |op: Operand| match op {
+            Operand::Reg(reg) => Some(reg),
+            Operand::Imm(_) => None,
+        };
+        match *self {
+            IRInstruction::Move { src, .. } => operand(src).into_iter().collect(),
+            IRInstruction::Binary { dst, src, .. } => [Some(dst), operand(src)].into_iter().flatten().collect(),
+            IRInstruction::Unary { dst, .. } => [dst].into_iter().collect(),
+            IRInstruction::Load { base, .. } => [base].into_iter().collect(),
+            IRInstruction::Store { base, value, .. } => [Some(base), operand(value)].into_iter().flatten().collect(),
+            IRInstruction::LoadPacket { index, .. } => index.into_iter().collect(),
+            IRInstruction::Branch { lhs, rhs, .. } => [Some(lhs), operand(rhs)].into_iter().flatten().collect(),
+            IRInstruction::Call { .. } => RegSet::range(1, 5),
+            IRInstruction::CallIndirect { reg } => RegSet::range(1, 5).union([reg].into_iter().collect()),
+            IRInstruction::Syscall { hash, .. } => match syscall_hash_signature(hash) {
+                Some(signature) => {
+                    let count: usize = signature.params.iter().map(|(_, kind)| kind.registers()).sum();
+                    RegSet::range(1, count as u8)
+                }
+                None => RegSet::range(1, 5),
+            },
+            IRInstruction::Exit => [0].into_iter().collect(),
+            _ => RegSet::new(),
+        }
+    }
+}
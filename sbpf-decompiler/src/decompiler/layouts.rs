@@ -0,0 +1,266 @@
+// Struct layout inference
+//
+// A pointer dereferenced at several constant offsets points to a struct with
+// a field at each offset, as wide as its widest access there. A pointer
+// passed as a call argument shares its layout with the callee's parameter,
+// so the accesses of every function receiving the same pointer are merged.
+// Pointers of a known type, such as the entrypoint's input buffer, get no
+// struct; the parameters they are passed to take their type instead.
+
+use crate::decompiler::ir::MemSize;
+use crate::decompiler::prototypes::Prototype;
+use crate::decompiler::ssa::{Callee, Expr, SsaFunction, Stmt};
+use crate::decompiler::types::{Type, Types};
+use crate::decompiler::variables::{UnionFind, Variables};
+use std::collections::{BTreeMap, HashMap};
+
+/// Name of the inferred struct `id`
+pub fn struct_name(id: usize) -> String {
+    format!("Struct{}", id + 1)
+}
+
+/// Name of the field at `offset`
+fn field_name(offset: i64) -> String {
+    format!("field_0x{:x}", offset)
+}
+
+/// Structs inferred across the functions of a program
+#[derive(Debug, Clone, Default)]
+pub struct Layouts {
+    /// Fields of each struct by offset
+    structs: Vec<BTreeMap<i64, MemSize>>,
+    /// Struct pointed to by each pointer, by function address and name
+    pointers: HashMap<(u64, String), usize>,
+    /// Known types of the parameters passed typed pointers, by function
+    /// address and register
+    params: HashMap<u64, BTreeMap<u8, Type>>,
+}
+
+impl Layouts {
+    /// Infer structs from the accesses through each function's pointers
+    pub fn infer(functions: &[(&SsaFunction, &Variables, &Types)], prototypes: &HashMap<u64, Prototype>) -> Self {
+        let mut pointers: Vec<(u64, String)> = Vec::new();
+        let mut index: HashMap<(u64, String), usize> = HashMap::new();
+        let mut number = |key: (u64, String)| {
+            *index.entry(key.clone()).or_insert_with(|| {
+                pointers.push(key);
+                pointers.len() - 1
+            })
+        };
+        let mut accesses: HashMap<usize, BTreeMap<i64, MemSize>> = HashMap::new();
+        let mut known: Vec<(usize, Type)> = Vec::new();
+        let mut params: Vec<(usize, u64, u8)> = Vec::new();
+        let mut sets = UnionFind::default();
+        for (function, variables, types) in functions {
+            let mut bases: Vec<_> = types.accesses().collect();
+            bases.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, offsets) in bases {
+                accesses.insert(number((function.address, name.clone())), offsets.clone());
+            }
+            for stmt in function.blocks.iter().flat_map(|b| &b.stmts) {
+                let Stmt::Call { callee: Callee::Function { address, .. }, args, .. } = stmt else {
+                    continue;
+                };
+                let Some(prototype) = prototypes.get(address) else {
+                    continue;
+                };
+                for (arg, &reg) in args.iter().zip(&prototype.params) {
+                    if let Expr::Var(var) = arg {
+                        let name = variables.name(*var);
+                        if let Some(ty @ Type::Ref { .. }) = types.get(&name) {
+                            known.push((number((function.address, name.clone())), ty.clone()));
+                        }
+                        let caller = number((function.address, name));
+                        let callee = number((*address, format!("arg{}", reg)));
+                        params.push((callee, *address, reg));
+                        sets.union(caller, callee);
+                    }
+                }
+            }
+        }
+
+        // Sets holding a pointer of a known type take that type
+        let known: HashMap<usize, Type> = known.into_iter().map(|(pointer, ty)| (sets.find(pointer), ty)).collect();
+
+        // Merge the accesses of each set of pointers
+        let mut merged: BTreeMap<usize, BTreeMap<i64, MemSize>> = BTreeMap::new();
+        for (pointer, offsets) in accesses {
+            let fields = merged.entry(sets.find(pointer)).or_default();
+            for (offset, size) in offsets {
+                let widest = fields.entry(offset).or_insert(size);
+                if size.bits() > widest.bits() {
+                    *widest = size;
+                }
+            }
+        }
+
+        let mut layouts = Layouts::default();
+        let mut ids: HashMap<usize, usize> = HashMap::new();
+        for (set, offsets) in merged {
+            if known.contains_key(&set) || offsets.len() < 2 || offsets.keys().any(|&offset| offset < 0) {
+                continue;
+            }
+            // Accesses overlapping an earlier field are left out of the layout
+            let mut fields = BTreeMap::new();
+            let mut end = 0;
+            for (offset, size) in offsets {
+                if offset >= end {
+                    fields.insert(offset, size);
+                    end = offset + size.bits() as i64 / 8;
+                }
+            }
+            ids.insert(set, layouts.structs.len());
+            layouts.structs.push(fields);
+        }
+        for (pointer, key) in pointers.into_iter().enumerate() {
+            if let Some(&id) = ids.get(&sets.find(pointer)) {
+                layouts.pointers.insert(key, id);
+            }
+        }
+        for (pointer, address, reg) in params {
+            if let Some(ty) = known.get(&sets.find(pointer)) {
+                layouts.params.entry(address).or_default().insert(reg, ty.clone());
+            }
+        }
+        layouts
+    }
+
+    /// Known types of the parameters of the function at `address`
+    pub fn params(&self, address: u64) -> BTreeMap<u8, Type> {
+        self.params.get(&address).cloned().unwrap_or_default()
+    }
+
+    /// Type the pointers of the function at `address` with their structs
+    pub fn apply(&self, address: u64, types: &mut Types) {
+        for ((function, name), &id) in &self.pointers {
+            if *function == address {
+                types.set_layout(name, id);
+            }
+        }
+    }
+
+    /// Name of the field of struct `id` accessed as `size` bytes at `offset`
+    pub fn field(&self, id: usize, offset: i64, size: MemSize) -> Option<String> {
+        (self.structs.get(id)?.get(&offset) == Some(&size)).then(|| field_name(offset))
+    }
+
+    /// Definitions of the inferred structs, padded to their field offsets
+    pub fn definitions(&self) -> String {
+        let mut output = String::new();
+        for (id, fields) in self.structs.iter().enumerate() {
+            output.push_str(&format!("#[repr(C)]\nstruct {} {{\n", struct_name(id)));
+            let mut end = 0;
+            for (&offset, size) in fields {
+                if offset > end {
+                    output.push_str(&format!("    _pad_0x{:x}: [u8; {}],\n", end, offset - end));
+                }
+                output.push_str(&format!("    {}: {},\n", field_name(offset), size.type_name()));
+                end = offset + size.bits() as i64 / 8;
+            }
+            output.push_str("}\n\n");
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use crate::decompiler::prune::eliminate_dead_code;
+    use crate::decompiler::prototypes::infer_prototypes;
+    use crate::decompiler::simplify::simplify;
+    use crate::decompiler::types::TypedNames;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_merge_across_calls() {
+        let program: Vec<u8> = [
+            insn(0x79, 1, 1, 0, 0),     // 0x00: ldxdw r1, [r1]
+            insn(0x61, 2, 1, 32, 0),    // 0x08: ldxw r2, [r1+32]
+            insn(0x7b, 1, 2, 0, 0),     // 0x10: stxdw [r1], r2
+            insn(0x85, 0, 1, 0, 1),     // 0x18: call 0x28
+            insn(0x95, 0, 0, 0, 0),     // 0x20: exit
+            insn(0x71, 0, 1, 4, 0),     // 0x28: ldxb r0, [r1+4]
+            insn(0x72, 1, 0, 40, 1),    // 0x30: stb [r1+40], 1
+            insn(0x95, 0, 0, 0, 0),     // 0x38: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut functions: Vec<SsaFunction> = (0..cfg.functions.len())
+            .map(|index| {
+                let mut function = SsaFunction::build(&cfg, &ir, index);
+                simplify(&mut function);
+                eliminate_dead_code(&mut function, false);
+                function
+            })
+            .collect();
+        let prototypes = infer_prototypes(&mut functions, 0, false);
+        let variables: Vec<Variables> = functions.iter().map(Variables::recover).collect();
+        let mut types: Vec<Types> = functions.iter().zip(&variables).map(|(f, v)| Types::infer(f, v, &BTreeMap::new())).collect();
+        let analyses: Vec<_> = functions.iter().zip(&variables).zip(&types).map(|((f, v), t)| (f, v, t)).collect();
+        let layouts = Layouts::infer(&analyses, &prototypes);
+        for (function, types) in functions.iter().zip(&mut types) {
+            layouts.apply(function.address, types);
+        }
+
+        assert_eq!(
+            layouts.definitions(),
+            "#[repr(C)]\nstruct Struct1 {\n    field_0x0: u64,\n    _pad_0x8: [u8; 24],\n    field_0x20: u32,\n    \
+             _pad_0x24: [u8; 4],\n    field_0x28: u8,\n}\n\n"
+        );
+        assert_eq!(types[1].get("arg1").map(|t| t.to_string()), Some("*mut Struct1".to_string()));
+        let names = TypedNames {
+            variables: &variables[1],
+            types: &types[1],
+            layouts: &layouts,
+        };
+        let rendered: Vec<String> = functions[1].blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&names)).collect();
+        // The byte at 4 overlaps the first field
+        assert_eq!(rendered[0], "result = *((arg1 + 4) as *const u8);");
+        assert_eq!(rendered[1], "arg1.field_0x28 = 1;");
+    }
+
+    #[test]
+    fn test_known_pointer_passed_to_callee() {
+        let program: Vec<u8> = [
+            insn(0x85, 0, 1, 0, 1),     // 0x00: call 0x10
+            insn(0x95, 0, 0, 0, 0),     // 0x08: exit
+            insn(0x79, 0, 1, 0x50, 0),  // 0x10: ldxdw r0, [r1+0x50]
+            insn(0x79, 2, 1, 0x58, 0),  // 0x18: ldxdw r2, [r1+0x58]
+            insn(0x0f, 0, 2, 0, 0),     // 0x20: add r0, r2
+            insn(0x95, 0, 0, 0, 0),     // 0x28: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut functions: Vec<SsaFunction> =
+            (0..cfg.functions.len()).map(|index| SsaFunction::build(&cfg, &ir, index)).collect();
+        let prototypes = infer_prototypes(&mut functions, 0, false);
+        let variables: Vec<Variables> = functions.iter().map(Variables::recover).collect();
+        let types = [
+            Types::infer(&functions[0], &variables[0], &Types::entry_params()),
+            Types::infer(&functions[1], &variables[1], &BTreeMap::new()),
+        ];
+        let analyses: Vec<_> = functions.iter().zip(&variables).zip(&types).map(|((f, v), t)| (f, v, t)).collect();
+        let layouts = Layouts::infer(&analyses, &prototypes);
+
+        // The input buffer gets no struct of its own
+        assert_eq!(layouts.definitions(), "");
+        let params = layouts.params(functions[1].address);
+        assert_eq!(params.get(&1).map(|t| t.to_string()), Some("&Input".to_string()));
+        let types = Types::infer(&functions[1], &variables[1], &params);
+        let names = TypedNames {
+            variables: &variables[1],
+            types: &types,
+            layouts: &layouts,
+        };
+        let rendered: Vec<String> = functions[1].blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&names)).collect();
+        assert_eq!(rendered[0], "var1 = arg1.accounts[0].lamports;");
+    }
+}
@@ -1,109 +1,57 @@
+use crate::decompiler::dataflow::NUM_REGS;
+use crate::decompiler::ir::{BasicBlock, BinaryOp, Condition, Function, IRInstruction, MemSize, Operand, UnaryOp, Width, IR};
+use sbpf_common::binary::sbpf::Opcode;
+use sbpf_common::cfg::ControlFlowGraph;
 use sbpf_common::instruction::Instruction;
-use std::collections::HashMap;
+use sbpf_common::pda::PdaDerivation;
+use sbpf_common::pubkey::Pubkey;
+use sbpf_common::syscall_args::SyscallCall;
+use std::collections::BTreeMap;
 
-/// Intermediate representation for decompilation
-#[derive(Debug, Clone)]
-pub struct IR {
-    pub basic_blocks: Vec<BasicBlock>,
-    #[allow(dead_code)]
-    pub functions: Vec<Function>,
-}
-
-/// Basic block in the IR
-#[derive(Debug, Clone)]
-pub struct BasicBlock {
-    pub address: u64,
-    pub instructions: Vec<IRInstruction>,
-    pub successors: Vec<u64>,
-}
+/// Lift instructions to intermediate representation, placing each PDA
+/// derivation before its syscall, typing syscalls whose arguments were
+/// recovered and loads of pubkey constants
+pub fn lift_to_ir(
+    instructions: &[Instruction],
+    cfg: &ControlFlowGraph,
+    derivations: &BTreeMap<u64, PdaDerivation>,
+    syscalls: &BTreeMap<u64, SyscallCall>,
+    pubkeys: &BTreeMap<u64, Pubkey>,
+) -> IR {
 
-/// Function in the IR
-#[derive(Debug, Clone)]
-pub struct Function {
-    #[allow(dead_code)]
-    pub address: u64,
-    #[allow(dead_code)]
-    pub name: String,
-    #[allow(dead_code)]
-    pub basic_blocks: Vec<u64>,
-}
-
-/// IR instruction representation
-#[derive(Debug, Clone)]
-pub enum IRInstruction {
-    Load { dst: u8, src: u8, offset: i16 },
-    Store { dst: u8, src: u8, offset: i16 },
-    Arithmetic { op: String, dst: u8, src: u8, imm: Option<i64> },
-    Logic { op: String, dst: u8, src: u8, imm: Option<i64> },
-    Jump { cond: Option<String>, target: u64 },
-    Call { target: i64, is_syscall: bool },
-    Return,
-    Exit,
-}
-
-/// Lift instructions to intermediate representation
-pub fn lift_to_ir(instructions: &[Instruction]) -> IR {
-    let mut basic_blocks = Vec::new();
-    let mut functions = Vec::new();
-    let mut block_map: HashMap<u64, usize> = HashMap::new();
-    
-    // Identify basic blocks (simplified - starts at entry and after jumps)
-    let mut block_starts = vec![0u64];
-    for (i, inst) in instructions.iter().enumerate() {
-        if matches!(inst.category(), sbpf_common::instruction::InstructionCategory::ControlFlow) {
-            if let Some(target) = calculate_jump_target(inst) {
-                if !block_starts.contains(&target) {
-                    block_starts.push(target);
-                }
-            }
-            if i + 1 < instructions.len() {
-                block_starts.push(instructions[i + 1].address);
-            }
-        }
-    }
-    block_starts.sort();
-    block_starts.dedup();
-    
     // Create basic blocks
-    for (idx, &start_addr) in block_starts.iter().enumerate() {
-        let end_addr = if idx + 1 < block_starts.len() {
-            block_starts[idx + 1]
-        } else {
-            instructions.last().map(|i| i.address + i.size as u64).unwrap_or(start_addr)
-        };
-        
-        let mut block_instructions = Vec::new();
-        let mut successors = Vec::new();
-        
-        for inst in instructions {
-            if inst.address >= start_addr && inst.address < end_addr {
-                block_instructions.push(lift_instruction(inst));
-                
-                // Track successors
-                if let Some(target) = calculate_jump_target(inst) {
-                    successors.push(target);
-                }
-            }
+    let basic_blocks = cfg.blocks.iter().map(|block| {
+        BasicBlock {
+            address: block.start,
+            instructions: instructions[block.instructions.clone()]
+                .iter()
+                .flat_map(|inst| {
+                    let derivation = derivations.get(&inst.address).cloned().map(IRInstruction::Pda);
+                    let lifted = match lift_instruction(inst) {
+                        IRInstruction::Syscall { hash, .. } => IRInstruction::Syscall {
+                            hash,
+                            recovered: syscalls
+                                .get(&inst.address)
+                                .map(|call| call.args.iter().map(|(_, value)| value.clone()).collect()),
+                        },
+                        IRInstruction::LoadImm { dst, value } => match pubkeys.get(&inst.address) {
+                            Some(&pubkey) => IRInstruction::LoadPubkey { dst, pubkey },
+                            None => IRInstruction::LoadImm { dst, value },
+                        },
+                        lifted => lifted,
+                    };
+                    derivation.into_iter().chain(std::iter::once(lifted))
+                })
+                .collect(),
         }
-        
-        let block_idx = basic_blocks.len();
-        block_map.insert(start_addr, block_idx);
-        
-        basic_blocks.push(BasicBlock {
-            address: start_addr,
-            instructions: block_instructions,
-            successors,
-        });
-    }
+    }).collect();
     
-    // Identify functions (simplified - entry point and call targets)
-    if !instructions.is_empty() {
-        functions.push(Function {
-            address: instructions[0].address,
-            name: "entry".to_string(),
-            basic_blocks: vec![0],
-        });
-    }
+    let functions = cfg.functions.iter().map(|function| Function {
+        address: function.address,
+        name: function.name.clone(),
+        basic_blocks: function.blocks.iter().map(|&b| cfg.blocks[b].start).collect(),
+        library: function.library,
+    }).collect();
     
     IR {
         basic_blocks,
@@ -111,88 +59,208 @@ pub fn lift_to_ir(instructions: &[Instruction]) -> IR {
     }
 }
 
-fn lift_instruction(inst: &Instruction) -> IRInstruction {
+/// Comparison performed by a conditional jump opcode
+pub fn condition(opcode: Opcode) -> Option<Condition> {
+    Some(match opcode {
+        Opcode::Jeq => Condition::Eq,
+        Opcode::Jne => Condition::Ne,
+        Opcode::Jgt => Condition::Gt,
+        Opcode::Jge => Condition::Ge,
+        Opcode::Jlt => Condition::Lt,
+        Opcode::Jle => Condition::Le,
+        Opcode::Jsgt => Condition::Sgt,
+        Opcode::Jsge => Condition::Sge,
+        Opcode::Jslt => Condition::Slt,
+        Opcode::Jsle => Condition::Sle,
+        Opcode::Jset => Condition::Set,
+        _ => return None,
+    })
+}
+
+/// Source operand of an ALU or jump instruction
+fn source(inst: &Instruction) -> Operand {
+    if inst.uses_src_reg() {
+        Operand::Reg(inst.src_reg)
+    } else {
+        Operand::Imm(inst.imm)
+    }
+}
+
+/// Lower a decoded instruction to its IR operation; instructions naming a
+/// register above r10 are invalid
+pub fn lift_instruction(inst: &Instruction) -> IRInstruction {
+    if inst.dst_reg >= NUM_REGS || inst.src_reg >= NUM_REGS {
+        return IRInstruction::Invalid { code: inst.code };
+    }
+    let width = if inst.is_alu32() { Width::W32 } else { Width::W64 };
+    let size = MemSize::from_bytes(inst.mem_size().unwrap_or(8));
+    let binary = |op| IRInstruction::Binary {
+        op,
+        dst: inst.dst_reg,
+        src: source(inst),
+        width,
+    };
     match inst.opcode {
-        sbpf_common::binary::sbpf::Opcode::Ldx => {
-            IRInstruction::Load {
-                dst: inst.dst_reg,
-                src: inst.src_reg,
-                offset: inst.off,
-            }
-        }
-        sbpf_common::binary::sbpf::Opcode::Stx => {
-            IRInstruction::Store {
-                dst: inst.dst_reg,
-                src: inst.src_reg,
-                offset: inst.off,
-            }
+        Opcode::Lddw => IRInstruction::LoadImm {
+            dst: inst.dst_reg,
+            value: inst.imm as u64,
+        },
+        Opcode::Ldx => IRInstruction::Load {
+            dst: inst.dst_reg,
+            base: inst.src_reg,
+            offset: inst.off,
+            size,
+        },
+        Opcode::St => IRInstruction::Store {
+            base: inst.dst_reg,
+            offset: inst.off,
+            value: Operand::Imm(inst.imm),
+            size,
+        },
+        Opcode::Stx => IRInstruction::Store {
+            base: inst.dst_reg,
+            offset: inst.off,
+            value: Operand::Reg(inst.src_reg),
+            size,
+        },
+        Opcode::LdAbs => IRInstruction::LoadPacket {
+            index: None,
+            offset: inst.imm,
+            size,
+        },
+        Opcode::LdInd => IRInstruction::LoadPacket {
+            index: Some(inst.src_reg),
+            offset: inst.imm,
+            size,
+        },
+        Opcode::Mov => IRInstruction::Move {
+            dst: inst.dst_reg,
+            src: source(inst),
+            width,
+        },
+        Opcode::Add => binary(BinaryOp::Add),
+        Opcode::Sub => binary(BinaryOp::Sub),
+        Opcode::Mul => binary(BinaryOp::Mul),
+        Opcode::Div => binary(BinaryOp::Div),
+        Opcode::Mod => binary(BinaryOp::Mod),
+        Opcode::Or => binary(BinaryOp::Or),
+        Opcode::And => binary(BinaryOp::And),
+        Opcode::Xor => binary(BinaryOp::Xor),
+        Opcode::Lsh => binary(BinaryOp::Lsh),
+        Opcode::Rsh => binary(BinaryOp::Rsh),
+        Opcode::Arsh => binary(BinaryOp::Arsh),
+        Opcode::Neg => IRInstruction::Unary {
+            op: UnaryOp::Neg,
+            dst: inst.dst_reg,
+            width,
+        },
+        Opcode::Le | Opcode::Be => IRInstruction::Unary {
+            op: match inst.opcode {
+                Opcode::Le => UnaryOp::ToLe(inst.imm as u8),
+                _ => UnaryOp::ToBe(inst.imm as u8),
+            },
+            dst: inst.dst_reg,
+            width: Width::W64,
+        },
+        Opcode::Ja => IRInstruction::Jump {
+            target: inst.jump_target().unwrap_or_default(),
+        },
+        Opcode::Jeq | Opcode::Jne | Opcode::Jgt | Opcode::Jge | Opcode::Jlt | Opcode::Jle
+        | Opcode::Jsgt | Opcode::Jsge | Opcode::Jslt | Opcode::Jsle | Opcode::Jset => IRInstruction::Branch {
+            cond: condition(inst.opcode).expect("conditional jump"),
+            lhs: inst.dst_reg,
+            rhs: source(inst),
+            target: inst.jump_target().unwrap_or_default(),
+        },
+        Opcode::Call => match inst.call_target() {
+            Some(target) => IRInstruction::Call { target },
+            None => IRInstruction::Syscall {
+                hash: inst.imm as u32,
+                recovered: None,
+            },
+        },
+        // The register is in the immediate before SBPF v2 and in src after
+        Opcode::Callx => match if inst.imm != 0 { inst.imm } else { inst.src_reg as i64 } {
+            reg @ 0..=10 => IRInstruction::CallIndirect { reg: reg as u8 },
+            _ => IRInstruction::Invalid { code: inst.code },
+        },
+        Opcode::Exit => IRInstruction::Exit,
+        Opcode::Unknown(code) => IRInstruction::Invalid { code },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    fn lift(bytes: &[u8]) -> IRInstruction {
+        let inst = InstructionDecoder::new(None).decode_at(bytes, 0).unwrap();
+        lift_instruction(&inst)
+    }
+
+    #[test]
+    fn test_lift_total() {
+        // Every opcode byte lowers to some operation, invalid bytes included
+        for code in 0..=u8::MAX {
+            let mut bytes = [0u8; 16];
+            bytes[0] = code;
+            let inst = InstructionDecoder::new(None).decode_at(&bytes, 0).unwrap();
+            let lifted = lift_instruction(&inst);
+            assert_eq!(matches!(lifted, IRInstruction::Invalid { .. }), matches!(inst.opcode, Opcode::Unknown(_)));
         }
-        sbpf_common::binary::sbpf::Opcode::Add | sbpf_common::binary::sbpf::Opcode::Sub
-        | sbpf_common::binary::sbpf::Opcode::Mul | sbpf_common::binary::sbpf::Opcode::Div
-        | sbpf_common::binary::sbpf::Opcode::Mod | sbpf_common::binary::sbpf::Opcode::Mov => {
-            IRInstruction::Arithmetic {
-                op: format!("{:?}", inst.opcode).to_lowercase(),
-                dst: inst.dst_reg,
-                src: inst.src_reg,
-                imm: if inst.imm != 0 { Some(inst.imm) } else { None },
+    }
+
+    #[test]
+    fn test_lift_operations() {
+        assert_eq!(
+            lift(&[0x18, 0x01, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            IRInstruction::LoadImm { dst: 1, value: 0x10 }
+        );
+        assert_eq!(
+            lift(&[0xc4, 0x02, 0, 0, 3, 0, 0, 0]),
+            IRInstruction::Binary {
+                op: BinaryOp::Arsh,
+                dst: 2,
+                src: Operand::Imm(3),
+                width: Width::W32,
             }
-        }
-        sbpf_common::binary::sbpf::Opcode::Or | sbpf_common::binary::sbpf::Opcode::And
-        | sbpf_common::binary::sbpf::Opcode::Xor | sbpf_common::binary::sbpf::Opcode::Lsh
-        | sbpf_common::binary::sbpf::Opcode::Rsh => {
-            IRInstruction::Logic {
-                op: format!("{:?}", inst.opcode).to_lowercase(),
-                dst: inst.dst_reg,
-                src: inst.src_reg,
-                imm: if inst.imm != 0 { Some(inst.imm) } else { None },
+        );
+        assert_eq!(
+            lift(&[0x72, 0x0a, 0xf8, 0xff, 1, 0, 0, 0]),
+            IRInstruction::Store {
+                base: 10,
+                offset: -8,
+                value: Operand::Imm(1),
+                size: MemSize::U8,
             }
-        }
-        sbpf_common::binary::sbpf::Opcode::Call => {
-            IRInstruction::Call {
-                target: inst.imm,
-                is_syscall: inst.is_syscall(),
+        );
+        assert_eq!(
+            lift(&[0x4d, 0x21, 2, 0, 0, 0, 0, 0]),
+            IRInstruction::Branch {
+                cond: Condition::Set,
+                lhs: 1,
+                rhs: Operand::Reg(2),
+                target: 0x18,
             }
-        }
-        sbpf_common::binary::sbpf::Opcode::Exit => {
-            IRInstruction::Exit
-        }
-        _ => {
-            // For jumps, we'll handle them in the basic block structure
-            if let Some(target) = calculate_jump_target(inst) {
-                let cond = match inst.opcode {
-                    sbpf_common::binary::sbpf::Opcode::Jeq => Some("==".to_string()),
-                    sbpf_common::binary::sbpf::Opcode::Jne => Some("!=".to_string()),
-                    sbpf_common::binary::sbpf::Opcode::Jgt => Some(">".to_string()),
-                    sbpf_common::binary::sbpf::Opcode::Jge => Some(">=".to_string()),
-                    sbpf_common::binary::sbpf::Opcode::Jlt => Some("<".to_string()),
-                    sbpf_common::binary::sbpf::Opcode::Jle => Some("<=".to_string()),
-                    _ => None,
-                };
-                IRInstruction::Jump { cond, target }
-            } else {
-                IRInstruction::Return
+        );
+        assert_eq!(
+            lift(&[0xdc, 0x03, 0, 0, 16, 0, 0, 0]),
+            IRInstruction::Unary {
+                op: UnaryOp::ToBe(16),
+                dst: 3,
+                width: Width::W64,
             }
-        }
+        );
     }
-}
 
-fn calculate_jump_target(inst: &Instruction) -> Option<u64> {
-    match inst.category() {
-        sbpf_common::instruction::InstructionCategory::ControlFlow => {
-            if matches!(inst.opcode, sbpf_common::binary::sbpf::Opcode::Ja) {
-                Some((inst.address as i64 + inst.imm + 1) as u64)
-            } else if matches!(
-                inst.opcode,
-                sbpf_common::binary::sbpf::Opcode::Jeq | sbpf_common::binary::sbpf::Opcode::Jne
-                | sbpf_common::binary::sbpf::Opcode::Jgt | sbpf_common::binary::sbpf::Opcode::Jge
-                | sbpf_common::binary::sbpf::Opcode::Jlt | sbpf_common::binary::sbpf::Opcode::Jle
-            ) {
-                Some((inst.address as i64 + inst.imm + 1) as u64)
-            } else {
-                None
-            }
-        }
-        _ => None,
+    #[test]
+    fn test_lift_invalid_registers() {
+        // mov r11, 1
+        assert_eq!(lift(&[0xb7, 0x0b, 0, 0, 1, 0, 0, 0]), IRInstruction::Invalid { code: 0xb7 });
+        // mov r0, r12
+        assert_eq!(lift(&[0xbf, 0xc0, 0, 0, 0, 0, 0, 0]), IRInstruction::Invalid { code: 0xbf });
+        // callx r11
+        assert_eq!(lift(&[0x8d, 0x00, 0, 0, 11, 0, 0, 0]), IRInstruction::Invalid { code: 0x8d });
     }
 }
-
@@ -1,25 +1,126 @@
-pub mod disasm;
+pub mod dataflow;
+pub mod ir;
+pub mod layouts;
 pub mod lifter;
+pub mod prototypes;
+pub mod prune;
 pub mod pseudocode;
+pub mod simplify;
+pub mod ssa;
+pub mod structure;
+pub mod types;
+pub mod variables;
 
 use sbpf_common::binary::SbpfBinary;
+use sbpf_common::cfg::ControlFlowGraph;
+use sbpf_common::dataflow::Dataflow;
+use sbpf_common::dispatch;
+use sbpf_common::pda;
+use sbpf_common::pubkey;
+use sbpf_common::syscall_args;
 use sbpf_common::instruction::InstructionDecoder;
-use crate::decompiler::disasm::disassemble;
+use sbpf_common::signatures::SignatureDb;
+use sbpf_common::instruction::disasm::{annotate, disassemble};
 use crate::decompiler::lifter::lift_to_ir;
+use crate::decompiler::prototypes::infer_prototypes;
+use crate::decompiler::prune::eliminate_dead_code;
 use crate::decompiler::pseudocode::generate_pseudocode;
+use crate::decompiler::simplify::simplify;
+use crate::decompiler::ssa::SsaFunction;
+use crate::decompiler::structure::StructuredFunction;
+use std::collections::BTreeMap;
 
-/// Decompile an SBPF binary to Rust-like pseudocode
-pub fn decompile(binary: &SbpfBinary) -> String {
+/// Inputs and switches for decompilation
+#[derive(Debug, Clone, Default)]
+pub struct DecompileOptions {
+    /// Known library function signatures
+    pub signatures: SignatureDb,
+    /// Print the SSA form of each program function instead of pseudocode
+    pub dump_ssa: bool,
+    /// List dead statements and unreachable blocks as comments instead of
+    /// dropping them
+    pub keep_pruned: bool,
+}
+
+/// Decompile an SBPF binary to Rust-like pseudocode, omitting the bodies of
+/// library functions found in the signatures and naming instruction handlers
+pub fn decompile(binary: &SbpfBinary, options: &DecompileOptions) -> String {
     let decoder = InstructionDecoder::new(binary.version);
     let instructions = decoder.decode_all(&binary.bytecode);
     
     // Disassemble
-    let assembly = disassemble(&instructions);
+    let mut assembly = disassemble(&instructions);
+    
+    // Identify functions and library code
+    let mut cfg = ControlFlowGraph::build_with_symbols(&instructions, binary.entry_offset(), &binary.symbols);
+    options.signatures.label(&mut cfg, &instructions);
+    
+    // Name instruction handlers after their dispatch tag
+    let dataflow = Dataflow::compute(&cfg, &instructions, binary.entry_offset());
+    let mut dispatch = dispatch::recover(&cfg, &instructions, &dataflow);
+    if let Some(dispatch) = &mut dispatch {
+        dispatch.name_handlers(&mut cfg);
+    }
+    
+    // Annotate PDA derivations with their seeds
+    let derivations: BTreeMap<_, _> = pda::recover(&cfg, &instructions, &dataflow, binary, dispatch.as_ref())
+        .into_iter()
+        .map(|derivation| (derivation.address, derivation))
+        .collect();
+    
+    // Name pubkey constants where their address is loaded
+    let mut pubkeys = BTreeMap::new();
+    for constant in pubkey::recover(&cfg, &instructions, &dataflow, binary) {
+        for &reference in &constant.references {
+            pubkeys.insert(reference, constant.pubkey);
+        }
+    }
+    let names = pubkeys.iter().map(|(&address, key)| (address, key.to_string())).collect();
+    annotate(&mut assembly, &names);
+    
+    // Type syscalls with their recovered arguments
+    let syscalls: BTreeMap<_, _> = syscall_args::recover(&cfg, &instructions, &dataflow, binary)
+        .into_iter()
+        .map(|call| (call.address, call))
+        .collect();
     
     // Lift to IR
-    let ir = lift_to_ir(&instructions);
+    let ir = lift_to_ir(&instructions, &cfg, &derivations, &syscalls, &pubkeys);
     
-    // Generate pseudocode
-    generate_pseudocode(&ir, &assembly)
+    // Convert program functions to SSA form, simplify their expressions,
+    // remove dead definitions and narrow calls to the callees' parameters
+    let mut functions: Vec<SsaFunction> = (0..cfg.functions.len())
+        .filter(|&index| !cfg.functions[index].library)
+        .map(|index| {
+            let mut function = SsaFunction::build(&cfg, &ir, index);
+            simplify(&mut function);
+            eliminate_dead_code(&mut function, options.keep_pruned);
+            function
+        })
+        .collect();
+    let prototypes = infer_prototypes(&mut functions, binary.entry_offset(), options.keep_pruned);
+    if options.dump_ssa {
+        return functions.iter().map(|f| f.dump()).collect::<Vec<_>>().join("\n");
+    }
+    
+    // Structure control flow and generate pseudocode
+    let structured: Vec<StructuredFunction> = (0..cfg.functions.len())
+        .filter(|&index| !cfg.functions[index].library)
+        .zip(functions)
+        .map(|(index, function)| StructuredFunction::new(&cfg, &instructions, index, function))
+        .collect();
+    generate_pseudocode(&ir, &structured, &prototypes, &assembly, binary.entry_offset(), options.keep_pruned)
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    /// Encode a single instruction slot
+    pub(crate) fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[0] = code;
+        bytes[1] = (src << 4) | dst;
+        bytes[2..4].copy_from_slice(&off.to_le_bytes());
+        bytes[4..8].copy_from_slice(&imm.to_le_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,172 @@
+// Function prototypes
+//
+// A function's parameters are the argument registers r1-r5 whose values on
+// entry it needs, and it has a result when r0 is defined at some exit. Both
+// depend on its callees, since passing a register to a function that ignores
+// it is not a use, so all prototypes are narrowed together until nothing
+// changes. Call sites then pass only the parameters and take a result only
+// from functions that have one.
+
+use crate::decompiler::prune::{eliminate_dead_code, live_variables};
+use crate::decompiler::ssa::{Callee, Expr, SsaFunction, Stmt, Var};
+use std::collections::{HashMap, HashSet};
+
+/// Parameters and result of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prototype {
+    /// Argument registers read before they are written
+    pub params: Vec<u8>,
+    /// Whether r0 carries a result at exit
+    pub returns: bool,
+}
+
+impl Prototype {
+    /// Whether argument `index` of a call, before trimming, is a parameter
+    fn takes(&self, index: usize) -> bool {
+        self.params.contains(&(index as u8 + 1))
+    }
+}
+
+/// Infer the prototypes of `functions` by address and trim their call sites
+/// and returns to match, pruning definitions that were only passed along;
+/// the entrypoint always returns r0
+pub fn infer_prototypes(functions: &mut [SsaFunction], entry: u64, keep_pruned: bool) -> HashMap<u64, Prototype> {
+    let mut prototypes: HashMap<u64, Prototype> = functions
+        .iter()
+        .map(|f| {
+            let prototype = Prototype {
+                params: (1..=5).collect(),
+                returns: true,
+            };
+            (f.address, prototype)
+        })
+        .collect();
+    loop {
+        let next = functions
+            .iter()
+            .map(|f| (f.address, prototype(f, &prototypes, f.address == entry)))
+            .collect();
+        if next == prototypes {
+            break;
+        }
+        prototypes = next;
+    }
+
+    for function in functions.iter_mut() {
+        let returns = prototypes[&function.address].returns;
+        for stmt in function.blocks.iter_mut().flat_map(|b| &mut b.stmts) {
+            match stmt {
+                Stmt::Call { callee: Callee::Function { address, .. }, args, ret, .. } => {
+                    let Some(callee) = prototypes.get(address) else {
+                        continue;
+                    };
+                    *args = std::mem::take(args)
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, _)| callee.takes(*i))
+                        .map(|(_, arg)| arg)
+                        .collect();
+                    if !callee.returns {
+                        *ret = None;
+                    }
+                }
+                Stmt::Return { value } if !returns => *value = None,
+                _ => {}
+            }
+        }
+        eliminate_dead_code(function, keep_pruned);
+    }
+    prototypes
+}
+
+/// Prototype of `function` given the current prototypes of its callees
+fn prototype(function: &SsaFunction, prototypes: &HashMap<u64, Prototype>, entry: bool) -> Prototype {
+    let stmts = || function.blocks.iter().flat_map(|b| &b.stmts);
+    // Results of calls to functions without one are undefined
+    let undefined: HashSet<Var> = stmts()
+        .filter_map(|stmt| match stmt {
+            Stmt::Call { callee: Callee::Function { address, .. }, ret: Some(ret), .. }
+                if prototypes.get(address).is_some_and(|p| !p.returns) =>
+            {
+                Some(*ret)
+            }
+            _ => None,
+        })
+        .chain(std::iter::once(Var { reg: 0, version: 0 }))
+        .collect();
+    let returns = entry
+        || stmts().any(|stmt| match stmt {
+            Stmt::Return { value: Some(Expr::Var(var)) } => !undefined.contains(var),
+            Stmt::Return { value } => value.is_some(),
+            _ => false,
+        });
+
+    let live = live_variables(function, |stmt| match stmt {
+        Stmt::Call { callee: Callee::Function { address, .. }, args, .. } if prototypes.contains_key(address) => {
+            let callee = &prototypes[address];
+            args.iter()
+                .enumerate()
+                .filter(|(i, _)| callee.takes(*i))
+                .flat_map(|(_, arg)| arg.vars())
+                .collect()
+        }
+        Stmt::Return { .. } if !returns => Vec::new(),
+        stmt => stmt.uses(),
+    });
+    Prototype {
+        params: (1..=5).filter(|&reg| live.contains(&Var { reg, version: 0 })).collect(),
+        returns,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use crate::decompiler::simplify::simplify;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_params_and_result() {
+        let program: Vec<u8> = [
+            insn(0xb7, 2, 0, 0, 7),     // 0x00: mov r2, 7
+            insn(0x85, 0, 1, 0, 5),     // 0x08: call 0x38
+            insn(0xbf, 1, 0, 0, 0),     // 0x10: mov r1, r0
+            insn(0xb7, 3, 0, 0, 9),     // 0x18: mov r3, 9
+            insn(0x85, 0, 1, 0, 5),     // 0x20: call 0x50
+            insn(0xb7, 0, 0, 0, 0),     // 0x28: mov r0, 0
+            insn(0x95, 0, 0, 0, 0),     // 0x30: exit
+            insn(0xbf, 0, 2, 0, 0),     // 0x38: mov r0, r2
+            insn(0x0f, 0, 1, 0, 0),     // 0x40: add r0, r1
+            insn(0x95, 0, 0, 0, 0),     // 0x48: exit
+            insn(0x7b, 1, 3, 0, 0),     // 0x50: stxdw [r1], r3
+            insn(0x95, 0, 0, 0, 0),     // 0x58: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut functions: Vec<SsaFunction> = (0..cfg.functions.len())
+            .map(|index| {
+                let mut function = SsaFunction::build(&cfg, &ir, index);
+                simplify(&mut function);
+                eliminate_dead_code(&mut function, false);
+                function
+            })
+            .collect();
+        let prototypes = infer_prototypes(&mut functions, 0, false);
+
+        assert_eq!(prototypes[&0x38], Prototype { params: vec![1, 2], returns: true });
+        assert_eq!(prototypes[&0x50], Prototype { params: vec![1, 3], returns: false });
+        // r1 is passed through to the first call; r4 and r5 are never read
+        assert_eq!(prototypes[&0], Prototype { params: vec![1], returns: true });
+        let name = |var: Var| format!("r{}", var.reg);
+        let rendered: Vec<String> = functions[0].blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&name)).collect();
+        assert_eq!(rendered, vec!["r0 = func_0x38(r1, 7);", "func_0x50(r0, 9);", "return 0;"]);
+        let rendered: Vec<String> = functions[2].blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&name)).collect();
+        assert_eq!(rendered, vec!["*(r1 as *mut u64) = r3;", "return;"]);
+    }
+}
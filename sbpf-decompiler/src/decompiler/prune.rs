@@ -0,0 +1,107 @@
+// Dead code elimination over SSA form
+//
+// A variable is live if a statement with an effect (store, call, branch or
+// return) reads it, or a live definition does. Definitions and phis of
+// variables that are not live are removed, and calls whose result is unused
+// drop their assignment.
+
+use crate::decompiler::ssa::{SsaFunction, Stmt, Var};
+use std::collections::{HashMap, HashSet};
+
+/// Remove dead definitions from `function`; with `keep` they are left in
+/// place as pruned statements so they can still be listed
+pub fn eliminate_dead_code(function: &mut SsaFunction, keep: bool) {
+    let live = live_variables(function, Stmt::uses);
+    for block in &mut function.blocks {
+        block.phis.retain(|phi| live.contains(&phi.dst));
+        for stmt in &mut block.stmts {
+            match stmt {
+                Stmt::Assign { dst, .. } if !live.contains(dst) => {
+                    *stmt = Stmt::Pruned(Box::new(stmt.clone()));
+                }
+                Stmt::Call { ret, .. } if ret.is_some_and(|r| !live.contains(&r)) => *ret = None,
+                _ => {}
+            }
+        }
+        if !keep {
+            block.stmts.retain(|stmt| !matches!(stmt, Stmt::Pruned(_)));
+        }
+    }
+}
+
+/// Variables whose values are needed, starting from the variables `roots`
+/// takes to be read by each statement with an effect
+pub fn live_variables(function: &SsaFunction, roots: impl Fn(&Stmt) -> Vec<Var>) -> HashSet<Var> {
+    // Variables read by the definition of each variable
+    let mut inputs: HashMap<Var, Vec<Var>> = HashMap::new();
+    let mut worklist: Vec<Var> = Vec::new();
+    for block in &function.blocks {
+        for phi in &block.phis {
+            inputs.insert(phi.dst, phi.args.iter().map(|(_, var)| *var).collect());
+        }
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Assign { dst, expr } => {
+                    inputs.insert(*dst, expr.vars());
+                }
+                stmt => worklist.extend(roots(stmt)),
+            }
+        }
+    }
+
+    let mut live: HashSet<Var> = HashSet::new();
+    while let Some(var) = worklist.pop() {
+        if live.insert(var) {
+            worklist.extend(inputs.get(&var).into_iter().flatten());
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_dead_loop_counter() {
+        let program: Vec<u8> = [
+            insn(0xbf, 6, 1, 0, 0),     // 0x00: mov r6, r1
+            insn(0xb7, 5, 0, 0, 0),     // 0x08: mov r5, 0
+            insn(0x07, 5, 0, 0, 1),     // 0x10: add r5, 1
+            insn(0x79, 2, 1, 0, 0),     // 0x18: ldxdw r2, [r1]
+            insn(0x55, 2, 0, -3, 0),    // 0x20: jne r2, 0, 0x10
+            insn(0xb7, 0, 0, 0, 0),     // 0x28: mov r0, 0
+            insn(0x95, 0, 0, 0, 0),     // 0x30: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut function = SsaFunction::build(&cfg, &ir, 0);
+        let mut kept = function.clone();
+        eliminate_dead_code(&mut function, false);
+        eliminate_dead_code(&mut kept, true);
+
+        let name = |var: Var| format!("r{}", var.reg);
+        let render = |f: &SsaFunction| -> Vec<String> {
+            f.blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&name)).collect()
+        };
+        // The counter in r5 is only read by its own update
+        assert_eq!(
+            render(&function),
+            vec![
+                "r2 = *(r1 as *const u64);",
+                "if r2 != 0 { goto 0x10; }",
+                "r0 = 0;",
+                "return r0;",
+            ]
+        );
+        assert!(function.blocks.iter().all(|b| b.phis.is_empty()));
+        assert_eq!(render(&kept)[0], "// dead: r6 = r1;");
+    }
+}
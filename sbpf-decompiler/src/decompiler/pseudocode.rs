@@ -1,44 +1,128 @@
-use crate::decompiler::lifter::{IR, IRInstruction};
-use crate::decompiler::disasm::DisassembledInstruction;
+use crate::decompiler::ir::{render_syscall, syscall_hash_signature, BinaryOp, IRInstruction, Operand, UnaryOp, Width, IR};
+use crate::decompiler::layouts::Layouts;
+use crate::decompiler::prototypes::Prototype;
+use crate::decompiler::ssa::{Expr, Names, Stmt, Var};
+use crate::decompiler::structure::{Node, StructuredFunction};
+use crate::decompiler::types::{TypedNames, Types};
+use crate::decompiler::variables::Variables;
+use sbpf_common::instruction::disasm::DisassembledInstruction;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-/// Generate Rust-like pseudocode from IR
-pub fn generate_pseudocode(ir: &IR, assembly: &[DisassembledInstruction]) -> String {
+/// Generate Rust-like pseudocode from IR, rendering each program function
+/// from its structured SSA form with its prototype; blocks no function
+/// reaches are listed as comments with `keep_pruned` and dropped otherwise
+pub fn generate_pseudocode(
+    ir: &IR,
+    functions: &[StructuredFunction],
+    prototypes: &HashMap<u64, Prototype>,
+    assembly: &[DisassembledInstruction],
+    entry: u64,
+    keep_pruned: bool,
+) -> String {
     let mut output = String::new();
     
     output.push_str("// Decompiled SBPF Program\n");
     output.push_str("// Generated pseudocode\n\n");
     
-    // Generate function for entry point
-    output.push_str("fn entry() -> Result<(), Error> {\n");
+    // Recover variables and their types, then structs across functions
+    let entry_params = |function: &StructuredFunction| {
+        if function.ssa.address == entry {
+            Types::entry_params()
+        } else {
+            BTreeMap::new()
+        }
+    };
+    let mut analyses: Vec<(Variables, Types)> = functions
+        .iter()
+        .map(|function| {
+            let variables = Variables::recover(&function.ssa);
+            let types = Types::infer(&function.ssa, &variables, &entry_params(function));
+            (variables, types)
+        })
+        .collect();
+    let layouts = Layouts::infer(
+        &functions
+            .iter()
+            .zip(&analyses)
+            .map(|(function, (variables, types))| (&function.ssa, variables, types))
+            .collect::<Vec<_>>(),
+        prototypes,
+    );
+    for (function, (variables, types)) in functions.iter().zip(&mut analyses) {
+        // Parameters receiving pointers of known types are typed again
+        let params = layouts.params(function.ssa.address);
+        if !params.is_empty() {
+            let mut known = entry_params(function);
+            known.extend(params);
+            *types = Types::infer(&function.ssa, variables, &known);
+        }
+        layouts.apply(function.ssa.address, types);
+    }
+    output.push_str(&layouts.definitions());
     
-    // Generate code for each basic block
-    for (idx, block) in ir.basic_blocks.iter().enumerate() {
-        if idx > 0 {
-            output.push_str(&format!("\n    // Basic block at 0x{:x}\n", block.address));
-        }
-        
-        // Generate code for instructions in block
-        for inst in &block.instructions {
-            output.push_str(&format!("    {}", generate_instruction_code(inst)));
-            output.push_str("\n");
-        }
-        
-        // Handle control flow
-        if !block.successors.is_empty() {
-            if block.successors.len() == 1 {
-                output.push_str(&format!("    // Jump to 0x{:x}\n", block.successors[0]));
-            } else {
-                output.push_str("    // Conditional jump\n");
+    // Blocks only reachable from library functions are not emitted
+    let program_blocks: HashSet<u64> = ir.functions
+        .iter()
+        .filter(|f| !f.library)
+        .flat_map(|f| f.basic_blocks.iter().copied())
+        .collect();
+    let library_blocks: HashSet<u64> = ir.functions
+        .iter()
+        .filter(|f| f.library)
+        .flat_map(|f| f.basic_blocks.iter().copied())
+        .filter(|b| !program_blocks.contains(b))
+        .collect();
+    let structured: HashMap<u64, (&StructuredFunction, &(Variables, Types))> =
+        functions.iter().zip(&analyses).map(|(f, analysis)| (f.ssa.address, (f, analysis))).collect();
+    
+    // Emit each function at its first block, in address order
+    for block in &ir.basic_blocks {
+        if library_blocks.contains(&block.address) {
+            if let Some(function) = ir.functions.iter().find(|f| f.address == block.address) {
+                output.push_str(&format!(
+                    "// Library function {} at 0x{:x} ({} blocks omitted)\n\n",
+                    function.name, function.address, function.basic_blocks.len()
+                ));
             }
+            continue;
         }
+        if !program_blocks.contains(&block.address) {
+            if keep_pruned {
+                output.push_str(&format!("// Unreachable block at 0x{:x}\n", block.address));
+                for inst in &block.instructions {
+                    output.push_str(&format!("// {}\n", generate_instruction_code(inst, ir)));
+                }
+                output.push('\n');
+            }
+            continue;
+        }
+        let Some(&(function, (variables, types))) = structured.get(&block.address) else {
+            continue;
+        };
+        output.push_str(&format!("// Function {} at 0x{:x}\n", function.ssa.name, function.ssa.address));
+        output.push_str(&format!("{} {{\n", signature(function, prototypes, variables, types)));
+        for declaration in types.declarations(&function.ssa, variables) {
+            output.push_str(&format!("    {}\n", declaration));
+        }
+        let names = TypedNames {
+            variables,
+            types,
+            layouts: &layouts,
+        };
+        render_nodes(function, &function.body, &names, 1, &mut output);
+        output.push_str("}\n\n");
     }
     
-    output.push_str("}\n\n");
-    
     // Add assembly comments for reference
     output.push_str("// Assembly reference:\n");
     for asm in assembly.iter().take(20) {
-        output.push_str(&format!("// 0x{:x}: {} {}\n", asm.address, asm.mnemonic, asm.operands));
+        match &asm.comment {
+            Some(comment) => output.push_str(&format!(
+                "// 0x{:x}: {} {} ; {}\n",
+                asm.address, asm.mnemonic, asm.operands, comment
+            )),
+            None => output.push_str(&format!("// 0x{:x}: {} {}\n", asm.address, asm.mnemonic, asm.operands)),
+        }
     }
     if assembly.len() > 20 {
         output.push_str(&format!("// ... ({} more instructions)\n", assembly.len() - 20));
@@ -47,48 +131,179 @@ pub fn generate_pseudocode(ir: &IR, assembly: &[DisassembledInstruction]) -> Str
     output
 }
 
-fn generate_instruction_code(inst: &IRInstruction) -> String {
+/// Rust-like signature of `function`, with a parameter for each argument
+/// register it reads and its result type when it has one
+fn signature(
+    function: &StructuredFunction,
+    prototypes: &HashMap<u64, Prototype>,
+    variables: &Variables,
+    types: &Types,
+) -> String {
+    let Some(prototype) = prototypes.get(&function.ssa.address) else {
+        return format!("fn {}()", function.ssa.name);
+    };
+    let type_of = |var: Var| {
+        let name = variables.name(var);
+        types.get(&name).map_or_else(|| "u64".to_string(), |ty| ty.to_string())
+    };
+    let params: Vec<String> = prototype
+        .params
+        .iter()
+        .map(|&reg| {
+            let var = Var { reg, version: 0 };
+            format!("{}: {}", variables.name(var), type_of(var))
+        })
+        .collect();
+    let name = &function.ssa.name;
+    if !prototype.returns {
+        return format!("fn {}({})", name, params.join(", "));
+    }
+    let result = function.ssa.blocks.iter().flat_map(|b| &b.stmts).find_map(|stmt| match stmt {
+        Stmt::Return { value: Some(Expr::Var(var)) } => Some(type_of(*var)),
+        _ => None,
+    });
+    format!("fn {}({}) -> {}", name, params.join(", "), result.unwrap_or_else(|| "u64".to_string()))
+}
+
+/// Label of block `id` for gotos, or of the loop it heads
+fn label(function: &StructuredFunction, id: usize, prefix: &str) -> String {
+    format!("{}_0x{:x}", prefix, function.ssa.block(id).map_or(0, |b| b.address))
+}
+
+/// Render structured code at `depth` levels of indentation, naming
+/// variables by `name`
+fn render_nodes(function: &StructuredFunction, nodes: &[Node], name: Names, depth: usize, output: &mut String) {
+    let indent = "    ".repeat(depth);
+    for node in nodes {
+        match node {
+            Node::Block(id) => {
+                if function.labels.contains(id) {
+                    output.push_str(&format!("{}{}:\n", "    ".repeat(depth - 1), label(function, *id, "label")));
+                }
+                for stmt in function.statements(*id) {
+                    output.push_str(&format!("{}{}\n", indent, stmt.render(name)));
+                }
+            }
+            Node::If { cond, then, otherwise } => {
+                output.push_str(&format!("{}if {} {{\n", indent, cond.render(name)));
+                render_nodes(function, then, name, depth + 1, output);
+                let mut otherwise = otherwise;
+                // Chain `else if` when the else arm is a single `if`
+                while let [Node::If { cond, then, otherwise: rest }] = otherwise.as_slice() {
+                    output.push_str(&format!("{}}} else if {} {{\n", indent, cond.render(name)));
+                    render_nodes(function, then, name, depth + 1, output);
+                    otherwise = rest;
+                }
+                if !otherwise.is_empty() {
+                    output.push_str(&format!("{}}} else {{\n", indent));
+                    render_nodes(function, otherwise, name, depth + 1, output);
+                }
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            Node::Loop { header, labelled, body } | Node::While { header, labelled, body, .. } => {
+                let prefix = if *labelled { format!("'{}: ", label(function, *header, "loop")) } else { String::new() };
+                match node {
+                    Node::While { cond, .. } => {
+                        output.push_str(&format!("{}{}while {} {{\n", indent, prefix, cond.render(name)));
+                    }
+                    _ => output.push_str(&format!("{}{}loop {{\n", indent, prefix)),
+                }
+                render_nodes(function, body, name, depth + 1, output);
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            Node::Break(target) | Node::Continue(target) => {
+                let keyword = if matches!(node, Node::Break(_)) { "break" } else { "continue" };
+                match target {
+                    Some(header) => output.push_str(&format!("{}{} '{};\n", indent, keyword, label(function, *header, "loop"))),
+                    None => output.push_str(&format!("{}{};\n", indent, keyword)),
+                }
+            }
+            Node::Goto(id) => output.push_str(&format!("{}goto {};\n", indent, label(function, *id, "label"))),
+        }
+    }
+}
+
+/// Render `base + offset` as an address expression
+fn address(base: u8, offset: i16) -> String {
+    match offset {
+        0 => format!("r{}", base),
+        off if off < 0 => format!("(r{} - 0x{:x})", base, off.unsigned_abs()),
+        off => format!("(r{} + 0x{:x})", base, off),
+    }
+}
+
+/// Name of the function at `target`
+fn function_name(ir: &IR, target: u64) -> String {
+    ir.functions
+        .iter()
+        .find(|f| f.address == target)
+        .map_or_else(|| format!("func_0x{:x}", target), |f| f.name.clone())
+}
+
+fn generate_instruction_code(inst: &IRInstruction, ir: &IR) -> String {
     match inst {
-        IRInstruction::Load { dst, src, offset } => {
-            format!("let r{} = *((r{} as *const u64).offset({}));", dst, src, offset / 8)
+        IRInstruction::Move { dst, src, width: Width::W64 } => format!("r{} = {};", dst, src),
+        IRInstruction::Move { dst, src: Operand::Reg(src), width: Width::W32 } => {
+            format!("r{} = r{} as u32;", dst, src)
         }
-        IRInstruction::Store { dst, src, offset } => {
-            format!("*((r{} as *mut u64).offset({})) = r{};", dst, offset / 8, src)
+        IRInstruction::Move { dst, src: Operand::Imm(imm), width: Width::W32 } => {
+            format!("r{} = {};", dst, Operand::Imm(*imm as u32 as i64))
         }
-        IRInstruction::Arithmetic { op, dst, src, imm } => {
-            if let Some(imm_val) = imm {
-                format!("r{} = r{} {} {};", dst, src, op, imm_val)
-            } else {
-                format!("r{} = r{} {} r{};", dst, src, op, src)
-            }
+        IRInstruction::LoadImm { dst, value } => format!("r{} = 0x{:x};", dst, value),
+        IRInstruction::LoadPubkey { dst, pubkey } => format!("r{} = &{};", dst, pubkey),
+        IRInstruction::Binary { op: BinaryOp::Arsh, dst, src, width } => {
+            format!("r{} = ((r{} as {}) >> {}) as {};", dst, dst, width.signed(), src, width.unsigned())
         }
-        IRInstruction::Logic { op, dst, src, imm } => {
-            if let Some(imm_val) = imm {
-                format!("r{} = r{} {} {};", dst, src, op, imm_val)
-            } else {
-                format!("r{} = r{} {} r{};", dst, src, op, src)
-            }
+        IRInstruction::Binary { op, dst, src, width: Width::W64 } => {
+            format!("r{} = r{} {} {};", dst, dst, op.symbol(), src)
         }
-        IRInstruction::Jump { cond, target } => {
-            if let Some(cond_str) = cond {
-                format!("if r{} {} r{} {{ goto 0x{:x}; }}", 0, cond_str, 0, target)
-            } else {
-                format!("goto 0x{:x};", target)
-            }
+        IRInstruction::Binary { op, dst, src, width: Width::W32 } => {
+            format!("r{} = (r{} {} {}) as u32;", dst, dst, op.symbol(), src)
         }
-        IRInstruction::Call { target, is_syscall } => {
-            if *is_syscall {
-                format!("syscall({});", target)
-            } else {
-                format!("call(0x{:x});", *target as u64)
-            }
+        IRInstruction::Unary { op: UnaryOp::Neg, dst, width } => {
+            format!("r{} = (r{} as {}).wrapping_neg();", dst, dst, width.unsigned())
+        }
+        IRInstruction::Unary { op: UnaryOp::ToLe(bits), dst, .. } => {
+            format!("r{} = (r{} as u{}).to_le();", dst, dst, bits)
+        }
+        IRInstruction::Unary { op: UnaryOp::ToBe(bits), dst, .. } => {
+            format!("r{} = (r{} as u{}).to_be();", dst, dst, bits)
+        }
+        IRInstruction::Load { dst, base, offset, size } => {
+            format!("r{} = *({} as *const {});", dst, address(*base, *offset), size.type_name())
         }
-        IRInstruction::Return => {
-            "return;".to_string()
+        IRInstruction::Store { base, offset, value, size } => {
+            format!("*({} as *mut {}) = {};", address(*base, *offset), size.type_name(), value)
         }
-        IRInstruction::Exit => {
-            "exit();".to_string()
+        IRInstruction::LoadPacket { index, offset, size } => {
+            let at = match index {
+                Some(reg) => format!("r{} + {}", reg, Operand::Imm(*offset)),
+                None => Operand::Imm(*offset).to_string(),
+            };
+            format!("r0 = packet_load::<{}>({});", size.type_name(), at)
         }
+        IRInstruction::Jump { target } => format!("goto 0x{:x};", target),
+        IRInstruction::Branch { cond, lhs, rhs, target } => {
+            // Immediates are sign-extended to 64 bits before comparing
+            let rhs = match rhs {
+                Operand::Imm(imm) if *imm < 0 && !cond.is_signed() => format!("0x{:x}", *imm as u64),
+                rhs => rhs.to_string(),
+            };
+            format!("if {} {{ goto 0x{:x}; }}", cond.render(&format!("r{}", lhs), &rhs), target)
+        }
+        IRInstruction::Call { target } => format!("{}();", function_name(ir, *target)),
+        IRInstruction::CallIndirect { reg } => format!("call_indirect(r{});", reg),
+        IRInstruction::Syscall { hash, recovered } => {
+            let args: Vec<String> = inst.uses().iter().map(|reg| format!("r{}", reg)).collect();
+            let call = render_syscall(*hash, recovered.as_deref(), &args);
+            if syscall_hash_signature(*hash).is_some_and(|s| s.returns) {
+                format!("r0 = {};", call)
+            } else {
+                format!("{};", call)
+            }
+        }
+        IRInstruction::Exit => "return r0;".to_string(),
+        IRInstruction::Pda(derivation) => format!("// PDA: {}", derivation),
+        IRInstruction::Invalid { code } => format!("// invalid instruction 0x{:02x}", code),
     }
 }
-
@@ -0,0 +1,257 @@
+// Expression propagation and simplification over SSA form
+//
+// Definitions are substituted into later statements of the same block when
+// their inputs still hold the same values there, so that renaming each
+// variable back to its register keeps the rendered code correct. Copies and
+// constants are substituted into every such use, other definitions only
+// into their single use. Expressions are then folded and simplified.
+
+use crate::decompiler::ir::{BinaryOp, MemSize, UnaryOp, Width};
+use crate::decompiler::ssa::{Expr, SsaFunction, Stmt};
+
+/// Propagate and simplify the expressions of `function`
+pub fn simplify(function: &mut SsaFunction) {
+    let mut counts = function.use_counts();
+    for block in &mut function.blocks {
+        let mut i = 0;
+        while i < block.stmts.len() {
+            for expr in block.stmts[i].exprs_mut() {
+                *expr = fold(expr.clone());
+            }
+            let Stmt::Assign { dst, expr } = &block.stmts[i] else {
+                i += 1;
+                continue;
+            };
+            let (dst, expr) = (*dst, expr.clone());
+            let uses = counts.get(&dst).copied().unwrap_or(0);
+            if uses == 0 || (uses > 1 && !matches!(expr, Expr::Var(_) | Expr::Const(_))) {
+                i += 1;
+                continue;
+            }
+
+            // Substitute until a use would see a different value
+            let inputs = expr.vars();
+            let reads_memory = expr.reads_memory();
+            let mut remaining = uses;
+            for stmt in &mut block.stmts[i + 1..] {
+                let replaced: usize = stmt.exprs_mut().into_iter().map(|e| e.substitute(dst, &expr)).sum();
+                remaining -= replaced;
+                for var in &inputs {
+                    *counts.entry(*var).or_insert(0) += replaced;
+                }
+                let kills = inputs.iter().any(|v| stmt.clobbers(v.reg))
+                    || (reads_memory && matches!(stmt, Stmt::Store { .. } | Stmt::Call { .. }));
+                if remaining == 0 || kills {
+                    break;
+                }
+            }
+            counts.insert(dst, remaining);
+            if remaining == 0 {
+                for var in &inputs {
+                    if let Some(count) = counts.get_mut(var) {
+                        *count -= 1;
+                    }
+                }
+                block.stmts.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Mask of the low `size` bytes
+fn mask(size: MemSize) -> u64 {
+    match size {
+        MemSize::U8 => 0xff,
+        MemSize::U16 => 0xffff,
+        MemSize::U32 => 0xffff_ffff,
+        MemSize::U64 => u64::MAX,
+    }
+}
+
+/// Evaluate a binary operation on constants; division by zero is left to
+/// fault at runtime
+fn evaluate(op: BinaryOp, width: Width, lhs: u64, rhs: u64) -> Option<u64> {
+    let value = match width {
+        Width::W64 => match op {
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            BinaryOp::Mul => lhs.wrapping_mul(rhs),
+            BinaryOp::Div => lhs.checked_div(rhs)?,
+            BinaryOp::Mod => lhs.checked_rem(rhs)?,
+            BinaryOp::Or => lhs | rhs,
+            BinaryOp::And => lhs & rhs,
+            BinaryOp::Xor => lhs ^ rhs,
+            BinaryOp::Lsh => lhs.wrapping_shl(rhs as u32),
+            BinaryOp::Rsh => lhs.wrapping_shr(rhs as u32),
+            BinaryOp::Arsh => (lhs as i64).wrapping_shr(rhs as u32) as u64,
+        },
+        Width::W32 => {
+            let (lhs, rhs) = (lhs as u32, rhs as u32);
+            (match op {
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                BinaryOp::Div => lhs.checked_div(rhs)?,
+                BinaryOp::Mod => lhs.checked_rem(rhs)?,
+                BinaryOp::Or => lhs | rhs,
+                BinaryOp::And => lhs & rhs,
+                BinaryOp::Xor => lhs ^ rhs,
+                BinaryOp::Lsh => lhs.wrapping_shl(rhs),
+                BinaryOp::Rsh => lhs.wrapping_shr(rhs),
+                BinaryOp::Arsh => (lhs as i32).wrapping_shr(rhs) as u32,
+            }) as u64
+        }
+    };
+    Some(value)
+}
+
+/// Fold constants and apply algebraic identities, bottom up
+pub fn fold(expr: Expr) -> Expr {
+    match expr {
+        Expr::Binary { op, width, lhs, rhs } => fold_binary(op, width, fold(*lhs), fold(*rhs)),
+        Expr::Unary { op, width, operand } => match (op, width, fold(*operand)) {
+            (UnaryOp::Neg, Width::W64, Expr::Const(c)) => Expr::Const(c.wrapping_neg()),
+            (UnaryOp::Neg, Width::W32, Expr::Const(c)) => Expr::Const((c as u32).wrapping_neg() as u64),
+            (op, width, operand) => Expr::Unary {
+                op,
+                width,
+                operand: Box::new(operand),
+            },
+        },
+        Expr::Trunc { size, operand } => match fold(*operand) {
+            Expr::Const(c) => Expr::Const(c & mask(size)),
+            // Already no wider than the truncation
+            operand @ (Expr::Binary { width: Width::W32, .. } | Expr::Unary { width: Width::W32, .. })
+                if size == MemSize::U32 =>
+            {
+                operand
+            }
+            Expr::Load { size: loaded, addr } if mask(loaded) <= mask(size) => Expr::Load { size: loaded, addr },
+            Expr::Trunc { size: inner, operand } => Expr::Trunc {
+                size: if mask(inner) < mask(size) { inner } else { size },
+                operand,
+            },
+            operand => Expr::Trunc {
+                size,
+                operand: Box::new(operand),
+            },
+        },
+        Expr::Load { size, addr } => Expr::Load {
+            size,
+            addr: Box::new(fold(*addr)),
+        },
+        Expr::Packet { size, index, offset } => Expr::Packet {
+            size,
+            index: index.map(|i| Box::new(fold(*i))),
+            offset,
+        },
+        Expr::Compare { cond, lhs, rhs } => Expr::Compare {
+            cond,
+            lhs: Box::new(fold(*lhs)),
+            rhs: Box::new(fold(*rhs)),
+        },
+        expr @ (Expr::Var(_) | Expr::Const(_) | Expr::Pubkey(_)) => expr,
+    }
+}
+
+fn fold_binary(op: BinaryOp, width: Width, lhs: Expr, rhs: Expr) -> Expr {
+    use BinaryOp::*;
+    let commutative = matches!(op, Add | Mul | Or | And | Xor);
+    match (lhs, rhs) {
+        (Expr::Const(a), Expr::Const(b)) => match evaluate(op, width, a, b) {
+            Some(value) => Expr::Const(value),
+            None => Expr::binary(op, width, Expr::Const(a), Expr::Const(b)),
+        },
+        // Constants go on the right
+        (Expr::Const(a), rhs) if commutative => fold_binary(op, width, rhs, Expr::Const(a)),
+        (lhs, rhs) if width == Width::W32 => Expr::binary(op, width, lhs, rhs),
+        (lhs, Expr::Const(0)) if matches!(op, Add | Sub | Or | Xor | Lsh | Rsh | Arsh) => lhs,
+        (lhs, Expr::Const(1)) if matches!(op, Mul | Div) => lhs,
+        (lhs, Expr::Const(u64::MAX)) if op == And => lhs,
+        (_, Expr::Const(0)) if matches!(op, Mul | And) => Expr::Const(0),
+        (lhs, rhs) if lhs == rhs && matches!(op, Sub | Xor) => Expr::Const(0),
+        (lhs, rhs) if lhs == rhs && matches!(op, And | Or) => lhs,
+        // Combine constant offsets: (x + a) + b, (x - a) + b, ...
+        (Expr::Binary { op: inner @ (Add | Sub), width: Width::W64, lhs: base, rhs }, Expr::Const(b))
+            if matches!(op, Add | Sub) && matches!(*rhs, Expr::Const(_)) =>
+        {
+            let Expr::Const(a) = *rhs else { unreachable!("constant operand") };
+            let a = if inner == Add { a } else { a.wrapping_neg() };
+            let b = if op == Add { b } else { b.wrapping_neg() };
+            Expr::offset(*base, a.wrapping_add(b) as i64)
+        }
+        // Subtracting a constant reads better as a negative offset
+        (lhs, Expr::Const(b)) if op == Add && (b as i64) < 0 => Expr::offset(lhs, b as i64),
+        (lhs, rhs) => Expr::binary(op, width, lhs, rhs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use crate::decompiler::ssa::Var;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+    use std::collections::BTreeMap;
+
+    fn simplified(program: &[u8]) -> Vec<String> {
+        let instructions = InstructionDecoder::new(None).decode_all(program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut function = SsaFunction::build(&cfg, &ir, 0);
+        simplify(&mut function);
+        let name = |var: Var| format!("r{}", var.reg);
+        function.blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&name)).collect()
+    }
+
+    #[test]
+    fn test_propagate_into_load() {
+        let program: Vec<u8> = [
+            insn(0xbf, 2, 1, 0, 0),     // mov r2, r1
+            insn(0x07, 2, 0, 0, 8),     // add r2, 8
+            insn(0x79, 3, 2, 0, 0),     // ldxdw r3, [r2]
+            insn(0xbf, 0, 3, 0, 0),     // mov r0, r3
+            insn(0x95, 0, 0, 0, 0),     // exit
+        ]
+        .concat();
+        assert_eq!(simplified(&program), vec!["return *((r1 + 8) as *const u64);"]);
+    }
+
+    #[test]
+    fn test_blocked_by_redefinition_and_store() {
+        let program: Vec<u8> = [
+            insn(0x79, 3, 1, 0, 0),     // ldxdw r3, [r1]
+            insn(0x7a, 1, 0, 0, 7),     // stdw [r1], 7
+            insn(0xb7, 4, 0, 0, 2),     // mov r4, 2
+            insn(0x27, 4, 0, 0, 3),     // mul r4, 3
+            insn(0x07, 1, 0, 0, -8),    // add r1, -8
+            insn(0x0f, 3, 4, 0, 0),     // add r3, r4
+            insn(0xbf, 0, 3, 0, 0),     // mov r0, r3
+            insn(0x95, 0, 0, 0, 0),     // exit
+        ]
+        .concat();
+        assert_eq!(
+            simplified(&program),
+            vec![
+                "r3 = *(r1 as *const u64);",
+                "*(r1 as *mut u64) = 7;",
+                "r1 = r1 - 8;",
+                "return r3 + 6;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold() {
+        let x = || Expr::Var(Var { reg: 1, version: 0 });
+        let add = |lhs, rhs| Expr::binary(BinaryOp::Add, Width::W64, lhs, rhs);
+        assert_eq!(fold(add(add(x(), Expr::Const(8)), Expr::Const(u64::MAX))), add(x(), Expr::Const(7)));
+        assert_eq!(fold(Expr::binary(BinaryOp::Xor, Width::W64, x(), x())), Expr::Const(0));
+        assert_eq!(fold(Expr::binary(BinaryOp::Sub, Width::W32, Expr::Const(0), Expr::Const(1))), Expr::Const(0xffff_ffff));
+        assert_eq!(fold(Expr::binary(BinaryOp::Div, Width::W64, x(), Expr::Const(1))), x());
+    }
+}
@@ -0,0 +1,583 @@
+// Control-flow structuring
+//
+// The blocks of a function are laid out along its dominator tree: a block
+// is followed by the code of the blocks it immediately dominates, either
+// inline as the arms of its branch or afterwards as the join points of those
+// arms. Natural loops become `loop`/`while` with `break` and `continue`, and
+// branches whose arm only tests another condition are merged into `&&`/`||`.
+// Edges that fit none of these, which only irreducible flow produces, are
+// left as gotos to labelled blocks.
+
+use crate::decompiler::ssa::{Expr, Names, SsaBlock, SsaFunction, Stmt};
+use sbpf_common::cfg::{ControlFlowGraph, DominatorTree, LoopForest};
+use sbpf_common::instruction::Instruction;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Condition of a structured branch or loop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cond {
+    Test(Expr),
+    Not(Box<Cond>),
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+}
+
+impl Cond {
+    /// Condition that holds exactly when this one does not
+    pub fn negate(self) -> Cond {
+        match self {
+            Cond::Test(Expr::Compare { cond, lhs, rhs }) => Cond::Test(Expr::Compare {
+                cond: cond.negate(),
+                lhs,
+                rhs,
+            }),
+            Cond::Test(expr) => Cond::Not(Box::new(Cond::Test(expr))),
+            Cond::Not(cond) => *cond,
+            Cond::And(a, b) => Cond::Or(Box::new(a.negate()), Box::new(b.negate())),
+            Cond::Or(a, b) => Cond::And(Box::new(a.negate()), Box::new(b.negate())),
+        }
+    }
+
+    /// Render with each variable named by `name`
+    pub fn render(&self, name: Names) -> String {
+        match self {
+            Cond::Test(expr) => expr.render(name),
+            Cond::Not(cond) => format!("!({})", cond.render(name)),
+            Cond::And(a, b) => {
+                let operand = |c: &Cond| match c {
+                    Cond::Or(..) => format!("({})", c.render(name)),
+                    _ => c.render(name),
+                };
+                format!("{} && {}", operand(a), operand(b))
+            }
+            Cond::Or(a, b) => format!("{} || {}", a.render(name), b.render(name)),
+        }
+    }
+}
+
+/// Structured statement over the blocks of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// Statements of a block, without its terminating jump or branch
+    Block(usize),
+    If { cond: Cond, then: Vec<Node>, otherwise: Vec<Node> },
+    /// Loop headed by block `header`, labelled when an inner loop leaves or
+    /// continues it
+    Loop { header: usize, labelled: bool, body: Vec<Node> },
+    While { header: usize, labelled: bool, cond: Cond, body: Vec<Node> },
+    /// Leave the innermost loop, or the loop with the given header
+    Break(Option<usize>),
+    Continue(Option<usize>),
+    /// Jump to a labelled block
+    Goto(usize),
+}
+
+/// Function in SSA form with its structured body
+#[derive(Debug, Clone)]
+pub struct StructuredFunction {
+    pub ssa: SsaFunction,
+    pub body: Vec<Node>,
+    /// Blocks targeted by gotos, which need labels
+    pub labels: HashSet<usize>,
+}
+
+impl StructuredFunction {
+    /// Structure `ssa`, the SSA form of function `index` of `cfg`
+    pub fn new(cfg: &ControlFlowGraph, instructions: &[Instruction], index: usize, ssa: SsaFunction) -> Self {
+        let function = &cfg.functions[index];
+        let domtree = DominatorTree::compute(cfg, function);
+        let forest = LoopForest::compute(cfg, function, &domtree, instructions);
+        let blocks: HashMap<usize, &SsaBlock> = ssa.blocks.iter().map(|b| (b.id, b)).collect();
+        // Only blocks that made it into SSA form can be laid out
+        let loops = forest
+            .loops
+            .iter()
+            .filter(|l| blocks.contains_key(&l.header))
+            .map(|l| (l.header, l.body.iter().copied().filter(|b| blocks.contains_key(b)).collect()))
+            .collect();
+        let mut structurer = Structurer {
+            blocks,
+            order: domtree.reverse_postorder.iter().enumerate().map(|(i, &b)| (b, i)).collect(),
+            children: domtree.children(),
+            loops,
+            domtree,
+            active: Vec::new(),
+            labels: HashSet::new(),
+        };
+        let body = structurer.region(function.entry, None);
+        let labels = structurer.labels;
+        StructuredFunction { ssa, body, labels }
+    }
+
+    /// Statements of block `id` other than its terminating jump or branch
+    pub fn statements(&self, id: usize) -> &[Stmt] {
+        self.ssa.block(id).map_or(&[], body_statements)
+    }
+}
+
+fn body_statements(block: &SsaBlock) -> &[Stmt] {
+    match block.stmts.split_last() {
+        Some((Stmt::Jump { .. } | Stmt::Branch { .. }, rest)) => rest,
+        _ => &block.stmts,
+    }
+}
+
+/// Where control goes after a block
+enum Exit {
+    /// Return or no known successor
+    None,
+    Goto(usize),
+    /// `if cond { goto taken } else { goto fallthrough }`
+    Branch { cond: Cond, taken: usize, fallthrough: usize },
+}
+
+/// Loop being structured
+struct ActiveLoop {
+    header: usize,
+    body: BTreeSet<usize>,
+    /// Block a `break` continues at
+    follow: Option<usize>,
+    /// Blocks dominated by the loop but outside it, placed after it
+    deferred: Vec<usize>,
+    labelled: bool,
+}
+
+struct Structurer<'a> {
+    blocks: HashMap<usize, &'a SsaBlock>,
+    domtree: DominatorTree,
+    /// Position of each block in reverse postorder
+    order: HashMap<usize, usize>,
+    children: HashMap<usize, Vec<usize>>,
+    /// Body of each natural loop by header
+    loops: HashMap<usize, BTreeSet<usize>>,
+    active: Vec<ActiveLoop>,
+    labels: HashSet<usize>,
+}
+
+impl Structurer<'_> {
+    fn exit(&self, id: usize) -> Exit {
+        let Some(block) = self.blocks.get(&id) else {
+            return Exit::None;
+        };
+        match (block.stmts.last(), block.successors.as_slice()) {
+            (Some(Stmt::Branch { cond, .. }), &[taken, fallthrough]) => Exit::Branch {
+                cond: Cond::Test(cond.clone()),
+                taken,
+                fallthrough,
+            },
+            (_, &[next]) => Exit::Goto(next),
+            _ => Exit::None,
+        }
+    }
+
+    /// Check if `block` only tests a condition
+    fn is_bare_branch(&self, block: usize) -> bool {
+        self.blocks.get(&block).is_some_and(|b| matches!(b.stmts.as_slice(), [Stmt::Branch { .. }]))
+    }
+
+    /// Check if `block` belongs to the innermost active loop, if any
+    fn in_current_loop(&self, block: usize) -> bool {
+        self.active.last().is_none_or(|l| l.body.contains(&block))
+    }
+
+    /// Check if `block` can be placed inline after the blocks in `consumed`:
+    /// it is dominated by them and only entered from them
+    fn is_inline(&self, block: usize, consumed: &[usize]) -> bool {
+        let dominated = self.domtree.immediate_dominator(block).is_some_and(|d| consumed.contains(&d));
+        let entered = self.blocks.get(&block).is_some_and(|b| {
+            b.predecessors
+                .iter()
+                .filter(|&&p| !self.domtree.dominates(block, p))
+                .all(|p| consumed.contains(p))
+        });
+        dominated && entered && self.in_current_loop(block) && !self.active.iter().any(|l| l.header == block)
+    }
+
+    /// Code for an edge to `target` when control would otherwise continue at
+    /// `next`
+    fn edge(&mut self, target: usize, next: Option<usize>) -> Vec<Node> {
+        if Some(target) == next {
+            return Vec::new();
+        }
+        let innermost = self.active.len().saturating_sub(1);
+        for (i, l) in self.active.iter_mut().enumerate().rev() {
+            let node: fn(Option<usize>) -> Node = if target == l.header {
+                Node::Continue
+            } else if Some(target) == l.follow {
+                Node::Break
+            } else {
+                continue;
+            };
+            if i == innermost {
+                return vec![node(None)];
+            }
+            l.labelled = true;
+            return vec![node(Some(l.header))];
+        }
+        self.labels.insert(target);
+        vec![Node::Goto(target)]
+    }
+
+    /// Check if control never continues past the end of `nodes`
+    fn is_terminal(&self, nodes: &[Node]) -> bool {
+        match nodes.last() {
+            Some(Node::Break(_) | Node::Continue(_) | Node::Goto(_)) => true,
+            Some(Node::Block(id)) => {
+                matches!(self.blocks.get(id).and_then(|b| b.stmts.last()), Some(Stmt::Return { .. }))
+            }
+            Some(Node::If { then, otherwise, .. }) => self.is_terminal(then) && self.is_terminal(otherwise),
+            _ => false,
+        }
+    }
+
+    /// Build an `if`, dropping empty arms and hoisting the rest of the code
+    /// out of an `else` when the other arm always leaves
+    fn make_if(&self, cond: Cond, then: Vec<Node>, otherwise: Vec<Node>) -> Vec<Node> {
+        match (then.is_empty(), otherwise.is_empty()) {
+            (true, true) => Vec::new(),
+            (true, false) => self.make_if(cond.negate(), otherwise, then),
+            (false, true) => vec![Node::If { cond, then, otherwise }],
+            _ if self.is_terminal(&then) && !self.is_terminal(&otherwise) => {
+                let mut nodes = vec![Node::If {
+                    cond,
+                    then,
+                    otherwise: Vec::new(),
+                }];
+                nodes.extend(otherwise);
+                nodes
+            }
+            _ if self.is_terminal(&otherwise) && !self.is_terminal(&then) => {
+                let mut nodes = vec![Node::If {
+                    cond: cond.negate(),
+                    then: otherwise,
+                    otherwise: Vec::new(),
+                }];
+                nodes.extend(then);
+                nodes
+            }
+            _ => vec![Node::If { cond, then, otherwise }],
+        }
+    }
+
+    /// Structure the blocks dominated by `entry`, continuing at `next`
+    fn region(&mut self, entry: usize, next: Option<usize>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut current = entry;
+        loop {
+            if self.loops.contains_key(&current) && !self.active.iter().any(|l| l.header == current) {
+                nodes.extend(self.structure_loop(current, next));
+                return nodes;
+            }
+            nodes.push(Node::Block(current));
+            let mut consumed = vec![current];
+            let mut exit = self.exit(current);
+
+            // Merge arms that only test another condition into `&&`/`||`
+            while let Exit::Branch { cond, taken, fallthrough } = exit {
+                let merged = [fallthrough, taken].into_iter().find_map(|arm| {
+                    // A loop header tests its condition on every iteration
+                    if consumed.contains(&arm)
+                        || self.loops.contains_key(&arm)
+                        || !self.is_bare_branch(arm)
+                        || !self.is_inline(arm, &consumed)
+                    {
+                        return None;
+                    }
+                    let Exit::Branch { cond: inner, taken: t, fallthrough: f } = self.exit(arm) else {
+                        return None;
+                    };
+                    if t == arm || f == arm {
+                        return None;
+                    }
+                    let (cond, taken, fallthrough) = match (arm == fallthrough, t == taken, f == taken, f == fallthrough, t == fallthrough) {
+                        (true, true, _, _, _) => (Cond::Or(Box::new(cond.clone()), Box::new(inner)), taken, f),
+                        (true, _, true, _, _) => (Cond::Or(Box::new(cond.clone()), Box::new(inner.negate())), taken, t),
+                        (false, _, _, true, _) => (Cond::And(Box::new(cond.clone()), Box::new(inner)), t, fallthrough),
+                        (false, _, _, _, true) => (Cond::And(Box::new(cond.clone()), Box::new(inner.negate())), f, fallthrough),
+                        _ => return None,
+                    };
+                    Some((arm, Exit::Branch { cond, taken, fallthrough }))
+                });
+                match merged {
+                    Some((arm, merged)) => {
+                        consumed.push(arm);
+                        exit = merged;
+                    }
+                    None => {
+                        exit = Exit::Branch { cond, taken, fallthrough };
+                        break;
+                    }
+                }
+            }
+
+            // Blocks dominated here that are not arms are placed after the
+            // arms, or after the loop they are outside of
+            let arms: Vec<usize> = match &exit {
+                Exit::None => Vec::new(),
+                Exit::Goto(target) => vec![*target],
+                Exit::Branch { taken, fallthrough, .. } => vec![*taken, *fallthrough],
+            };
+            let inline: Vec<usize> = arms.iter().copied().filter(|&a| self.is_inline(a, &consumed)).collect();
+            let mut joins: Vec<usize> = Vec::new();
+            for &block in &consumed {
+                for &child in self.children.get(&block).into_iter().flatten() {
+                    if consumed.contains(&child) || inline.contains(&child) {
+                        continue;
+                    }
+                    if self.in_current_loop(child) {
+                        joins.push(child);
+                    } else {
+                        let outer = (1..self.active.len())
+                            .rev()
+                            .find(|&i| self.active[i - 1].body.contains(&child))
+                            .unwrap_or(0);
+                        self.active[outer].deferred.push(child);
+                    }
+                }
+            }
+            joins.sort_by_key(|b| self.order[b]);
+            let after = joins.first().copied().or(next);
+
+            let arm = |this: &mut Self, target: usize| {
+                if inline.contains(&target) {
+                    this.region(target, after)
+                } else {
+                    this.edge(target, after)
+                }
+            };
+            match exit {
+                Exit::None => {}
+                Exit::Goto(target) if inline.contains(&target) && joins.is_empty() => {
+                    current = target;
+                    continue;
+                }
+                Exit::Goto(target) => nodes.extend(arm(self, target)),
+                Exit::Branch { cond, taken, fallthrough } => {
+                    let then = arm(self, taken);
+                    let otherwise = arm(self, fallthrough);
+                    nodes.extend(self.make_if(cond, then, otherwise));
+                }
+            }
+
+            let Some(last) = joins.pop() else {
+                return nodes;
+            };
+            for (i, &join) in joins.iter().enumerate() {
+                let following = joins.get(i + 1).copied().unwrap_or(last);
+                nodes.extend(self.region(join, Some(following)));
+            }
+            current = last;
+        }
+    }
+
+    /// Structure the natural loop headed by `header`, followed by the
+    /// blocks it dominates outside its body
+    fn structure_loop(&mut self, header: usize, next: Option<usize>) -> Vec<Node> {
+        let body = self.loops[&header].clone();
+        let exits: BTreeSet<usize> = body
+            .iter()
+            .filter_map(|b| self.blocks.get(b))
+            .flat_map(|b| b.successors.iter().copied())
+            .filter(|s| !body.contains(s))
+            .collect();
+        let follow = exits
+            .iter()
+            .copied()
+            .filter(|&e| self.domtree.dominates(header, e) && self.in_current_loop(e))
+            .min_by_key(|e| self.order[e])
+            .or_else(|| next.filter(|n| exits.contains(n)));
+        self.active.push(ActiveLoop {
+            header,
+            body,
+            follow,
+            deferred: Vec::new(),
+            labelled: false,
+        });
+        let mut inner = self.region(header, Some(header));
+        let active = self.active.pop().expect("active loop");
+
+        // `loop { if cond { break; } ... }` is `while !cond { ... }`
+        let mut nodes = Vec::new();
+        let bare = self.statements_empty(header) && !self.labels.contains(&header);
+        match inner.as_slice() {
+            [Node::Block(_), Node::If { then, otherwise, .. }, ..]
+                if bare && otherwise.is_empty() && then == &[Node::Break(None)] =>
+            {
+                let Node::If { cond, .. } = inner.remove(1) else { unreachable!("matched if") };
+                inner.remove(0);
+                nodes.push(Node::While {
+                    header,
+                    labelled: active.labelled,
+                    cond: cond.negate(),
+                    body: inner,
+                });
+            }
+            _ => nodes.push(Node::Loop {
+                header,
+                labelled: active.labelled,
+                body: inner,
+            }),
+        }
+
+        let mut deferred = active.deferred;
+        deferred.sort_by_key(|b| (Some(*b) != active.follow, self.order[b]));
+        for (i, &block) in deferred.iter().enumerate() {
+            let following = deferred.get(i + 1).copied().or(next);
+            nodes.extend(self.region(block, following));
+        }
+        nodes
+    }
+
+    /// Check if block `id` has no statements besides its terminator
+    fn statements_empty(&self, id: usize) -> bool {
+        self.blocks.get(&id).is_none_or(|b| body_statements(b).is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use crate::decompiler::ssa::Var;
+    use sbpf_common::instruction::InstructionDecoder;
+    use std::collections::BTreeMap;
+
+    fn structure(program: &[u8]) -> StructuredFunction {
+        let instructions = InstructionDecoder::new(None).decode_all(program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let ssa = SsaFunction::build(&cfg, &ir, 0);
+        StructuredFunction::new(&cfg, &instructions, 0, ssa)
+    }
+
+    /// Shape of the structured code, with conditions rendered
+    fn shape(nodes: &[Node]) -> String {
+        let name = |var: Var| format!("r{}", var.reg);
+        let parts: Vec<String> = nodes
+            .iter()
+            .map(|node| match node {
+                Node::Block(_) => "B".to_string(),
+                Node::If { cond, then, otherwise } if otherwise.is_empty() => {
+                    format!("if {} {{{}}}", cond.render(&name), shape(then))
+                }
+                Node::If { cond, then, otherwise } => {
+                    format!("if {} {{{}}} else {{{}}}", cond.render(&name), shape(then), shape(otherwise))
+                }
+                Node::Loop { body, .. } => format!("loop {{{}}}", shape(body)),
+                Node::While { cond, body, .. } => format!("while {} {{{}}}", cond.render(&name), shape(body)),
+                Node::Break(_) => "break".to_string(),
+                Node::Continue(_) => "continue".to_string(),
+                Node::Goto(_) => "goto".to_string(),
+            })
+            .collect();
+        parts.join("; ")
+    }
+
+    #[test]
+    fn test_if_else() {
+        let program: Vec<u8> = [
+            insn(0x15, 1, 0, 2, 0),     // 0x00: jeq r1, 0, 0x18
+            insn(0xb7, 0, 0, 0, 1),     // 0x08: mov r0, 1
+            insn(0x05, 0, 0, 1, 0),     // 0x10: ja 0x20
+            insn(0xb7, 0, 0, 0, 2),     // 0x18: mov r0, 2
+            insn(0x95, 0, 0, 0, 0),     // 0x20: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(shape(&function.body), "B; if r1 == 0 {B} else {B}; B");
+        assert!(function.labels.is_empty());
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let program: Vec<u8> = [
+            insn(0x15, 1, 0, 2, 0),     // 0x00: jeq r1, 0, 0x18
+            insn(0x15, 2, 0, 1, 0),     // 0x08: jeq r2, 0, 0x18
+            insn(0xb7, 0, 0, 0, 1),     // 0x10: mov r0, 1
+            insn(0x95, 0, 0, 0, 0),     // 0x18: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(shape(&function.body), "B; if r1 != 0 && r2 != 0 {B}; B");
+    }
+
+    #[test]
+    fn test_self_loop_arm_not_merged() {
+        let program: Vec<u8> = [
+            insn(0x15, 1, 0, 1, 0),     // 0x00: jeq r1, 0, 0x10
+            insn(0xa5, 2, 0, -1, 1),    // 0x08: jlt r2, 1, 0x08
+            insn(0x95, 0, 0, 0, 0),     // 0x10: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(shape(&function.body), "B; if r1 != 0 {while r2 < 1 {}}; B");
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let program: Vec<u8> = [
+            insn(0xb7, 0, 0, 0, 0),     // 0x00: mov r0, 0
+            insn(0x15, 1, 0, 3, 0),     // 0x08: jeq r1, 0, 0x28
+            insn(0x07, 0, 0, 0, 1),     // 0x10: add r0, 1
+            insn(0x07, 1, 0, 0, -1),    // 0x18: add r1, -1
+            insn(0x05, 0, 0, -4, 0),    // 0x20: ja 0x08
+            insn(0x95, 0, 0, 0, 0),     // 0x28: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(shape(&function.body), "B; while r1 != 0 {B}; B");
+    }
+
+    #[test]
+    fn test_loop_with_break_and_continue() {
+        let program: Vec<u8> = [
+            insn(0x07, 1, 0, 0, 1),     // 0x00: add r1, 1
+            insn(0x15, 1, 0, 5, 5),     // 0x08: jeq r1, 5, 0x38
+            insn(0x55, 1, 0, 2, 3),     // 0x10: jne r1, 3, 0x28
+            insn(0x07, 3, 0, 0, 1),     // 0x18: add r3, 1
+            insn(0x15, 3, 0, -5, 0),    // 0x20: jeq r3, 0, 0x00
+            insn(0x07, 2, 0, 0, 1),     // 0x28: add r2, 1
+            insn(0x05, 0, 0, -7, 0),    // 0x30: ja 0x00
+            insn(0x95, 0, 0, 0, 0),     // 0x38: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(
+            shape(&function.body),
+            "loop {B; if r1 == 5 {break}; B; if r1 == 3 {B; if r3 == 0 {continue}}; B}; B"
+        );
+    }
+
+    #[test]
+    fn test_irreducible_goto() {
+        let program: Vec<u8> = [
+            insn(0x15, 1, 0, 2, 0),     // 0x00: jeq r1, 0, 0x18
+            insn(0x07, 2, 0, 0, 1),     // 0x08: add r2, 1
+            insn(0x55, 2, 0, 1, 9),     // 0x10: jne r2, 9, 0x20
+            insn(0x07, 2, 0, 0, 2),     // 0x18: add r2, 2
+            insn(0x55, 2, 0, -4, 7),    // 0x20: jne r2, 7, 0x08
+            insn(0x95, 0, 0, 0, 0),     // 0x28: exit
+        ]
+        .concat();
+        let function = structure(&program);
+        assert!(!function.labels.is_empty());
+        assert!(shape(&function.body).contains("goto"));
+    }
+
+    #[test]
+    fn test_unreachable_jump_into_loop() {
+        let program: Vec<u8> = [
+            insn(0xb7, 1, 0, 0, 0),     // 0x00: mov r1, 0
+            insn(0x07, 1, 0, 0, 1),     // 0x08: add r1, 1
+            insn(0x25, 1, 0, 2, 100),   // 0x10: jgt r1, 100, 0x28
+            insn(0xa5, 1, 0, -3, 10),   // 0x18: jlt r1, 10, 0x08
+            insn(0x95, 0, 0, 0, 0),     // 0x20: exit
+            insn(0x95, 0, 0, 0, 0),     // 0x28: exit
+            insn(0x05, 0, 0, -4, 0),    // 0x30: ja 0x18 (unreachable)
+        ]
+        .concat();
+        let function = structure(&program);
+        assert_eq!(shape(&function.body), "B; loop {B; if r1 > 100 {goto}; B; if r1 >= 10 {break}}; B; B");
+    }
+}
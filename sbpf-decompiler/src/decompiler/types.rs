@@ -0,0 +1,561 @@
+// Type inference over recovered variables
+//
+// Each variable and stack slot collects evidence from how it is used:
+// integer widths from load and store sizes and 32-bit operations,
+// signedness from signed comparisons and arithmetic shifts, pointers from
+// dereferences, and known types from syscall signatures and the layout of
+// the entrypoint's input buffer. Evidence is merged until nothing changes.
+
+use crate::decompiler::ir::{syscall_hash_signature, BinaryOp, MemSize, Width};
+use crate::decompiler::layouts::{struct_name, Layouts};
+use crate::decompiler::ssa::{slot_name, Callee, Expr, Naming, SsaFunction, Stmt, Var};
+use crate::decompiler::variables::Variables;
+use sbpf_common::input::{AccountField, ACCOUNT_DATA_OFFSET, FIRST_ACCOUNT_OFFSET};
+use sbpf_common::syscalls::ArgKind;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// Inferred type of a variable or stack slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int { bits: u8, signed: bool },
+    /// Raw pointer to values of a type
+    Ptr { pointee: Box<Type>, mutable: bool },
+    /// Reference to a known struct
+    Ref { name: &'static str, mutable: bool },
+    /// Known struct held by value, such as a sysvar copied to the stack
+    Struct(&'static str),
+    /// Struct inferred from field accesses, by id in [`Layouts`]
+    Layout(usize),
+}
+
+impl Type {
+    const U64: Type = Type::Int { bits: 64, signed: false };
+
+    /// Unsigned integer of `size` bytes
+    fn int(size: MemSize) -> Type {
+        Type::Int {
+            bits: size.bits(),
+            signed: false,
+        }
+    }
+
+    fn signed(bits: u8) -> Type {
+        Type::Int { bits, signed: true }
+    }
+
+    fn reference(name: &'static str) -> Type {
+        Type::Ref { name, mutable: false }
+    }
+
+    fn pointer(pointee: Type, mutable: bool) -> Type {
+        Type::Ptr {
+            pointee: Box::new(pointee),
+            mutable,
+        }
+    }
+
+    /// Combine two pieces of evidence about the same value; known structs
+    /// win over pointers, pointers over integers, and integers widen
+    fn merge(self, other: Type) -> Type {
+        match (self, other) {
+            (Type::Ref { name, mutable }, Type::Ref { mutable: other, .. }) => Type::Ref {
+                name,
+                mutable: mutable || other,
+            },
+            (known @ (Type::Ref { .. } | Type::Struct(_) | Type::Layout(_)), _)
+            | (_, known @ (Type::Ref { .. } | Type::Struct(_) | Type::Layout(_))) => known,
+            (Type::Ptr { pointee, mutable }, Type::Ptr { pointee: other, mutable: other_mutable }) => {
+                Type::pointer(pointee.merge(*other), mutable || other_mutable)
+            }
+            (pointer @ Type::Ptr { .. }, _) | (_, pointer @ Type::Ptr { .. }) => pointer,
+            (Type::Int { bits, signed }, Type::Int { bits: other, signed: other_signed }) => Type::Int {
+                bits: bits.max(other),
+                signed: signed || other_signed,
+            },
+        }
+    }
+
+    /// Type of the value stored where a pointer of this type points
+    fn pointee(&self) -> Option<Type> {
+        match self {
+            Type::Ptr { pointee, .. } => Some(*pointee.clone()),
+            Type::Ref { name, .. } => Some(Type::Struct(name)),
+            _ => None,
+        }
+    }
+
+    /// Type of a constant `offset` from a pointer of this type
+    fn offset(&self, offset: i64) -> Option<Type> {
+        match (self, offset) {
+            (Type::Ref { name: "Input", .. }, FIRST_ACCOUNT_OFFSET) => Some(Type::reference("AccountInfo")),
+            (Type::Ref { name: "Input", .. }, offset) => account_field_pointer(offset - FIRST_ACCOUNT_OFFSET),
+            (Type::Ref { name: "AccountInfo", .. }, offset) => account_field_pointer(offset),
+            (Type::Ptr { .. }, _) => Some(self.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int { bits, signed } => write!(f, "{}{}", if *signed { "i" } else { "u" }, bits),
+            Type::Ptr { pointee, mutable } => write!(f, "*{} {}", if *mutable { "mut" } else { "const" }, pointee),
+            Type::Ref { name, mutable } => write!(f, "&{}{}", if *mutable { "mut " } else { "" }, name),
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::Layout(id) => write!(f, "{}", struct_name(*id)),
+        }
+    }
+}
+
+/// Field of an account record at exactly `offset`, with its type
+fn account_field(offset: i64) -> Option<(AccountField, Type)> {
+    let field = AccountField::from_record_offset(offset).filter(|f| f.record_offset() == Some(offset))?;
+    let ty = match field {
+        AccountField::DupInfo | AccountField::IsSigner | AccountField::IsWritable | AccountField::Executable => {
+            Type::int(MemSize::U8)
+        }
+        AccountField::OriginalDataLen => Type::int(MemSize::U32),
+        AccountField::Key | AccountField::Owner => Type::Struct("Pubkey"),
+        AccountField::Lamports | AccountField::DataLen => Type::U64,
+        AccountField::Data | AccountField::RentEpoch => return None,
+    };
+    Some((field, ty))
+}
+
+/// Type of a pointer to the account record field at `offset`
+fn account_field_pointer(offset: i64) -> Option<Type> {
+    if offset == ACCOUNT_DATA_OFFSET {
+        return Some(Type::pointer(Type::int(MemSize::U8), true));
+    }
+    match account_field(offset)? {
+        (_, Type::Struct(name)) => Some(Type::reference(name)),
+        (_, ty) => Some(Type::pointer(ty, true)),
+    }
+}
+
+/// Known struct a syscall pointer parameter points to
+fn syscall_pointee(syscall: &str, param: &str) -> Option<&'static str> {
+    Some(match (syscall, param) {
+        ("sol_get_clock_sysvar", _) => "Clock",
+        ("sol_get_rent_sysvar", _) => "Rent",
+        ("sol_get_epoch_schedule_sysvar", _) => "EpochSchedule",
+        ("sol_get_fees_sysvar", _) => "Fees",
+        ("sol_get_epoch_rewards_sysvar", _) => "EpochRewards",
+        ("sol_invoke_signed_c" | "sol_invoke_signed_rust", "instruction") => "Instruction",
+        ("sol_invoke_signed_c" | "sol_invoke_signed_rust", "account_infos") => "AccountInfo",
+        (_, "address" | "program_id") => "Pubkey",
+        _ => return None,
+    })
+}
+
+/// Types of the variables and stack slots of a function
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    /// Type of each variable and stack slot by name
+    types: HashMap<String, Type>,
+    /// Stack slots by offset below the frame pointer, with their store count
+    slots: BTreeMap<u64, usize>,
+    /// Widest access at each constant offset from each pointer
+    accesses: HashMap<String, BTreeMap<i64, MemSize>>,
+}
+
+impl Types {
+    /// Known parameter types of the entrypoint, which receives the
+    /// serialized input buffer in r1
+    pub fn entry_params() -> BTreeMap<u8, Type> {
+        BTreeMap::from([(1, Type::reference("Input"))])
+    }
+
+    /// Infer the types of the variables of `function`, whose argument
+    /// registers hold values of the known `params` types
+    pub fn infer(function: &SsaFunction, variables: &Variables, params: &BTreeMap<u8, Type>) -> Self {
+        let mut inference = Inference {
+            variables,
+            types: Types::default(),
+            compared_signed: HashSet::new(),
+        };
+        for (&reg, ty) in params {
+            inference.constrain(Var { reg, version: 0 }, ty.clone());
+        }
+        loop {
+            let before = inference.types.types.clone();
+            inference.types.slots.clear();
+            for stmt in function.blocks.iter().flat_map(|b| &b.stmts) {
+                inference.stmt(stmt);
+            }
+            if inference.types.types == before {
+                break;
+            }
+        }
+        // Narrower values keep their width and are widened at the compare
+        for name in std::mem::take(&mut inference.compared_signed) {
+            if matches!(inference.types.get(&name), None | Some(Type::Int { bits: 64, .. })) {
+                inference.constrain_name(name, Type::signed(64));
+            }
+        }
+        inference.types
+    }
+
+    /// Type of the variable or stack slot `name`
+    pub fn get(&self, name: &str) -> Option<&Type> {
+        self.types.get(name)
+    }
+
+    /// Accesses through each pointer that is not of a known type
+    pub fn accesses(&self) -> impl Iterator<Item = (&String, &BTreeMap<i64, MemSize>)> {
+        self.accesses
+            .iter()
+            .filter(|(name, _)| !matches!(self.types.get(*name), Some(Type::Ref { .. })))
+    }
+
+    /// Make `name` a pointer to the inferred struct `id`, unless it already
+    /// has a known type
+    pub fn set_layout(&mut self, name: &str, id: usize) {
+        if let Some(Type::Ref { .. }) = self.types.get(name) {
+            return;
+        }
+        let mutable = matches!(self.types.get(name), Some(Type::Ptr { mutable: true, .. }));
+        self.types.insert(name.to_string(), Type::pointer(Type::Layout(id), mutable));
+    }
+
+    /// Declarations of the locals and stack slots of a function, mutable
+    /// when assigned more than once or merged at a join
+    pub fn declarations(&self, function: &SsaFunction, variables: &Variables) -> Vec<String> {
+        let mut assignments: HashMap<String, usize> = HashMap::new();
+        // Phis count too, so that locals updated in loops are mutable
+        for block in &function.blocks {
+            let defs = block.phis.iter().map(|phi| phi.dst).chain(block.stmts.iter().filter_map(|s| s.def()));
+            for var in defs {
+                *assignments.entry(variables.name(var)).or_insert(0) += 1;
+            }
+        }
+        let declare = |name: &str, count: usize| {
+            let ty = self.types.get(name).unwrap_or(&Type::U64);
+            format!("let {}{}: {};", if count > 1 { "mut " } else { "" }, name, ty)
+        };
+        let locals = variables.locals().iter().map(|name| declare(name, assignments.get(name).copied().unwrap_or(0)));
+        let slots = self.slots.iter().map(|(&offset, &stores)| declare(&slot_name(offset), stores));
+        locals.chain(slots).collect()
+    }
+
+    /// Rendering of an access through a pointer of type `ty`
+    fn access(ty: &Type, base: &str, offset: i64, size: MemSize, layouts: &Layouts) -> Option<String> {
+        let field = |offset| {
+            let (field, ty) = account_field(offset)?;
+            (ty == Type::int(size)).then(|| field.name())
+        };
+        match ty {
+            Type::Ref { name: "AccountInfo", .. } => Some(format!("{}.{}", base, field(offset)?)),
+            Type::Ref { name: "Input", .. } if offset == 0 && size == MemSize::U64 => {
+                Some(format!("{}.num_accounts", base))
+            }
+            Type::Ref { name: "Input", .. } => {
+                Some(format!("{}.accounts[0].{}", base, field(offset - FIRST_ACCOUNT_OFFSET)?))
+            }
+            Type::Ptr { pointee, .. } => match **pointee {
+                Type::Layout(id) => Some(format!("{}.{}", base, layouts.field(id, offset, size)?)),
+                ref pointee if offset == 0 && *pointee == Type::int(size) => Some(format!("*{}", base)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Naming of variables that renders accesses through typed pointers as
+/// fields and dereferences
+pub struct TypedNames<'a> {
+    pub variables: &'a Variables,
+    pub types: &'a Types,
+    pub layouts: &'a Layouts,
+}
+
+impl Naming for TypedNames<'_> {
+    fn name(&self, var: Var) -> String {
+        self.variables.name(var)
+    }
+
+    fn int_type(&self, var: Var) -> Option<String> {
+        match self.types.get(&self.variables.name(var))? {
+            ty @ Type::Int { .. } => Some(ty.to_string()),
+            _ => None,
+        }
+    }
+
+    fn access(&self, base: Var, offset: i64, size: MemSize) -> Option<String> {
+        let base = self.variables.name(base);
+        Types::access(self.types.get(&base)?, &base, offset, size, self.layouts)
+    }
+}
+
+/// Evidence collection for one function
+struct Inference<'a> {
+    variables: &'a Variables,
+    types: Types,
+    /// Variables compared as signed
+    compared_signed: HashSet<String>,
+}
+
+impl Inference<'_> {
+    fn constrain(&mut self, var: Var, ty: Type) {
+        self.constrain_name(self.variables.name(var), ty);
+    }
+
+    fn constrain_name(&mut self, name: String, ty: Type) {
+        let merged = match self.types.types.remove(&name) {
+            Some(known) => known.merge(ty),
+            None => ty,
+        };
+        self.types.types.insert(name, merged);
+    }
+
+    /// Constrain the value of `expr`, or the slot it points to when it is a
+    /// stack address and `ty` a pointer
+    fn constrain_expr(&mut self, expr: &Expr, ty: Type) {
+        match (expr, expr.frame_slot()) {
+            (Expr::Var(var), _) => self.constrain(*var, ty),
+            (_, Some(offset)) => {
+                if let Some(pointee) = ty.pointee() {
+                    self.slots_touched(offset, 0);
+                    self.constrain_name(slot_name(offset), pointee);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record a stack slot and `stores` writes to it
+    fn slots_touched(&mut self, offset: u64, stores: usize) {
+        *self.types.slots.entry(offset).or_insert(0) += stores;
+    }
+
+    /// Type of the value of `expr`, when there is evidence for it
+    fn type_of(&self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Var(var) => self.types.get(&self.variables.name(*var)).cloned(),
+            Expr::Load { size, addr } => {
+                let field = addr.base_offset().and_then(|(base, offset)| {
+                    let pointer = self.types.get(&self.variables.name(base))?;
+                    let (_, ty) = match pointer {
+                        Type::Ref { name: "AccountInfo", .. } => account_field(offset)?,
+                        Type::Ref { name: "Input", .. } => account_field(offset - FIRST_ACCOUNT_OFFSET)?,
+                        _ => return None,
+                    };
+                    Some(ty)
+                });
+                Some(field.filter(|ty| *ty == Type::int(*size)).unwrap_or(Type::int(*size)))
+            }
+            Expr::Pubkey(_) => Some(Type::reference("Pubkey")),
+            Expr::Trunc { size, .. } | Expr::Packet { size, .. } => Some(Type::int(*size)),
+            Expr::Binary { op: BinaryOp::Arsh, width, .. } => Some(Type::signed(width.bits())),
+            Expr::Binary { width: Width::W32, .. } | Expr::Unary { width: Width::W32, .. } => {
+                Some(Type::int(MemSize::U32))
+            }
+            Expr::Binary { op: BinaryOp::Add | BinaryOp::Sub, .. } if expr.frame_slot().is_none() => {
+                let (base, offset) = expr.base_offset()?;
+                self.types.get(&self.variables.name(base))?.offset(offset)
+            }
+            _ => None,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        for expr in stmt.exprs() {
+            self.expr(expr);
+        }
+        match stmt {
+            Stmt::Assign { dst, expr } => {
+                if let Some(ty) = self.type_of(expr) {
+                    self.constrain(*dst, ty);
+                }
+            }
+            Stmt::Store { size, addr, value } => {
+                self.deref(addr, *size, true);
+                if let Expr::Var(var) = value {
+                    self.constrain(*var, Type::int(*size));
+                }
+                if let Some(offset) = addr.frame_slot() {
+                    self.slots_touched(offset, 1);
+                    let ty = self.type_of(value).map_or(Type::int(*size), |ty| ty.merge(Type::int(*size)));
+                    self.constrain_name(slot_name(offset), ty);
+                }
+            }
+            Stmt::Call { callee: Callee::Syscall { hash, .. }, args, ret, .. } => {
+                let Some(signature) = syscall_hash_signature(*hash) else {
+                    return;
+                };
+                let mut args = args.iter();
+                for (param, kind) in signature.params {
+                    let Some(pointer) = args.next() else { break };
+                    let length = if kind.registers() > 1 { args.next() } else { None };
+                    let ty = match kind {
+                        ArgKind::U64 => Type::U64,
+                        ArgKind::Pubkey => Type::reference("Pubkey"),
+                        ArgKind::Ptr => match syscall_pointee(signature.name, param) {
+                            Some(name) => Type::Ref { name, mutable: true },
+                            None => Type::pointer(Type::int(MemSize::U8), true),
+                        },
+                        ArgKind::Str | ArgKind::Bytes => Type::pointer(Type::int(MemSize::U8), false),
+                        ArgKind::Slices => Type::reference("[&[u8]]"),
+                    };
+                    // Slots a syscall writes through are assigned by it
+                    if let (ArgKind::Ptr, Some(offset)) = (kind, pointer.frame_slot()) {
+                        self.slots_touched(offset, 1);
+                    }
+                    self.constrain_expr(pointer, ty);
+                    if let Some(length) = length {
+                        self.constrain_expr(length, Type::U64);
+                    }
+                }
+                if let Some(ret) = ret.filter(|_| signature.returns) {
+                    self.constrain(ret, Type::U64);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Collect evidence from the subexpressions of `expr`
+    fn expr(&mut self, expr: &Expr) {
+        for child in expr.children() {
+            self.expr(child);
+        }
+        match expr {
+            Expr::Load { size, addr } => {
+                self.deref(addr, *size, false);
+                if let Some(offset) = addr.frame_slot() {
+                    self.slots_touched(offset, 0);
+                    self.constrain_name(slot_name(offset), Type::int(*size));
+                }
+            }
+            Expr::Compare { cond, lhs, rhs } if cond.is_signed() => {
+                for operand in [lhs, rhs] {
+                    if let Expr::Var(var) = **operand {
+                        self.compared_signed.insert(self.variables.name(var));
+                    }
+                }
+            }
+            Expr::Binary { op: BinaryOp::Arsh, width, lhs, .. } => {
+                if let Expr::Var(var) = **lhs {
+                    self.constrain(var, Type::signed(width.bits()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record a `size`-byte access through the pointer in `addr`
+    fn deref(&mut self, addr: &Expr, size: MemSize, mutable: bool) {
+        if addr.frame_slot().is_some() {
+            return;
+        }
+        if let Some((base, offset)) = addr.base_offset() {
+            let widest = self.types.accesses.entry(self.variables.name(base)).or_default().entry(offset).or_insert(size);
+            if size.bits() > widest.bits() {
+                *widest = size;
+            }
+            let pointee = if offset == 0 { Type::int(size) } else { Type::int(MemSize::U8) };
+            self.constrain(base, Type::pointer(pointee, mutable));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use crate::decompiler::prune::eliminate_dead_code;
+    use crate::decompiler::simplify::simplify;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+
+    #[test]
+    fn test_infer_types() {
+        let program: Vec<u8> = [
+            insn(0xbf, 6, 1, 0, 0),     // 0x00: mov r6, r1
+            insn(0x07, 6, 0, 0, 8),     // 0x08: add r6, 8
+            insn(0x79, 7, 6, 72, 0),    // 0x10: ldxdw r7, [r6+72]
+            insn(0x71, 8, 6, 1, 0),     // 0x18: ldxb r8, [r6+1]
+            insn(0x79, 9, 2, 0, 0),     // 0x20: ldxdw r9, [r2]
+            insn(0xc7, 9, 0, 0, 1),     // 0x28: arsh r9, 1
+            insn(0x7b, 10, 7, -8, 0),   // 0x30: stxdw [r10-8], r7
+            insn(0x6d, 9, 8, 1, 0),     // 0x38: jsgt r9, r8, 0x48
+            insn(0x7b, 6, 9, 80, 0),    // 0x40: stxdw [r6+80], r9
+            insn(0xbf, 0, 7, 0, 0),     // 0x48: mov r0, r7
+            insn(0x95, 0, 0, 0, 0),     // 0x50: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut function = SsaFunction::build(&cfg, &ir, 0);
+        simplify(&mut function);
+        eliminate_dead_code(&mut function, false);
+        let variables = Variables::recover(&function);
+        let types = Types::infer(&function, &variables, &Types::entry_params());
+
+        assert_eq!(
+            types.declarations(&function, &variables),
+            vec![
+                "let var1: &AccountInfo;",
+                "let var2: u64;",
+                "let var3: u8;",
+                "let var4: i64;",
+                "let local_0x8: u64;",
+            ]
+        );
+        assert_eq!(types.get("arg2").map(|t| t.to_string()), Some("*const u64".to_string()));
+        let names = TypedNames {
+            variables: &variables,
+            types: &types,
+            layouts: &Layouts::default(),
+        };
+        let rendered: Vec<String> = function.blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&names)).collect();
+        assert_eq!(rendered[0], "var1 = arg1 + 8;");
+        assert_eq!(rendered[1], "var2 = var1.lamports;");
+        assert!(rendered.contains(&"var1.data_len = var4;".to_string()), "{:?}", rendered);
+        // The declared type replaces the cast back to u64, and the byte
+        // keeps its width until the signed compare widens it
+        assert_eq!(rendered[3], "var4 = (*arg2 as i64) >> 1;");
+        assert_eq!(rendered[5], "if var4 > (var3 as i64) { goto 0x48; }");
+    }
+
+    #[test]
+    fn test_compare_casts_follow_declarations() {
+        let program: Vec<u8> = [
+            insn(0x79, 2, 1, 0, 0),     // 0x00: ldxdw r2, [r1]
+            insn(0xc7, 2, 0, 0, 1),     // 0x08: arsh r2, 1
+            insn(0x65, 2, 0, 2, 0),     // 0x10: jsgt r2, 0, 0x28
+            insn(0x55, 2, 0, 1, -1),    // 0x18: jne r2, -1, 0x28
+            insn(0xb7, 0, 0, 0, 1),     // 0x20: mov r0, 1
+            insn(0x95, 0, 0, 0, 0),     // 0x28: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let mut function = SsaFunction::build(&cfg, &ir, 0);
+        simplify(&mut function);
+        eliminate_dead_code(&mut function, false);
+        let variables = Variables::recover(&function);
+        let types = Types::infer(&function, &variables, &BTreeMap::new());
+        let names = TypedNames {
+            variables: &variables,
+            types: &types,
+            layouts: &Layouts::default(),
+        };
+        let rendered: Vec<String> = function.blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&names)).collect();
+
+        // A signed variable is only cast for the unsigned compare
+        assert!(types.declarations(&function, &variables).contains(&"let var1: i64;".to_string()));
+        assert!(rendered.contains(&"if var1 > 0 { goto 0x28; }".to_string()), "{:?}", rendered);
+        assert!(
+            rendered.contains(&"if (var1 as u64) != 0xffffffffffffffff { goto 0x28; }".to_string()),
+            "{:?}",
+            rendered
+        );
+    }
+}
@@ -0,0 +1,161 @@
+// Variable recovery over SSA form
+//
+// Versions of a register joined by phis form one live range and become one
+// local; versions of a register never hold different values at the same
+// time, so each range can be named independently. Incoming r1-r5 are the
+// arguments and the r0 ranges that are returned are the function result.
+
+use crate::decompiler::ssa::{Expr, SsaFunction, Stmt, Var};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Names of the variables of a function
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    names: HashMap<Var, String>,
+    /// Names of the locals defined in the function, in order of definition
+    locals: Vec<String>,
+}
+
+impl Variables {
+    /// Split `function` into live ranges and name each one
+    pub fn recover(function: &SsaFunction) -> Self {
+        let mut ranges = UnionFind::default();
+        for phi in function.blocks.iter().flat_map(|b| &b.phis) {
+            for (_, arg) in &phi.args {
+                ranges.union(phi.dst, *arg);
+            }
+        }
+
+        // Variables in order of definition, each range named after its first
+        let mut order: Vec<Var> = Vec::new();
+        let mut returned: Vec<Var> = Vec::new();
+        for block in &function.blocks {
+            order.extend(block.phis.iter().map(|phi| phi.dst));
+            for stmt in &block.stmts {
+                order.extend(stmt.uses().into_iter().filter(|var| var.version == 0));
+                order.extend(stmt.def());
+                if let Stmt::Return { value: Some(Expr::Var(var)) } = stmt {
+                    returned.push(*var);
+                }
+            }
+        }
+        let mut range_names: HashMap<Var, String> = HashMap::new();
+        for var in returned.into_iter().filter(|var| var.reg == 0) {
+            range_names.insert(ranges.find(var), "result".to_string());
+        }
+        let mut inputs: HashSet<Var> = HashSet::new();
+        for var in order.iter().filter(|var| var.version == 0) {
+            inputs.insert(ranges.find(*var));
+            let name = match var.reg {
+                1..=5 => format!("arg{}", var.reg),
+                reg => format!("r{}", reg),
+            };
+            range_names.insert(ranges.find(*var), name);
+        }
+
+        let mut names = HashMap::new();
+        let mut locals: Vec<String> = Vec::new();
+        let mut count = 0;
+        for var in order {
+            let range = ranges.find(var);
+            let name = range_names.entry(range).or_insert_with(|| {
+                count += 1;
+                format!("var{}", count)
+            });
+            if !inputs.contains(&range) && !locals.contains(name) {
+                locals.push(name.clone());
+            }
+            names.insert(var, name.clone());
+        }
+        Variables { names, locals }
+    }
+
+    /// Names of the locals defined in the function, in order of definition
+    pub fn locals(&self) -> &[String] {
+        &self.locals
+    }
+
+    /// Name of the local holding `var`
+    pub fn name(&self, var: Var) -> String {
+        self.names.get(&var).cloned().unwrap_or_else(|| format!("r{}", var.reg))
+    }
+}
+
+/// Disjoint sets, each represented by its smallest member
+#[derive(Debug, Clone)]
+pub struct UnionFind<T> {
+    parent: HashMap<T, T>,
+}
+
+impl<T> Default for UnionFind<T> {
+    fn default() -> Self {
+        UnionFind { parent: HashMap::new() }
+    }
+}
+
+impl<T: Copy + Eq + Hash + Ord> UnionFind<T> {
+    /// Representative of the set containing `item`
+    pub fn find(&mut self, item: T) -> T {
+        let parent = *self.parent.get(&item).unwrap_or(&item);
+        if parent == item {
+            return item;
+        }
+        let root = self.find(parent);
+        self.parent.insert(item, root);
+        root
+    }
+
+    /// Merge the sets containing `a` and `b`
+    pub fn union(&mut self, a: T, b: T) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent.insert(a.max(b), a.min(b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompiler::tests::insn;
+    use crate::decompiler::lifter::lift_to_ir;
+    use sbpf_common::cfg::ControlFlowGraph;
+    use sbpf_common::instruction::InstructionDecoder;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_split_and_name() {
+        let program: Vec<u8> = [
+            insn(0x79, 3, 1, 0, 0),     // 0x00: ldxdw r3, [r1]
+            insn(0x7b, 10, 3, -24, 0),  // 0x08: stxdw [r10-24], r3
+            insn(0x79, 3, 1, 8, 0),     // 0x10: ldxdw r3, [r1+8]
+            insn(0xb7, 0, 0, 0, 0),     // 0x18: mov r0, 0
+            insn(0x15, 3, 0, 1, 0),     // 0x20: jeq r3, 0, 0x30
+            insn(0x79, 0, 10, -24, 0),  // 0x28: ldxdw r0, [r10-24]
+            insn(0x95, 0, 0, 0, 0),     // 0x30: exit
+        ]
+        .concat();
+        let instructions = InstructionDecoder::new(None).decode_all(&program);
+        let cfg = ControlFlowGraph::build(&instructions, 0);
+        let ir = lift_to_ir(&instructions, &cfg, &BTreeMap::new(), &BTreeMap::new(), &BTreeMap::new());
+        let function = SsaFunction::build(&cfg, &ir, 0);
+        let variables = Variables::recover(&function);
+        let name = |var: Var| variables.name(var);
+        let rendered: Vec<String> = function.blocks.iter().flat_map(|b| &b.stmts).map(|s| s.render(&name)).collect();
+        // The two ranges of r3 are separate locals
+        assert_eq!(
+            rendered,
+            vec![
+                "var1 = *(arg1 as *const u64);",
+                "local_0x18 = var1;",
+                "var2 = *((arg1 + 8) as *const u64);",
+                "result = 0;",
+                "if var2 == 0 { goto 0x30; }",
+                "result = local_0x18;",
+                "return result;",
+            ]
+        );
+        assert_eq!(variables.locals(), ["var1", "var2", "result"]);
+    }
+}
@@ -4,7 +4,8 @@ mod output;
 use anyhow::{Context, Result};
 use clap::Parser;
 use sbpf_common::binary::load_binary;
-use crate::decompiler::decompile;
+use sbpf_common::signatures::SignatureDb;
+use crate::decompiler::{decompile, DecompileOptions};
 
 /// SBPF Decompiler
 /// 
@@ -23,6 +24,19 @@ struct Args {
     #[arg(short, long)]
     output: Option<String>,
     
+    /// Library signature file used to omit statically linked functions
+    /// (may be repeated)
+    #[arg(short, long)]
+    signatures: Vec<String>,
+    
+    /// Print each function in SSA form instead of pseudocode
+    #[arg(long)]
+    dump_ssa: bool,
+    
+    /// Keep dead statements and unreachable blocks as commented-out listings
+    #[arg(long)]
+    keep_pruned: bool,
+    
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -38,11 +52,20 @@ fn main() -> Result<()> {
     let binary = load_binary(&args.binary)
         .with_context(|| format!("Failed to load binary: {}", args.binary))?;
     
+    let mut options = DecompileOptions {
+        dump_ssa: args.dump_ssa,
+        keep_pruned: args.keep_pruned,
+        ..Default::default()
+    };
+    for path in &args.signatures {
+        options.signatures.merge(&SignatureDb::load(path)?);
+    }
+    
     if args.verbose {
         eprintln!("Decompiling binary...");
     }
     
-    let pseudocode = decompile(&binary);
+    let pseudocode = decompile(&binary, &options);
     
     if let Some(output_path) = args.output {
         std::fs::write(&output_path, &pseudocode)